@@ -0,0 +1,773 @@
+use chrono::{DateTime, Duration, Utc};
+use clap::ValueEnum;
+use oauth2::basic::BasicClient;
+use oauth2::{
+    AuthUrl, AuthorizationCode, ClientId, ClientSecret, CsrfToken, HttpRequest, HttpResponse,
+    PkceCodeChallenge, RedirectUrl, Scope, TokenResponse, TokenUrl,
+};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+
+use crate::endpoints::{self, Endpoints};
+use crate::http_trace;
+use crate::output;
+use crate::redact;
+use crate::simulator;
+use crate::{expand_tilde, is_interactive, success, verbose};
+
+const DEFAULT_TOKEN_FILE: &str = "~/.youtube_tokens.json";
+const KEYRING_SERVICE: &str = "youtube-scheduler";
+const KEYRING_ACCOUNT: &str = "oauth-tokens";
+/// Refresh token read by the `env` token store
+pub const REFRESH_TOKEN_VAR: &str = "YT_UPLOAD_REFRESH_TOKEN";
+const CLIENT_ID_VAR: &str = "YT_UPLOAD_CLIENT_ID";
+const CLIENT_SECRET_VAR: &str = "YT_UPLOAD_CLIENT_SECRET";
+/// Variables taking precedence over the OAuth config and token store
+pub const CREDENTIAL_VARS: [&str; 3] = [REFRESH_TOKEN_VAR, CLIENT_ID_VAR, CLIENT_SECRET_VAR];
+
+/// OAuth client credentials of a Google Cloud project.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OAuthConfig {
+    client_id: String,
+    client_secret: String,
+    redirect_uri: String,
+}
+
+/// Client credentials from `YT_UPLOAD_CLIENT_ID` and
+/// `YT_UPLOAD_CLIENT_SECRET`, if both are set.
+pub fn oauth_config_from_env() -> Option<OAuthConfig> {
+    let client_id = std::env::var(CLIENT_ID_VAR).ok()?;
+    let client_secret = std::env::var(CLIENT_SECRET_VAR).ok()?;
+    Some(OAuthConfig {
+        client_id,
+        client_secret,
+        redirect_uri: "http://localhost".to_string(),
+    })
+}
+
+/// Reads the client credentials from `config_path` unless they are given
+/// through the environment.
+pub fn load_oauth_config(config_path: &str) -> Result<OAuthConfig, Box<dyn std::error::Error>> {
+    // The simulator accepts any credentials
+    if simulator::base_url().is_some() {
+        return Ok(OAuthConfig {
+            client_id: "simulated".to_string(),
+            client_secret: "simulated-secret".to_string(),
+            redirect_uri: "http://localhost".to_string(),
+        });
+    }
+    if let Some(config) = oauth_config_from_env() {
+        return Ok(config);
+    }
+    let expanded_path = expand_tilde(config_path);
+    let content = fs::read_to_string(&expanded_path).map_err(|e| {
+        format!(
+            "Failed to read OAuth config from '{}': {}",
+            expanded_path, e
+        )
+    })?;
+    let config: OAuthConfig = serde_json::from_str(&content)
+        .map_err(|e| format!("Failed to parse OAuth config: {}", e))?;
+    Ok(config)
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Tokens {
+    pub access_token: String,
+    pub refresh_token: Option<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// Default of `--token-refresh-margin`
+pub const DEFAULT_REFRESH_MARGIN_MINUTES: i64 = 5;
+/// Lifetime of Google access tokens, longer margins refresh every time
+pub const ACCESS_TOKEN_LIFETIME_MINUTES: i64 = 60;
+
+impl Tokens {
+    /// Whether the access token stays valid for at least a few minutes.
+    pub fn is_fresh(&self) -> bool {
+        self.is_fresh_for(Duration::minutes(DEFAULT_REFRESH_MARGIN_MINUTES))
+    }
+
+    /// Whether the access token stays valid for at least `margin`.
+    pub fn is_fresh_for(&self, margin: Duration) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| expires_at > Utc::now() + margin)
+    }
+
+    /// Keeps both tokens out of all output, see `redact`.
+    fn redact(&self) {
+        redact::register(&self.access_token);
+        if let Some(refresh_token) = &self.refresh_token {
+            redact::register(refresh_token);
+        }
+    }
+}
+
+/// Where tokens are kept between runs.
+pub trait TokenStore: Send + Sync {
+    /// The stored tokens, or `None` when nothing was stored yet.
+    fn load(&self) -> Result<Option<Tokens>, Box<dyn std::error::Error>>;
+    fn save(&self, tokens: &Tokens) -> Result<(), Box<dyn std::error::Error>>;
+    fn delete(&self) -> Result<(), Box<dyn std::error::Error>>;
+}
+
+impl<T: TokenStore + ?Sized> TokenStore for Box<T> {
+    fn load(&self) -> Result<Option<Tokens>, Box<dyn std::error::Error>> {
+        (**self).load()
+    }
+
+    fn save(&self, tokens: &Tokens) -> Result<(), Box<dyn std::error::Error>> {
+        (**self).save(tokens)
+    }
+
+    fn delete(&self) -> Result<(), Box<dyn std::error::Error>> {
+        (**self).delete()
+    }
+}
+
+/// Token store selected with `--token-store`.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TokenBackend {
+    /// JSON file in the home directory
+    File,
+    /// OS keychain through secret-tool (Linux) or security (macOS)
+    Keyring,
+    /// Refresh token from YT_UPLOAD_REFRESH_TOKEN, nothing is written
+    Env,
+}
+
+impl TokenBackend {
+    pub fn store(self) -> Box<dyn TokenStore> {
+        match self {
+            TokenBackend::File => Box::new(FileTokenStore::default()),
+            TokenBackend::Keyring => Box::new(KeyringTokenStore::default()),
+            TokenBackend::Env => Box::new(EnvTokenStore::new(REFRESH_TOKEN_VAR)),
+        }
+    }
+}
+
+/// Tokens in a JSON file, `~/.youtube_tokens.json` by default.
+pub struct FileTokenStore {
+    path: PathBuf,
+}
+
+impl FileTokenStore {
+    pub fn new(path: &str) -> Self {
+        Self {
+            path: PathBuf::from(expand_tilde(path)),
+        }
+    }
+}
+
+impl Default for FileTokenStore {
+    fn default() -> Self {
+        Self::new(DEFAULT_TOKEN_FILE)
+    }
+}
+
+impl TokenStore for FileTokenStore {
+    fn load(&self) -> Result<Option<Tokens>, Box<dyn std::error::Error>> {
+        match fs::read_to_string(&self.path) {
+            Ok(content) => Ok(Some(serde_json::from_str(&content)?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn save(&self, tokens: &Tokens) -> Result<(), Box<dyn std::error::Error>> {
+        fs::write(&self.path, serde_json::to_string_pretty(tokens)?)?;
+        Ok(())
+    }
+
+    fn delete(&self) -> Result<(), Box<dyn std::error::Error>> {
+        match fs::remove_file(&self.path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Tokens as a JSON secret in the OS keychain, using the `secret-tool`
+/// command of libsecret on Linux and `security` on macOS.
+pub struct KeyringTokenStore {
+    service: String,
+    account: String,
+}
+
+impl KeyringTokenStore {
+    pub fn new(service: &str, account: &str) -> Self {
+        Self {
+            service: service.to_string(),
+            account: account.to_string(),
+        }
+    }
+
+    /// Fails on systems with neither tool rather than trying secret-tool
+    /// where it does not exist.
+    fn check_platform() -> Result<(), Box<dyn std::error::Error>> {
+        if cfg!(any(target_os = "linux", target_os = "macos")) {
+            return Ok(());
+        }
+        Err(format!(
+            "--token-store keyring is only supported on Linux and macOS, not {}",
+            std::env::consts::OS
+        )
+        .into())
+    }
+
+    /// Runs `command` with `input` on stdin, keeping secrets off the
+    /// command line where other users could read them. Returns what the
+    /// command wrote to stderr.
+    fn run_with_input(
+        command: &mut Command,
+        input: &str,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let program = command.get_program().to_string_lossy().to_string();
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Failed to run {}: {}", program, e))?;
+        child
+            .stdin
+            .take()
+            .ok_or_else(|| format!("{} has no stdin", program))?
+            .write_all(input.as_bytes())?;
+        let output = child.wait_with_output()?;
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        if !output.status.success() {
+            return Err(format!("{} failed: {}", program, stderr).into());
+        }
+        Ok(stderr)
+    }
+
+    fn run(&self, command: &mut Command) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        let program = command.get_program().to_string_lossy().to_string();
+        let output = command
+            .output()
+            .map_err(|e| format!("Failed to run {}: {}", program, e))?;
+        if output.status.success() {
+            return Ok(Some(
+                String::from_utf8_lossy(&output.stdout).trim().to_string(),
+            ));
+        }
+        // Both tools exit with an error without output for a missing item
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        if stderr.is_empty() || stderr.contains("could not be found") {
+            return Ok(None);
+        }
+        Err(format!("{} failed: {}", program, stderr).into())
+    }
+}
+
+/// Quotes `value` as one argument for the interactive mode of `security`.
+fn security_quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+impl Default for KeyringTokenStore {
+    fn default() -> Self {
+        Self::new(KEYRING_SERVICE, KEYRING_ACCOUNT)
+    }
+}
+
+impl TokenStore for KeyringTokenStore {
+    fn load(&self) -> Result<Option<Tokens>, Box<dyn std::error::Error>> {
+        Self::check_platform()?;
+        let secret = if cfg!(target_os = "macos") {
+            self.run(Command::new("security").args([
+                "find-generic-password",
+                "-s",
+                &self.service,
+                "-a",
+                &self.account,
+                "-w",
+            ]))?
+        } else {
+            self.run(Command::new("secret-tool").args([
+                "lookup",
+                "service",
+                &self.service,
+                "account",
+                &self.account,
+            ]))?
+        };
+        match secret.filter(|secret| !secret.is_empty()) {
+            Some(secret) => Ok(Some(serde_json::from_str(&secret)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn save(&self, tokens: &Tokens) -> Result<(), Box<dyn std::error::Error>> {
+        Self::check_platform()?;
+        let secret = serde_json::to_string(tokens)?;
+        if cfg!(target_os = "macos") {
+            // security takes the password as an argument or from a terminal
+            // prompt, so the command goes through its interactive mode on
+            // stdin. Hex needs no quoting
+            let hex: String = secret.bytes().map(|b| format!("{:02x}", b)).collect();
+            let command = format!(
+                "add-generic-password -U -s {} -a {} -X {}\n",
+                security_quote(&self.service),
+                security_quote(&self.account),
+                hex
+            );
+            let stderr = Self::run_with_input(Command::new("security").arg("-i"), &command)?;
+            // The interactive mode does not fail when its commands do
+            if !stderr.is_empty() {
+                return Err(format!("security failed: {}", stderr).into());
+            }
+            return Ok(());
+        }
+
+        // secret-tool reads the secret from stdin
+        Self::run_with_input(
+            Command::new("secret-tool")
+                .args(["store", "--label", &self.service])
+                .args(["service", &self.service, "account", &self.account]),
+            &secret,
+        )?;
+        Ok(())
+    }
+
+    fn delete(&self) -> Result<(), Box<dyn std::error::Error>> {
+        Self::check_platform()?;
+        if cfg!(target_os = "macos") {
+            self.run(Command::new("security").args([
+                "delete-generic-password",
+                "-s",
+                &self.service,
+                "-a",
+                &self.account,
+            ]))?;
+        } else {
+            self.run(Command::new("secret-tool").args([
+                "clear",
+                "service",
+                &self.service,
+                "account",
+                &self.account,
+            ]))?;
+        }
+        Ok(())
+    }
+}
+
+/// A refresh token injected through an environment variable, e.g. by a CI
+/// secrets manager. Access tokens minted from it are only kept in memory.
+pub struct EnvTokenStore {
+    var: String,
+    minted: Mutex<Option<Tokens>>,
+}
+
+impl EnvTokenStore {
+    pub fn new(var: &str) -> Self {
+        Self {
+            var: var.to_string(),
+            minted: Mutex::new(None),
+        }
+    }
+}
+
+impl TokenStore for EnvTokenStore {
+    fn load(&self) -> Result<Option<Tokens>, Box<dyn std::error::Error>> {
+        if let Some(tokens) = self.minted.lock().unwrap().clone() {
+            return Ok(Some(tokens));
+        }
+        match std::env::var(&self.var) {
+            Ok(refresh_token) if !refresh_token.trim().is_empty() => Ok(Some(Tokens {
+                access_token: String::new(),
+                refresh_token: Some(refresh_token.trim().to_string()),
+                expires_at: None,
+            })),
+            _ => Ok(None),
+        }
+    }
+
+    fn save(&self, tokens: &Tokens) -> Result<(), Box<dyn std::error::Error>> {
+        *self.minted.lock().unwrap() = Some(tokens.clone());
+        Ok(())
+    }
+
+    fn delete(&self) -> Result<(), Box<dyn std::error::Error>> {
+        *self.minted.lock().unwrap() = None;
+        Ok(())
+    }
+}
+
+/// Tokens kept only for the lifetime of the process.
+#[derive(Default)]
+pub struct MemoryTokenStore {
+    tokens: Mutex<Option<Tokens>>,
+}
+
+impl MemoryTokenStore {
+    /// A store holding just `refresh_token`, so the first access token is
+    /// minted from it without any interaction.
+    pub fn with_refresh_token(refresh_token: &str) -> Self {
+        Self {
+            tokens: Mutex::new(Some(Tokens {
+                access_token: String::new(),
+                refresh_token: Some(refresh_token.to_string()),
+                expires_at: None,
+            })),
+        }
+    }
+}
+
+impl TokenStore for MemoryTokenStore {
+    fn load(&self) -> Result<Option<Tokens>, Box<dyn std::error::Error>> {
+        Ok(self.tokens.lock().unwrap().clone())
+    }
+
+    fn save(&self, tokens: &Tokens) -> Result<(), Box<dyn std::error::Error>> {
+        *self.tokens.lock().unwrap() = Some(tokens.clone());
+        Ok(())
+    }
+
+    fn delete(&self) -> Result<(), Box<dyn std::error::Error>> {
+        *self.tokens.lock().unwrap() = None;
+        Ok(())
+    }
+}
+
+/// The stored grant is gone: its refresh token was revoked, expired or
+/// belongs to another client, and only authorizing again helps.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GrantRevoked(pub String);
+
+impl fmt::Display for GrantRevoked {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "The stored authorization was revoked or has expired ({}).\n  \
+             Suggestion: run once in a terminal to authorize again, finished uploads are skipped on the next run.",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for GrantRevoked {}
+
+/// The error of a rejected token refresh, `GrantRevoked` for `invalid_grant`.
+pub fn refresh_error(token_data: &serde_json::Value) -> Box<dyn std::error::Error> {
+    let reason = token_data["error_description"]
+        .as_str()
+        .or(token_data["error"].as_str())
+        .unwrap_or("no access token in response");
+    if token_data["error"] == "invalid_grant" {
+        return Box::new(GrantRevoked(reason.to_string()));
+    }
+    format!("Token refresh rejected: {}", reason).into()
+}
+
+/// How `Authenticator::access_token` got its token.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenSource {
+    Stored,
+    Refreshed,
+    Authorized,
+}
+
+impl fmt::Display for TokenSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenSource::Stored => write!(f, "stored"),
+            TokenSource::Refreshed => write!(f, "refreshed"),
+            TokenSource::Authorized => write!(f, "authorized"),
+        }
+    }
+}
+
+/// Google OAuth for installed apps, independent of any particular API.
+pub struct Authenticator {
+    client: Client,
+    oauth_client: BasicClient,
+    config: OAuthConfig,
+    scopes: Vec<String>,
+    store: Arc<dyn TokenStore>,
+    endpoints: Endpoints,
+    refresh_margin: Duration,
+}
+
+impl Authenticator {
+    pub fn new(
+        config: &OAuthConfig,
+        store: impl TokenStore + 'static,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let endpoints = endpoints::current();
+        let oauth_client = BasicClient::new(
+            ClientId::new(config.client_id.clone()),
+            Some(ClientSecret::new(config.client_secret.clone())),
+            AuthUrl::new(endpoints.auth_url.clone())?,
+            Some(TokenUrl::new(endpoints.token_url.clone())?),
+        )
+        .set_redirect_uri(RedirectUrl::new(config.redirect_uri.clone())?);
+        redact::register(&config.client_secret);
+
+        Ok(Self {
+            client: endpoints::client()?,
+            oauth_client,
+            config: config.clone(),
+            scopes: Vec::new(),
+            store: Arc::new(store),
+            endpoints,
+            refresh_margin: Duration::minutes(DEFAULT_REFRESH_MARGIN_MINUTES),
+        })
+    }
+
+    /// Requests an additional scope the next time the user authorizes.
+    /// Tokens stored before the scope was added have to be re-authorized.
+    pub fn add_scope(&mut self, scope: &str) {
+        if !self.scopes.iter().any(|s| s == scope) {
+            self.scopes.push(scope.to_string());
+        }
+    }
+
+    /// Refreshes stored access tokens expiring within `margin`.
+    pub fn set_refresh_margin(&mut self, margin: Duration) {
+        self.refresh_margin = margin;
+    }
+
+    /// Runs `task` on the token store off the runtime threads, as stores
+    /// read files and run secret-tool or security.
+    async fn with_store<T: Send + 'static>(
+        &self,
+        task: impl FnOnce(&dyn TokenStore) -> Result<T, Box<dyn std::error::Error>> + Send + 'static,
+    ) -> Result<T, Box<dyn std::error::Error>> {
+        let store = Arc::clone(&self.store);
+        tokio::task::spawn_blocking(move || task(store.as_ref()).map_err(|e| e.to_string()))
+            .await?
+            .map_err(Into::into)
+    }
+
+    async fn load_tokens(&self) -> Result<Option<Tokens>, Box<dyn std::error::Error>> {
+        self.with_store(|store| store.load()).await
+    }
+
+    async fn save_tokens(&self, tokens: &Tokens) -> Result<(), Box<dyn std::error::Error>> {
+        let tokens = tokens.clone();
+        self.with_store(move |store| store.save(&tokens)).await
+    }
+
+    /// Sends a token request of the oauth2 crate through the shared client,
+    /// so the configured endpoints, proxy and --trace-http apply to it.
+    async fn send_oauth(&self, request: HttpRequest) -> Result<HttpResponse, reqwest::Error> {
+        let response = http_trace::send(
+            self.client
+                .request(request.method, request.url)
+                .headers(request.headers)
+                .body(request.body),
+        )
+        .await?;
+        Ok(HttpResponse {
+            status_code: response.status(),
+            headers: response.headers().clone(),
+            body: response.bytes().await?.to_vec(),
+        })
+    }
+
+    /// A valid access token: the stored one while fresh, otherwise
+    /// refreshed, and as a last resort authorized interactively.
+    pub async fn access_token(&self) -> Result<(String, TokenSource), Box<dyn std::error::Error>> {
+        // The error is dropped right away, it isn't Send and mustn't be held
+        // across the awaits below
+        let stored = self.load_tokens().await.ok().flatten();
+        if let Some(tokens) = stored {
+            tokens.redact();
+            if tokens.is_fresh_for(self.refresh_margin) {
+                verbose!("Using existing valid token");
+                return Ok((tokens.access_token, TokenSource::Stored));
+            }
+            if let Some(refresh_token) = &tokens.refresh_token {
+                let refreshed = self.refresh(refresh_token).await.map_err(|e| {
+                    let revoked = e.downcast_ref::<GrantRevoked>().cloned();
+                    (revoked, e.to_string())
+                });
+                match refreshed {
+                    Ok(tokens) => {
+                        verbose!("Refreshed access token");
+                        return Ok((tokens.access_token, TokenSource::Refreshed));
+                    }
+                    // Without a terminal there is no one to authorize
+                    Err((Some(revoked), _)) if !is_interactive() => return Err(revoked.into()),
+                    Err((Some(revoked), _)) => {
+                        crate::warning!(
+                            "The stored authorization was revoked or has expired ({}), authorize again to continue",
+                            revoked.0
+                        );
+                    }
+                    Err((None, e)) => crate::warning!("Refreshing the access token failed: {}", e),
+                }
+            }
+        }
+
+        let tokens = self.obtain().await?;
+        Ok((tokens.access_token, TokenSource::Authorized))
+    }
+
+    /// The stored tokens without refreshing them, `None` before the first
+    /// authorization.
+    pub async fn stored_tokens(&self) -> Result<Option<Tokens>, Box<dyn std::error::Error>> {
+        self.load_tokens().await
+    }
+
+    /// Scopes granted to `access_token`, from Google's tokeninfo endpoint.
+    pub async fn granted_scopes(
+        &self,
+        access_token: &str,
+    ) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let response = http_trace::send(
+            self.client
+                .get(&self.endpoints.tokeninfo_url)
+                .query(&[("access_token", access_token)]),
+        )
+        .await?;
+        if !response.status().is_success() {
+            return Err(format!(
+                "tokeninfo rejected the access token ({})",
+                response.status()
+            )
+            .into());
+        }
+        let info: serde_json::Value = response.json().await?;
+        Ok(info["scope"]
+            .as_str()
+            .unwrap_or_default()
+            .split_whitespace()
+            .map(str::to_string)
+            .collect())
+    }
+
+    /// Scopes requested when authorizing.
+    pub fn scopes(&self) -> &[String] {
+        &self.scopes
+    }
+
+    /// Makes the next `access_token` refresh even when the stored access
+    /// token looks fresh, e.g. after the API rejected it.
+    pub async fn expire_access_token(&self) -> Result<(), Box<dyn std::error::Error>> {
+        self.with_store(|store| {
+            if let Some(mut tokens) = store.load()? {
+                tokens.expires_at = None;
+                store.save(&tokens)?;
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    /// Runs the interactive authorization flow and stores the new tokens.
+    pub async fn obtain(&self) -> Result<Tokens, Box<dyn std::error::Error>> {
+        if !is_interactive() {
+            return Err("Authorization required but stdin is not a terminal. \
+                        Run once interactively to store tokens."
+                .into());
+        }
+
+        // Generate PKCE challenge
+        let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
+
+        // Generate authorization URL
+        let (auth_url, _csrf_token) = self
+            .oauth_client
+            .authorize_url(CsrfToken::new_random)
+            .add_scopes(self.scopes.iter().cloned().map(Scope::new))
+            .set_pkce_challenge(pkce_challenge)
+            .url();
+
+        // Through output::print, so the prompt stays off stdout with --output json
+        output::print("Open this URL in your browser to authenticate:");
+        output::print(auth_url.as_str());
+        output::print("\nAfter authorization, you'll be redirected to your redirect URI.");
+        output::print("Copy the 'code' parameter from the redirect URL and paste it here:");
+
+        // Get authorization code from user
+        let auth_code = tokio::task::spawn_blocking(|| {
+            let mut line = String::new();
+            std::io::stdin().read_line(&mut line).map(|_| line)
+        })
+        .await??;
+        let auth_code = auth_code.trim();
+
+        // Exchange authorization code for access token
+        let token_result = self
+            .oauth_client
+            .exchange_code(AuthorizationCode::new(auth_code.to_string()))
+            .set_pkce_verifier(pkce_verifier)
+            .request_async(|request| self.send_oauth(request))
+            .await?;
+
+        let expires_at = token_result
+            .expires_in()
+            .map(|duration| Utc::now() + Duration::seconds(duration.as_secs() as i64));
+        let tokens = Tokens {
+            access_token: token_result.access_token().secret().clone(),
+            refresh_token: token_result.refresh_token().map(|t| t.secret().clone()),
+            expires_at,
+        };
+        tokens.redact();
+        self.save_tokens(&tokens).await?;
+
+        success!("Authentication successful!");
+        Ok(tokens)
+    }
+
+    /// Mints a new access token from `refresh_token` and stores it.
+    pub async fn refresh(&self, refresh_token: &str) -> Result<Tokens, Box<dyn std::error::Error>> {
+        let params = [
+            ("grant_type", "refresh_token"),
+            ("refresh_token", refresh_token),
+            ("client_id", &self.config.client_id),
+            ("client_secret", &self.config.client_secret),
+        ];
+
+        let response =
+            http_trace::send(self.client.post(&self.endpoints.token_url).form(&params)).await?;
+        let token_data: serde_json::Value = response.json().await?;
+
+        let access_token = match token_data["access_token"].as_str() {
+            Some(access_token) => access_token.to_string(),
+            None => return Err(refresh_error(&token_data)),
+        };
+        let expires_in = token_data["expires_in"].as_u64().unwrap_or(3600);
+
+        let tokens = Tokens {
+            access_token,
+            refresh_token: Some(refresh_token.to_string()),
+            expires_at: Some(Utc::now() + Duration::seconds(expires_in as i64)),
+        };
+        tokens.redact();
+        self.save_tokens(&tokens).await?;
+        Ok(tokens)
+    }
+
+    /// Revokes the stored grant at Google and forgets the tokens.
+    pub async fn revoke(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let stored = self.load_tokens().await?;
+        if let Some(tokens) = stored {
+            // Revoking the refresh token also invalidates its access tokens
+            let token = tokens.refresh_token.unwrap_or(tokens.access_token);
+            let response = http_trace::send(
+                self.client
+                    .post(&self.endpoints.revoke_url)
+                    .form(&[("token", token)]),
+            )
+            .await
+            .map_err(|e| e.to_string())?;
+            // 400 means the token was already invalid
+            if !response.status().is_success() && response.status() != 400 {
+                return Err(format!("Revoking the token failed ({})", response.status()).into());
+            }
+        }
+        self.with_store(|store| store.delete()).await
+    }
+}
@@ -0,0 +1,54 @@
+use chrono::{Datelike, Months, NaiveDate, Weekday};
+use std::collections::BTreeMap;
+
+/// Width of a day in the grid: the day number and a mark like `*3`
+const CELL_WIDTH: usize = 5;
+
+/// Month grids from the first to the last month with a publish on
+/// `dates`, weeks starting on Monday. Days with one publish are marked
+/// `*`, days with more `*N`.
+pub fn month_grid(dates: &[NaiveDate]) -> Vec<String> {
+    let mut counts: BTreeMap<NaiveDate, usize> = BTreeMap::new();
+    for date in dates {
+        *counts.entry(*date).or_default() += 1;
+    }
+    let (Some(first), Some(last)) = (counts.keys().next(), counts.keys().next_back()) else {
+        return Vec::new();
+    };
+
+    let mut lines = Vec::new();
+    let mut month = first.with_day(1).unwrap_or(*first);
+    while month <= *last {
+        let title = month.format("%B %Y").to_string();
+        lines.push(
+            format!("{:^width$}", title, width = 7 * CELL_WIDTH)
+                .trim_end()
+                .to_string(),
+        );
+        lines.push("Mo   Tu   We   Th   Fr   Sa   Su".to_string());
+
+        let mut week = " ".repeat(month.weekday().num_days_from_monday() as usize * CELL_WIDTH);
+        for day in month.iter_days().take_while(|d| d.month() == month.month()) {
+            let mark = match counts.get(&day) {
+                None => String::new(),
+                Some(1) => "*".to_string(),
+                Some(n) => format!("*{}", n),
+            };
+            week.push_str(&format!("{:>2}{:<3}", day.day(), mark));
+            if day.weekday() == Weekday::Sun {
+                lines.push(week.trim_end().to_string());
+                week.clear();
+            }
+        }
+        if !week.trim().is_empty() {
+            lines.push(week.trim_end().to_string());
+        }
+        lines.push(String::new());
+        let Some(next) = month.checked_add_months(Months::new(1)) else {
+            break;
+        };
+        month = next;
+    }
+    lines.push("* one publish, *N N publishes".to_string());
+    lines
+}
@@ -0,0 +1,157 @@
+use clap::ValueEnum;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::expand_tilde;
+use crate::ffmpeg;
+use crate::lint;
+use crate::spool;
+use crate::template::render_template;
+
+/// Where `--captions` gets the subtitles of each video.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CaptionSource {
+    /// `<video>.srt` and `<video>.<language>.srt` sidecars, videos without
+    /// one get no captions
+    Sidecar,
+    /// The sidecars, or an SRT written by the transcription command from the
+    /// `captions` section of the config file
+    Auto,
+    Off,
+}
+
+/// Transcription settings from the `captions` section of the config file.
+#[derive(Debug, Clone, Deserialize)]
+pub struct CaptionsConfig {
+    /// Program and arguments writing an SRT, run without a shell. `{video}`
+    /// is the video, `{audio}` a 16 kHz mono WAV of its sound, `{srt}` the
+    /// file to write and `{output}` the same without `.srt`, the way
+    /// whisper.cpp's `-of` takes it
+    pub transcribe_command: Vec<String>,
+    /// Language of generated captions for videos without one in their
+    /// metadata
+    #[serde(default = "default_language")]
+    pub language: String,
+}
+
+fn default_language() -> String {
+    "en".to_string()
+}
+
+/// A caption file found next to a video.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sidecar {
+    pub path: PathBuf,
+    /// From the file name, e.g. `de` for `ep1.de.srt`, `None` for `ep1.srt`
+    pub language: Option<String>,
+}
+
+/// Caption sidecars of `video`: `ep1.srt` and `ep1.<language>.srt` for
+/// `ep1.mkv`, sorted by language with the untagged one first.
+pub fn find_sidecars(video: &str) -> Result<Vec<Sidecar>, Box<dyn std::error::Error>> {
+    let video = Path::new(video);
+    let dir = match video.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let stem = video.file_stem().unwrap_or_default().to_string_lossy();
+    let mut sidecars = Vec::new();
+    for entry in fs::read_dir(dir)
+        .map_err(|e| format!("Failed to read directory '{}': {}", dir.display(), e))?
+    {
+        let path = entry?.path();
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let Some(rest) = name.strip_prefix(stem.as_ref()) else {
+            continue;
+        };
+        let language = if rest.eq_ignore_ascii_case(".srt") {
+            None
+        } else {
+            match rest
+                .strip_prefix('.')
+                .and_then(|rest| rest.rsplit_once('.'))
+            {
+                Some((language, extension))
+                    if extension.eq_ignore_ascii_case("srt")
+                        && lint::check_language(language).is_empty() =>
+                {
+                    Some(language.to_string())
+                }
+                _ => continue,
+            }
+        };
+        sidecars.push(Sidecar { path, language });
+    }
+    sidecars.sort_by(|a, b| a.language.cmp(&b.language));
+    Ok(sidecars)
+}
+
+/// Runs the transcription command for `video` and returns the SRT it
+/// wrote. SRTs are cached like preprocessed videos, so re-runs do not
+/// transcribe again.
+pub fn transcribe(
+    video: &str,
+    config: &CaptionsConfig,
+    cache_dir: &Path,
+) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let Some(program) = config.transcribe_command.first() else {
+        return Err("The transcription command is empty".into());
+    };
+    let key = ffmpeg::cache_key(&[video], &config.transcribe_command.join(" "))?;
+    let stem = Path::new(video)
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy();
+    let output = cache_dir.join(format!("{}-{}", stem, key));
+    let srt = cache_dir.join(format!("{}-{}.srt", stem, key));
+    if spool::reuse(&srt) {
+        return Ok(srt);
+    }
+    fs::create_dir_all(cache_dir)?;
+
+    let audio = cache_dir.join(format!("{}-{}.wav", stem, key));
+    let mut values = HashMap::new();
+    values.insert("video", video.to_string());
+    values.insert("srt", srt.to_string_lossy().into_owned());
+    values.insert("output", output.to_string_lossy().into_owned());
+    values.insert("audio", audio.to_string_lossy().into_owned());
+    let wants_audio = config
+        .transcribe_command
+        .iter()
+        .any(|arg| arg.contains("{audio}"));
+    if wants_audio {
+        let mut args: Vec<OsString> = ["-i", video, "-vn", "-ac", "1", "-ar", "16000"]
+            .map(OsString::from)
+            .to_vec();
+        args.push(audio.clone().into());
+        ffmpeg::run_ffmpeg(&args)?;
+    }
+
+    let result = Command::new(expand_tilde(program))
+        .args(
+            config.transcribe_command[1..]
+                .iter()
+                .map(|arg| expand_tilde(&render_template(arg, &values))),
+        )
+        .output();
+    if wants_audio {
+        let _ = fs::remove_file(&audio);
+    }
+    let result = result.map_err(|e| format!("Failed to run {}: {}", program, e))?;
+    if !result.status.success() {
+        return Err(format!(
+            "{} failed: {}",
+            program,
+            String::from_utf8_lossy(&result.stderr).trim()
+        )
+        .into());
+    }
+    if !srt.exists() {
+        return Err(format!("{} did not write {}", program, srt.display()).into());
+    }
+    Ok(srt)
+}
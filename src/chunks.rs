@@ -0,0 +1,219 @@
+use clap::ValueEnum;
+use std::io::{self, SeekFrom};
+use tokio::fs::File;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio::sync::mpsc;
+
+/// Default size of resumable upload chunks
+pub const DEFAULT_CHUNK_SIZE: u64 = 8 << 20;
+/// Resumable upload chunks have to be a multiple of this
+pub const CHUNK_ALIGNMENT: u64 = 256 << 10;
+const MAX_CHUNK_SIZE: u64 = 1 << 30;
+
+/// How chunks get from disk into memory, selected with `--io-backend`.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum IoBackend {
+    /// read() calls into a fresh buffer per chunk
+    #[default]
+    Buffered,
+    /// Chunks copied straight out of a read-only memory map of the file,
+    /// saving the read calls. Falls back to buffered outside Unix
+    Mmap,
+}
+
+/// How an upload reads its file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkOptions {
+    /// Bytes per request
+    pub chunk_size: u64,
+    /// Chunks read ahead while the current one is sent
+    pub read_ahead: usize,
+    pub backend: IoBackend,
+}
+
+impl Default for ChunkOptions {
+    fn default() -> Self {
+        Self {
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            read_ahead: 1,
+            backend: IoBackend::default(),
+        }
+    }
+}
+
+impl ChunkOptions {
+    pub fn new(chunk_size: u64, read_ahead: usize) -> Result<Self, Box<dyn std::error::Error>> {
+        if chunk_size == 0 || !chunk_size.is_multiple_of(CHUNK_ALIGNMENT) {
+            return Err(format!(
+                "Chunk size must be a multiple of 256 KiB, got {} bytes",
+                chunk_size
+            )
+            .into());
+        }
+        if chunk_size > MAX_CHUNK_SIZE {
+            return Err("Chunk size must be at most 1 GiB".into());
+        }
+        Ok(Self {
+            chunk_size,
+            read_ahead,
+            backend: IoBackend::default(),
+        })
+    }
+
+    pub fn with_backend(mut self, backend: IoBackend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    /// Upper bound of the memory an upload holds: the chunks waiting, the
+    /// one being read, and the one being sent along with its request copy.
+    /// Uploads run one after another, so this is also the batch's ceiling.
+    pub fn memory_ceiling(&self) -> u64 {
+        self.chunk_size * (self.read_ahead as u64 + 3)
+    }
+}
+
+/// Reads a file chunk by chunk in a background task, ahead of the upload.
+/// Only `read_ahead` chunks wait in memory, so a slow network holds the
+/// disk back instead of filling up memory.
+pub struct ChunkReader {
+    chunks: mpsc::Receiver<io::Result<Vec<u8>>>,
+}
+
+impl ChunkReader {
+    /// Starts reading `path` at `offset` up to `total` bytes. A file of
+    /// zero bytes yields one empty chunk.
+    pub fn start(path: &str, offset: u64, total: u64, options: ChunkOptions) -> Self {
+        let (sender, chunks) = mpsc::channel(options.read_ahead.max(1));
+        let path = path.to_string();
+        #[cfg(unix)]
+        if options.backend == IoBackend::Mmap {
+            tokio::task::spawn_blocking(move || {
+                if let Err(e) = map_chunks(&path, offset, total, options.chunk_size, &sender) {
+                    let _ = sender.blocking_send(Err(e));
+                }
+            });
+            return Self { chunks };
+        }
+        tokio::spawn(async move {
+            if let Err(e) = read_chunks(&path, offset, total, options.chunk_size, &sender).await {
+                let _ = sender.send(Err(e)).await;
+            }
+        });
+        Self { chunks }
+    }
+
+    /// The next chunk, or `None` after the last one.
+    pub async fn next(&mut self) -> Option<io::Result<Vec<u8>>> {
+        self.chunks.recv().await
+    }
+}
+
+async fn read_chunks(
+    path: &str,
+    mut offset: u64,
+    total: u64,
+    chunk_size: u64,
+    sender: &mpsc::Sender<io::Result<Vec<u8>>>,
+) -> io::Result<()> {
+    let mut file = File::open(path).await?;
+    file.seek(SeekFrom::Start(offset)).await?;
+    loop {
+        let mut chunk = vec![0; chunk_size.min(total - offset) as usize];
+        file.read_exact(&mut chunk).await?;
+        offset += chunk.len() as u64;
+        // The receiver is gone once the upload finished or failed
+        if sender.send(Ok(chunk)).await.is_err() || offset >= total {
+            return Ok(());
+        }
+    }
+}
+
+/// `read_chunks` through a memory map. The request body still needs its
+/// own copy of each chunk, so this saves the read calls and the kernel to
+/// buffer copy, not the copy into the request.
+#[cfg(unix)]
+fn map_chunks(
+    path: &str,
+    mut offset: u64,
+    total: u64,
+    chunk_size: u64,
+    sender: &mpsc::Sender<io::Result<Vec<u8>>>,
+) -> io::Result<()> {
+    let file = std::fs::File::open(path)?;
+    // Mapping past the end of the file faults on access instead of failing
+    if file.metadata()?.len() < total {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            format!("{} is shorter than {} bytes", path, total),
+        ));
+    }
+    let map = Mmap::new(&file, total as usize)?;
+    let data = map.as_slice();
+    loop {
+        let end = (offset + chunk_size).min(total);
+        let chunk = data[offset as usize..end as usize].to_vec();
+        offset = end;
+        if sender.blocking_send(Ok(chunk)).is_err() || offset >= total {
+            return Ok(());
+        }
+    }
+}
+
+/// Read-only mapping of the start of a file, unmapped on drop.
+#[cfg(unix)]
+struct Mmap {
+    ptr: *mut libc::c_void,
+    len: usize,
+}
+
+#[cfg(unix)]
+impl Mmap {
+    fn new(file: &std::fs::File, len: usize) -> io::Result<Self> {
+        use std::os::fd::AsRawFd;
+
+        if len == 0 {
+            return Ok(Self {
+                ptr: std::ptr::null_mut(),
+                len,
+            });
+        }
+        // SAFETY: a fresh private read-only mapping of an open file, checked
+        // for failure. A file truncated while mapped would fault on access,
+        // the same as any other mmap reader.
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: advice on the mapping just created; failure is harmless
+        unsafe { libc::madvise(ptr, len, libc::MADV_SEQUENTIAL) };
+        Ok(Self { ptr, len })
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        if self.len == 0 {
+            return &[];
+        }
+        // SAFETY: the mapping is `len` readable bytes and lives as long as self
+        unsafe { std::slice::from_raw_parts(self.ptr as *const u8, self.len) }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Mmap {
+    fn drop(&mut self) {
+        if self.len > 0 {
+            // SAFETY: unmaps exactly the mapping created in `new`
+            unsafe { libc::munmap(self.ptr, self.len) };
+        }
+    }
+}
@@ -0,0 +1,69 @@
+use chrono::{DateTime, Duration, Utc};
+use reqwest::header::{HeaderMap, DATE};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+
+use crate::report::format_duration;
+
+/// Largest difference to the servers' clocks accepted without a warning,
+/// set by `init`.
+static TOLERANCE: OnceLock<Duration> = OnceLock::new();
+/// The warning is shown once per process.
+static WARNED: AtomicBool = AtomicBool::new(false);
+
+/// Default of `--clock-skew-tolerance`
+const DEFAULT_TOLERANCE_SECS: i64 = 120;
+
+pub fn init(tolerance: Duration) {
+    let _ = TOLERANCE.set(tolerance);
+}
+
+/// How far the local clock is ahead of a server that answered with the
+/// HTTP `Date` header `server_date` at `now`, negative when behind.
+pub fn skew(server_date: &str, now: DateTime<Utc>) -> Option<Duration> {
+    let server = DateTime::parse_from_rfc2822(server_date).ok()?;
+    Some(now - server.with_timezone(&Utc))
+}
+
+/// The warning for `skew`, `None` within `tolerance`. Token expiry is
+/// computed on the local clock while Google checks it on its own, and
+/// publish times are compared to Google's clock as well.
+pub fn skew_warning(skew: Duration, tolerance: Duration) -> Option<String> {
+    if skew.num_seconds().abs() <= tolerance.num_seconds() {
+        return None;
+    }
+    Some(format!(
+        "the local clock is {} {} of Google's servers, more than --clock-skew-tolerance allows. \
+         Tokens may expire early and publish times be off, sync the clock (e.g. with NTP)",
+        format_duration(skew.num_seconds().unsigned_abs()),
+        if skew > Duration::zero() {
+            "ahead"
+        } else {
+            "behind"
+        }
+    ))
+}
+
+/// Compares the local clock to the `Date` of a response, warning once
+/// when they differ by more than the tolerance.
+pub fn check_response(headers: &HeaderMap) {
+    if WARNED.load(Ordering::Relaxed) {
+        return;
+    }
+    let Some(skew) = headers
+        .get(DATE)
+        .and_then(|date| date.to_str().ok())
+        .and_then(|date| skew(date, Utc::now()))
+    else {
+        return;
+    };
+    let tolerance = TOLERANCE
+        .get()
+        .copied()
+        .unwrap_or(Duration::seconds(DEFAULT_TOLERANCE_SECS));
+    if let Some(warning) = skew_warning(skew, tolerance) {
+        if !WARNED.swap(true, Ordering::Relaxed) {
+            crate::warning!("{}", warning);
+        }
+    }
+}
@@ -0,0 +1,226 @@
+use chrono::{NaiveTime, Weekday};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+
+use crate::captions::CaptionsConfig;
+use crate::email::SmtpConfig;
+use crate::endpoints::{ClientIdentity, Endpoints, HttpConfig, HttpVersion};
+use crate::expand_tilde;
+use crate::links::{Link, UtmParameters};
+use crate::screening::ScreeningConfig;
+use crate::simulator::SimulatorConfig;
+use crate::telegram::TelegramConfig;
+use crate::youtube::License;
+
+pub const DEFAULT_PROFILE: &str = "default";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub profiles: HashMap<String, Profile>,
+    pub smtp: Option<SmtpConfig>,
+    pub telegram: Option<TelegramConfig>,
+    pub captions: Option<CaptionsConfig>,
+    /// Wordlist or command checking titles and descriptions before upload
+    pub screening: Option<ScreeningConfig>,
+    /// Watch mode subfolders and the profile used for videos dropped there
+    pub watch_folders: HashMap<String, String>,
+    pub simulator: SimulatorConfig,
+    pub endpoints: Endpoints,
+    pub client: ClientIdentity,
+    pub http: HttpConfig,
+    /// Weekly publish slots picked with --schedule-template
+    pub schedule_templates: HashMap<String, ScheduleTemplate>,
+    /// Agency clients picked with --client
+    pub clients: HashMap<String, ClientConfig>,
+}
+
+/// A client of an agency, uploading to their own channel. Everything that
+/// could carry one client's credentials, history or defaults into another
+/// client's uploads must be given.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ClientConfig {
+    pub oauth_config: String,
+    pub token_file: String,
+    /// Metadata profile, which must be listed under `profiles` and not be
+    /// used by another client
+    pub profile: String,
+    pub state_dir: String,
+}
+
+/// Publish slots at fixed local times on some weekdays, e.g. weeknights
+/// at 19:00.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ScheduleTemplate {
+    /// `mon` to `sun`, every day when empty
+    #[serde(default)]
+    pub days: Vec<Weekday>,
+    /// Times of day like `19:00`
+    pub times: Vec<String>,
+    /// Timezone of `times`, unless --timezone is given
+    #[serde(default)]
+    pub timezone: Option<String>,
+    /// Skip dates file, unless --skip-dates is given
+    #[serde(default)]
+    pub skip_dates: Option<String>,
+}
+
+impl ScheduleTemplate {
+    /// `times` parsed and in order.
+    pub fn times(&self) -> Result<Vec<NaiveTime>, Box<dyn std::error::Error>> {
+        if self.times.is_empty() {
+            return Err("Schedule template has no times".into());
+        }
+        let mut times = self
+            .times
+            .iter()
+            .map(|time| {
+                NaiveTime::parse_from_str(time, "%H:%M")
+                    .map_err(|e| format!("Invalid time '{}' in schedule template: {}", time, e))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        times.sort();
+        times.dedup();
+        Ok(times)
+    }
+}
+
+/// Metadata defaults applied to videos that have no metadata file entry.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Profile {
+    pub tags: Vec<String>,
+    pub category_id: String,
+    pub privacy_status: String,
+    pub language: Option<String>,
+    /// Description with `{title}`, `{file}`, `{stem}`, `{index}` and
+    /// `{pool}` placeholders, filtered like `{stem|titlecase}`, see
+    /// `template::render_template`. `{publish_date}`, `{publish_time}` and
+    /// `{episode}` are filled in once the schedule is known
+    pub description_template: Option<String>,
+    /// Publish cadence used when --interval isn't given
+    pub interval: Option<String>,
+    /// Series the videos belong to, numbered and collected in a playlist
+    /// of the same name
+    pub series: Option<String>,
+    /// Title suffixes rotated through the batch for A/B comparisons, an
+    /// empty one leaves the title alone
+    pub title_suffixes: Vec<String>,
+    /// Audience declaration, also applied to metadata file entries without
+    /// one
+    pub made_for_kids: Option<bool>,
+    /// `youtube` or `creativeCommon`, also applied to metadata file entries
+    /// without one
+    pub license: Option<License>,
+    /// Channel the profile uploads to, e.g. `UC...`. Uploads stop before
+    /// the first video when the token belongs to another channel
+    pub channel_id: Option<String>,
+    /// Links listed at the end of every description, see `links`
+    pub links: Vec<Link>,
+    /// UTM parameters added to the links
+    pub utm: Option<UtmParameters>,
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Self {
+            tags: vec!["gaming".to_string()],
+            category_id: "20".to_string(), // GAMING
+            privacy_status: "private".to_string(),
+            language: None,
+            description_template: None,
+            interval: None,
+            series: None,
+            title_suffixes: Vec::new(),
+            made_for_kids: None,
+            license: None,
+            channel_id: None,
+            links: Vec::new(),
+            utm: None,
+        }
+    }
+}
+
+impl Config {
+    /// Looks up an agency client, refusing clients that share a profile,
+    /// token file or state dir with another one.
+    pub fn client(&self, name: &str) -> Result<ClientConfig, Box<dyn std::error::Error>> {
+        let client = self
+            .clients
+            .get(name)
+            .ok_or_else(|| format!("Client '{}' not found in config", name))?;
+        if !self.profiles.contains_key(&client.profile) {
+            return Err(format!(
+                "Profile '{}' of client '{}' not found in config",
+                client.profile, name
+            )
+            .into());
+        }
+        for (other_name, other) in &self.clients {
+            if other_name == name {
+                continue;
+            }
+            let shared = if other.profile == client.profile {
+                Some(("profile", &client.profile))
+            } else if expand_tilde(&other.token_file) == expand_tilde(&client.token_file) {
+                Some(("token file", &client.token_file))
+            } else if expand_tilde(&other.state_dir) == expand_tilde(&client.state_dir) {
+                Some(("state dir", &client.state_dir))
+            } else {
+                None
+            };
+            if let Some((what, value)) = shared {
+                return Err(format!(
+                    "Clients '{}' and '{}' share the {} '{}'",
+                    name, other_name, what, value
+                )
+                .into());
+            }
+        }
+        Ok(client.clone())
+    }
+
+    /// Looks up a profile by name. The default profile may be omitted from
+    /// the config file, in which case the built-in defaults are used.
+    pub fn profile(&self, name: &str) -> Result<Profile, Box<dyn std::error::Error>> {
+        match self.profiles.get(name) {
+            Some(profile) => Ok(profile.clone()),
+            None if name == DEFAULT_PROFILE => Ok(Profile::default()),
+            None => Err(format!("Profile '{}' not found in config", name).into()),
+        }
+    }
+
+    pub fn schedule_template(
+        &self,
+        name: &str,
+    ) -> Result<ScheduleTemplate, Box<dyn std::error::Error>> {
+        self.schedule_templates
+            .get(name)
+            .cloned()
+            .ok_or_else(|| format!("Schedule template '{}' not found in config", name).into())
+    }
+}
+
+/// Loads the config file, treating a missing file as an empty config.
+pub fn load_config(config_path: &str) -> Result<Config, Box<dyn std::error::Error>> {
+    let expanded_path = expand_tilde(config_path);
+    let content = match fs::read_to_string(&expanded_path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Config::default()),
+        Err(e) => {
+            return Err(format!("Failed to read config from '{}': {}", expanded_path, e).into())
+        }
+    };
+    let config: Config = serde_json::from_str(&content)
+        .map_err(|e| format!("Failed to parse config '{}': {}", expanded_path, e))?;
+    if config.http.version == HttpVersion::Http3 && !cfg!(feature = "http3") {
+        return Err(format!(
+            "'{}' asks for HTTP/3, which needs a build with --features http3",
+            expanded_path
+        )
+        .into());
+    }
+    Ok(config)
+}
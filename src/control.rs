@@ -0,0 +1,119 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::sync::Notify;
+
+/// Shared pause switch for a running batch. The batch checks it between
+/// videos, so pausing never interrupts an upload in progress.
+#[derive(Clone, Default)]
+pub struct PauseControl {
+    paused: Arc<AtomicBool>,
+    resumed: Arc<Notify>,
+    retry: Arc<AtomicBool>,
+}
+
+impl PauseControl {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::SeqCst);
+    }
+
+    pub fn resume(&self) {
+        self.paused.store(false, Ordering::SeqCst);
+        self.resumed.notify_waiters();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    /// Asks the batch to queue its failed videos again before it finishes.
+    pub fn request_retry(&self) {
+        self.retry.store(true, Ordering::SeqCst);
+    }
+
+    /// Returns whether a retry was requested since the last call.
+    pub fn take_retry_request(&self) -> bool {
+        self.retry.swap(false, Ordering::SeqCst)
+    }
+
+    /// Returns immediately unless paused, otherwise waits for `resume`.
+    pub async fn wait_while_paused(&self) {
+        loop {
+            // Register before checking so a resume in between isn't lost
+            let resumed = self.resumed.notified();
+            if !self.is_paused() {
+                return;
+            }
+            resumed.await;
+        }
+    }
+
+    /// Pauses on SIGUSR1 and resumes on SIGUSR2 for the rest of the process.
+    #[cfg(unix)]
+    pub fn listen_for_signals(&self) -> std::io::Result<()> {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut pause_signal = signal(SignalKind::user_defined1())?;
+        let mut resume_signal = signal(SignalKind::user_defined2())?;
+        let control = self.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    Some(()) = pause_signal.recv() => {
+                        crate::info!("Received SIGUSR1, pausing after the current video");
+                        control.pause();
+                    }
+                    Some(()) = resume_signal.recv() => {
+                        crate::info!("Received SIGUSR2, resuming");
+                        control.resume();
+                    }
+                    else => break,
+                }
+            }
+        });
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    pub fn listen_for_signals(&self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Cooperative cancellation of uploads. Clones share the same state, so an
+/// application keeps one and hands the others to the uploads it may abort.
+#[derive(Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+    notify: Arc<Notify>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+        self.notify.notify_waiters();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Completes once `cancel` was called on any clone.
+    pub async fn cancelled(&self) {
+        loop {
+            // Register before checking so a cancel in between isn't lost
+            let notified = self.notify.notified();
+            if self.is_cancelled() {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
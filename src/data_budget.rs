@@ -0,0 +1,116 @@
+use chrono::NaiveDate;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::expand_tilde;
+
+const DATA_USAGE_FILE: &str = "data_usage.json";
+
+/// What `--data-budget` limits.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BudgetPeriod {
+    /// Bytes sent by this run
+    Run,
+    /// Bytes sent by all runs on the same local day
+    Day,
+}
+
+/// Bytes sent on `date`, kept in the state dir for `--data-budget-period day`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct DataUsage {
+    date: NaiveDate,
+    bytes: u64,
+}
+
+fn data_usage_path(state_dir: &str) -> PathBuf {
+    Path::new(&expand_tilde(state_dir)).join(DATA_USAGE_FILE)
+}
+
+/// Upload volume allowed on a metered or capped connection.
+#[derive(Debug, Clone)]
+pub struct DataBudget {
+    limit: u64,
+    used: u64,
+    period: BudgetPeriod,
+    state_dir: String,
+}
+
+impl DataBudget {
+    /// The budget with what was sent on `today` already counted for
+    /// `BudgetPeriod::Day`.
+    pub fn load(
+        limit: u64,
+        period: BudgetPeriod,
+        state_dir: &str,
+        today: NaiveDate,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let used = match period {
+            BudgetPeriod::Run => 0,
+            BudgetPeriod::Day => {
+                let path = data_usage_path(state_dir);
+                match fs::read_to_string(&path) {
+                    Ok(content) => {
+                        let usage: DataUsage = serde_json::from_str(&content)
+                            .map_err(|e| format!("Failed to parse '{}': {}", path.display(), e))?;
+                        if usage.date == today {
+                            usage.bytes
+                        } else {
+                            0
+                        }
+                    }
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => 0,
+                    Err(e) => {
+                        return Err(format!("Failed to read '{}': {}", path.display(), e).into())
+                    }
+                }
+            }
+        };
+        Ok(Self {
+            limit,
+            used,
+            period,
+            state_dir: state_dir.to_string(),
+        })
+    }
+
+    pub fn limit(&self) -> u64 {
+        self.limit
+    }
+
+    pub fn used(&self) -> u64 {
+        self.used
+    }
+
+    /// Whether a file of `bytes` can be sent without going over.
+    pub fn fits(&self, bytes: u64) -> bool {
+        self.used.saturating_add(bytes) <= self.limit
+    }
+
+    /// Counts `bytes` sent on `today`, saving the total for the day.
+    pub fn record(
+        &mut self,
+        bytes: u64,
+        today: NaiveDate,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.used = self.used.saturating_add(bytes);
+        if self.period == BudgetPeriod::Day {
+            let path = data_usage_path(&self.state_dir);
+            // A run across midnight counts into the new day from here
+            let previous = fs::read_to_string(&path)
+                .ok()
+                .and_then(|content| serde_json::from_str::<DataUsage>(&content).ok())
+                .filter(|usage| usage.date == today)
+                .map_or(0, |usage| usage.bytes);
+            let usage = DataUsage {
+                date: today,
+                bytes: previous.saturating_add(bytes),
+            };
+            fs::create_dir_all(path.parent().unwrap_or(Path::new(".")))?;
+            fs::write(&path, serde_json::to_string_pretty(&usage)?)?;
+        }
+        Ok(())
+    }
+}
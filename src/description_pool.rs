@@ -0,0 +1,114 @@
+use clap::ValueEnum;
+use rand::seq::SliceRandom;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::expand_tilde;
+
+const CURSOR_FILE: &str = "description_cursors.json";
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PoolOrder {
+    /// Pick any line, repeats allowed
+    Random,
+    /// Walk the file line by line, continuing where the last run stopped
+    Sequential,
+    /// Random, but no line is used twice until the pool is exhausted
+    NoRepeat,
+}
+
+/// A file of one-line descriptions handed out to videos without metadata.
+pub struct DescriptionPool {
+    path: String,
+    lines: Vec<String>,
+    order: PoolOrder,
+    cursor: usize,
+    remaining: Vec<usize>,
+}
+
+impl DescriptionPool {
+    pub fn load(path: &str, order: PoolOrder, state_dir: &str) -> io::Result<Self> {
+        let path = expand_tilde(path);
+        let lines: Vec<String> = fs::read_to_string(&path)?
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect();
+
+        if lines.is_empty() {
+            return Err(io::Error::other(format!("No lines found in '{}'", path)));
+        }
+
+        let cursor = if order == PoolOrder::Sequential {
+            load_cursors(state_dir).get(&path).copied().unwrap_or(0) % lines.len()
+        } else {
+            0
+        };
+
+        Ok(Self {
+            path,
+            lines,
+            order,
+            cursor,
+            remaining: Vec::new(),
+        })
+    }
+
+    pub fn next_description(&mut self) -> String {
+        let mut rng = rand::thread_rng();
+        match self.order {
+            PoolOrder::Random => self.lines.choose(&mut rng).unwrap().clone(),
+            PoolOrder::Sequential => {
+                let line = self.lines[self.cursor].clone();
+                self.cursor = (self.cursor + 1) % self.lines.len();
+                line
+            }
+            PoolOrder::NoRepeat => {
+                if self.remaining.is_empty() {
+                    if self.cursor > 0 {
+                        crate::warning!(
+                            "description pool '{}' exhausted, lines will repeat",
+                            self.path
+                        );
+                    }
+                    self.remaining = (0..self.lines.len()).collect();
+                    self.remaining.shuffle(&mut rng);
+                }
+                self.cursor += 1;
+                let index = self.remaining.pop().unwrap();
+                self.lines[index].clone()
+            }
+        }
+    }
+
+    /// Persists the rotation position so the next run continues from here.
+    /// Only meaningful for sequential pools.
+    pub fn save_cursor(&self, state_dir: &str) -> io::Result<()> {
+        if self.order != PoolOrder::Sequential {
+            return Ok(());
+        }
+
+        let state_dir = expand_tilde(state_dir);
+        fs::create_dir_all(&state_dir)?;
+        let mut cursors = load_cursors(&state_dir);
+        cursors.insert(self.path.clone(), self.cursor);
+        fs::write(
+            cursor_path(&state_dir),
+            serde_json::to_string_pretty(&cursors)?,
+        )
+    }
+}
+
+fn cursor_path(state_dir: &str) -> PathBuf {
+    Path::new(&expand_tilde(state_dir)).join(CURSOR_FILE)
+}
+
+fn load_cursors(state_dir: &str) -> HashMap<String, usize> {
+    fs::read_to_string(cursor_path(state_dir))
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
@@ -0,0 +1,312 @@
+use base64::Engine;
+use md5::Md5;
+use reqwest::header::{self, HeaderMap};
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncWriteExt;
+
+use crate::http_trace;
+use crate::spool;
+
+/// Attempts per download, each resuming where the one before stopped
+const DOWNLOAD_ATTEMPTS: u32 = 5;
+/// Next to a download, the URL it comes from
+const SOURCE_FILE: &str = "source.url";
+
+/// Whether `source` is a URL to download rather than a local file.
+pub fn is_remote(source: &str) -> bool {
+    ["http://", "https://", "s3://"]
+        .iter()
+        .any(|scheme| source.starts_with(scheme))
+}
+
+/// HTTP URL of `source`. `s3://bucket/key` addresses a public object,
+/// private ones need a presigned `https://` URL.
+fn http_url(source: &str) -> String {
+    match source.strip_prefix("s3://") {
+        Some(rest) => {
+            let (bucket, key) = rest.split_once('/').unwrap_or((rest, ""));
+            format!("https://{}.s3.amazonaws.com/{}", bucket, key)
+        }
+        None => source.to_string(),
+    }
+}
+
+/// Where `source` is downloaded to: its file name, so titles taken from
+/// file names are the same as for local files, in a folder of the cache
+/// dir named after the URL.
+pub fn local_path(source: &str, cache_dir: &Path) -> PathBuf {
+    let digest = Sha256::digest(source.as_bytes());
+    let folder: String = digest[..8].iter().map(|b| format!("{:02x}", b)).collect();
+    let path = source.split(['?', '#']).next().unwrap_or_default();
+    let name =
+        percent_decode(path.rsplit('/').next().unwrap_or_default()).replace(['/', '\\'], "_");
+    let name = match name.as_str() {
+        "" | "." | ".." => "download".to_string(),
+        _ => name,
+    };
+    cache_dir.join("downloads").join(folder).join(name)
+}
+
+/// The path `source` is downloaded to, remembering the URL there so the
+/// download can happen after the batch is planned, or by a later run
+/// uploading a saved plan.
+pub fn register(source: &str, cache_dir: &Path) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let path = local_path(source, cache_dir);
+    let folder = path.parent().unwrap_or(cache_dir);
+    fs::create_dir_all(folder)?;
+    fs::write(folder.join(SOURCE_FILE), source)
+        .map_err(|e| format!("Failed to write '{}': {}", folder.display(), e))?;
+    Ok(path)
+}
+
+/// The URL registered for the download at `path`, `None` for local files.
+pub fn source_of(path: &Path) -> Option<String> {
+    let folder = path.parent()?;
+    if folder.parent()?.file_name()? != "downloads" {
+        return None;
+    }
+    fs::read_to_string(folder.join(SOURCE_FILE)).ok()
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// What the server said about the file, kept next to a partial download so
+/// it resumes only while the file is unchanged.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Validators {
+    etag: Option<String>,
+    last_modified: Option<String>,
+    content_md5: Option<String>,
+    length: Option<u64>,
+}
+
+impl Validators {
+    fn from_headers(headers: &HeaderMap, length: Option<u64>) -> Self {
+        let text = |name| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        };
+        Self {
+            etag: text(header::ETAG),
+            last_modified: text(header::LAST_MODIFIED),
+            content_md5: text(header::HeaderName::from_static("content-md5")),
+            length,
+        }
+    }
+
+    /// Value for `If-Range`, without one the download starts over.
+    fn if_range(&self) -> Option<&str> {
+        self.etag
+            .as_deref()
+            .filter(|etag| !etag.starts_with("W/"))
+            .or(self.last_modified.as_deref())
+    }
+
+    /// MD5 of the file as hex, from Content-MD5 or an S3 ETag of a single
+    /// part upload, which is the MD5 in quotes.
+    fn expected_md5(&self) -> Option<String> {
+        if let Some(md5) = &self.content_md5 {
+            let md5 = base64::engine::general_purpose::STANDARD
+                .decode(md5.trim())
+                .ok()?;
+            return Some(md5.iter().map(|b| format!("{:02x}", b)).collect());
+        }
+        let etag = self.etag.as_deref()?.trim_matches('"');
+        (etag.len() == 32 && etag.chars().all(|c| c.is_ascii_hexdigit()))
+            .then(|| etag.to_ascii_lowercase())
+    }
+}
+
+enum FetchError {
+    /// Worth resuming
+    Retry(String),
+    Stop(String),
+}
+
+/// Downloads `source` into the cache dir unless it is there already.
+/// Interrupted transfers resume with a range request while the server
+/// reports the same ETag, and the result is checked against the MD5 the
+/// server gives as Content-MD5 or as the ETag of an S3 object.
+pub async fn download(
+    client: &Client,
+    source: &str,
+    cache_dir: &Path,
+) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let path = local_path(source, cache_dir);
+    if spool::reuse(&path) {
+        crate::verbose!("Using {} downloaded before", path.display());
+        return Ok(path);
+    }
+    fs::create_dir_all(path.parent().unwrap_or(cache_dir))?;
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let partial = path.with_file_name(format!("{}.download", name));
+    let validators_path = path.with_file_name(format!("{}.download.json", name));
+    let url = http_url(source);
+
+    crate::info!("Downloading {}", source);
+    let mut attempt = 1;
+    let validators = loop {
+        match fetch(client, &url, &partial, &validators_path).await {
+            Ok(validators) => break validators,
+            Err(FetchError::Retry(e)) if attempt < DOWNLOAD_ATTEMPTS => {
+                crate::warning!(
+                    "Download of {} interrupted ({}), resuming {}/{}",
+                    source,
+                    e,
+                    attempt,
+                    DOWNLOAD_ATTEMPTS - 1
+                );
+                tokio::time::sleep(std::time::Duration::from_secs(attempt as u64)).await;
+                attempt += 1;
+            }
+            Err(FetchError::Retry(e) | FetchError::Stop(e)) => {
+                return Err(format!("Failed to download {}: {}", source, e).into())
+            }
+        }
+    };
+
+    if let Some(expected) = validators.expected_md5() {
+        // Hashing reads the whole file, keep it off the runtime threads
+        let hashed = partial.clone();
+        let actual =
+            tokio::task::spawn_blocking(move || md5_file(&hashed).map_err(|e| e.to_string()))
+                .await??;
+        if actual != expected {
+            let _ = fs::remove_file(&partial);
+            let _ = fs::remove_file(&validators_path);
+            return Err(format!(
+                "Checksum mismatch for {}: the server says MD5 {}, the download is {}",
+                source, expected, actual
+            )
+            .into());
+        }
+        crate::verbose!("Checked MD5 of {}", source);
+    }
+    fs::rename(&partial, &path)?;
+    let _ = fs::remove_file(&validators_path);
+    Ok(path)
+}
+
+/// Requests the rest of `url` after what `partial` holds and appends it,
+/// or starts over when there is nothing to resume.
+async fn fetch(
+    client: &Client,
+    url: &str,
+    partial: &Path,
+    validators_path: &Path,
+) -> Result<Validators, FetchError> {
+    let stop = |e: io::Error| FetchError::Stop(e.to_string());
+    let saved: Option<Validators> = fs::read_to_string(validators_path)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok());
+    let resume = saved.as_ref().and_then(|validators| {
+        let offset = fs::metadata(partial).map(|m| m.len()).ok()?;
+        Some((offset, validators.if_range()?.to_string())).filter(|(offset, _)| *offset > 0)
+    });
+    // Stopped after the last byte but before the checksum
+    if let (Some((offset, _)), Some(validators)) = (&resume, &saved) {
+        if validators.length == Some(*offset) {
+            return Ok(saved.unwrap_or_default());
+        }
+    }
+
+    let mut request = client.get(url);
+    if let Some((offset, if_range)) = &resume {
+        request = request
+            .header(header::RANGE, format!("bytes={}-", offset))
+            .header(header::IF_RANGE, if_range);
+    }
+    let mut response = http_trace::send(request)
+        .await
+        .map_err(|e| FetchError::Retry(e.to_string()))?;
+    let status = response.status();
+    let (mut file, validators) = match (status, resume, saved) {
+        (StatusCode::PARTIAL_CONTENT, Some((offset, _)), Some(validators)) => {
+            crate::verbose!("Resuming {} at byte {}", url, offset);
+            let file = tokio::fs::OpenOptions::new()
+                .append(true)
+                .open(partial)
+                .await
+                .map_err(stop)?;
+            (file, validators)
+        }
+        // A new download, or the file changed since the last attempt
+        (status, _, _) if status.is_success() => {
+            let validators =
+                Validators::from_headers(response.headers(), response.content_length());
+            let json = serde_json::to_string_pretty(&validators)
+                .map_err(|e| FetchError::Stop(e.to_string()))?;
+            fs::write(validators_path, json).map_err(stop)?;
+            let file = tokio::fs::File::create(partial).await.map_err(stop)?;
+            (file, validators)
+        }
+        (StatusCode::RANGE_NOT_SATISFIABLE, _, _) => {
+            let _ = fs::remove_file(validators_path);
+            return Err(FetchError::Retry(
+                "range not satisfiable, starting over".to_string(),
+            ));
+        }
+        (status, _, _) if status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS => {
+            return Err(FetchError::Retry(format!("HTTP {}", status)))
+        }
+        (status, _, _) => return Err(FetchError::Stop(format!("HTTP {}", status))),
+    };
+
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| FetchError::Retry(e.to_string()))?
+    {
+        file.write_all(&chunk).await.map_err(stop)?;
+    }
+    file.flush().await.map_err(stop)?;
+    let received = file.metadata().await.map_err(stop)?.len();
+    match validators.length {
+        Some(length) if received < length => Err(FetchError::Retry(format!(
+            "connection closed after {} of {} bytes",
+            received, length
+        ))),
+        _ => Ok(validators),
+    }
+}
+
+/// MD5 of the file at `path` as hex.
+pub fn md5_file(path: &Path) -> Result<String, Box<dyn std::error::Error>> {
+    let mut file =
+        File::open(path).map_err(|e| format!("Failed to read '{}': {}", path.display(), e))?;
+    let mut md5 = Md5::new();
+    io::copy(&mut file, &mut md5)?;
+    Ok(md5
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
@@ -0,0 +1,168 @@
+use base64::Engine;
+use chrono::Utc;
+use serde::Deserialize;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+
+use crate::redact;
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpSecurity {
+    /// Plain connection upgraded with STARTTLS (usually port 587)
+    #[default]
+    Starttls,
+    /// TLS from the first byte (usually port 465)
+    Tls,
+    /// No encryption, only for local relays
+    None,
+}
+
+/// SMTP server settings from the `smtp` section of the config file.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SmtpConfig {
+    pub host: String,
+    #[serde(default = "default_port")]
+    pub port: u16,
+    #[serde(default)]
+    pub security: SmtpSecurity,
+    pub username: Option<String>,
+    /// Falls back to the YT_UPLOAD_SMTP_PASSWORD environment variable
+    pub password: Option<String>,
+    pub from: String,
+}
+
+fn default_port() -> u16 {
+    587
+}
+
+trait SmtpStream: AsyncRead + AsyncWrite + Unpin + Send {}
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> SmtpStream for T {}
+
+struct SmtpConnection {
+    stream: BufReader<Box<dyn SmtpStream>>,
+}
+
+impl SmtpConnection {
+    /// Reads a possibly multi-line reply and checks its status code.
+    async fn expect(&mut self, code: u16) -> Result<String, Box<dyn std::error::Error>> {
+        let mut reply = String::new();
+        loop {
+            let mut line = String::new();
+            if self.stream.read_line(&mut line).await? == 0 {
+                return Err("SMTP server closed the connection".into());
+            }
+            reply.push_str(&line);
+            // "250-..." continues the reply, "250 ..." ends it
+            if line.as_bytes().get(3) != Some(&b'-') {
+                break;
+            }
+        }
+
+        if reply.starts_with(&code.to_string()) {
+            Ok(reply)
+        } else {
+            Err(format!("SMTP error, expected {}: {}", code, reply.trim()).into())
+        }
+    }
+
+    async fn command(
+        &mut self,
+        line: &str,
+        code: u16,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let stream = self.stream.get_mut();
+        stream.write_all(line.as_bytes()).await?;
+        stream.write_all(b"\r\n").await?;
+        stream.flush().await?;
+        self.expect(code).await
+    }
+}
+
+async fn tls_wrap(
+    stream: Box<dyn SmtpStream>,
+    host: &str,
+) -> Result<Box<dyn SmtpStream>, Box<dyn std::error::Error>> {
+    let connector = tokio_native_tls::TlsConnector::from(native_tls::TlsConnector::new()?);
+    Ok(Box::new(connector.connect(host, stream).await?))
+}
+
+/// Sends a plain-text email, credentials redacted. Lines starting with a
+/// dot are escaped as the SMTP DATA command requires.
+pub async fn send_email(
+    config: &SmtpConfig,
+    to: &[String],
+    subject: &str,
+    body: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let tcp = TcpStream::connect((config.host.as_str(), config.port))
+        .await
+        .map_err(|e| {
+            format!(
+                "Failed to connect to {}:{}: {}",
+                config.host, config.port, e
+            )
+        })?;
+    let mut stream: Box<dyn SmtpStream> = Box::new(tcp);
+    if config.security == SmtpSecurity::Tls {
+        stream = tls_wrap(stream, &config.host).await?;
+    }
+
+    let mut smtp = SmtpConnection {
+        stream: BufReader::new(stream),
+    };
+    smtp.expect(220).await?;
+    smtp.command("EHLO youtube-scheduler", 250).await?;
+
+    if config.security == SmtpSecurity::Starttls {
+        smtp.command("STARTTLS", 220).await?;
+        let stream = tls_wrap(smtp.stream.into_inner(), &config.host).await?;
+        smtp = SmtpConnection {
+            stream: BufReader::new(stream),
+        };
+        smtp.command("EHLO youtube-scheduler", 250).await?;
+    }
+
+    if let Some(username) = &config.username {
+        let password = config
+            .password
+            .clone()
+            .or_else(|| std::env::var("YT_UPLOAD_SMTP_PASSWORD").ok())
+            .ok_or("SMTP username set but no password in config or YT_UPLOAD_SMTP_PASSWORD")?;
+        let credentials = base64::engine::general_purpose::STANDARD
+            .encode(format!("\0{}\0{}", username, password));
+        redact::register(&password);
+        redact::register(&credentials);
+        smtp.command(&format!("AUTH PLAIN {}", credentials), 235)
+            .await?;
+    }
+
+    smtp.command(&format!("MAIL FROM:<{}>", config.from), 250)
+        .await?;
+    for recipient in to {
+        smtp.command(&format!("RCPT TO:<{}>", recipient), 250)
+            .await?;
+    }
+    smtp.command("DATA", 354).await?;
+
+    let mut message = format!(
+        "From: {}\r\nTo: {}\r\nSubject: {}\r\nDate: {}\r\n\
+         MIME-Version: 1.0\r\nContent-Type: text/plain; charset=utf-8\r\n\r\n",
+        config.from,
+        to.join(", "),
+        subject,
+        Utc::now().to_rfc2822()
+    );
+    for line in redact::redact(body).lines() {
+        if line.starts_with('.') {
+            message.push('.');
+        }
+        message.push_str(line);
+        message.push_str("\r\n");
+    }
+    message.push('.');
+    smtp.command(&message, 250).await?;
+    let _ = smtp.command("QUIT", 221).await;
+
+    Ok(())
+}
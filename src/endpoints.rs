@@ -0,0 +1,248 @@
+use reqwest::header::{HeaderMap, HeaderValue, USER_AGENT};
+use reqwest::Client;
+use serde::Deserialize;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use crate::simulator;
+
+/// Endpoints of this process, set by `init`.
+static ENDPOINTS: OnceLock<Endpoints> = OnceLock::new();
+/// Identity sent with every Google request, set by `init`.
+static IDENTITY: OnceLock<ClientIdentity> = OnceLock::new();
+/// Connection settings, set by `init`.
+static HTTP: OnceLock<HttpConfig> = OnceLock::new();
+/// The client shared by auth, uploads and API calls, so connections and
+/// TLS sessions are reused across all of them.
+static CLIENT: OnceLock<Client> = OnceLock::new();
+/// The client sending upload data over HTTP/3, when configured.
+#[cfg(feature = "http3")]
+static UPLOAD_CLIENT: OnceLock<Client> = OnceLock::new();
+
+/// Google URLs requests go to, e.g. to route them through a recording
+/// proxy. Set in the `endpoints` section of the config file or with
+/// `YT_UPLOAD_*_URL` variables, anything unset keeps the production URL.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct Endpoints {
+    /// OAuth consent page
+    pub auth_url: String,
+    pub token_url: String,
+    pub revoke_url: String,
+    /// Scopes and expiry of an access token
+    pub tokeninfo_url: String,
+    /// Base of media uploads, followed by `/upload/youtube/v3/...`
+    pub upload_base_url: String,
+    /// Base of YouTube Data and Calendar API calls, followed by
+    /// `/youtube/v3/...` and `/calendar/v3/...`
+    pub api_base_url: String,
+}
+
+impl Default for Endpoints {
+    fn default() -> Self {
+        Self {
+            auth_url: "https://accounts.google.com/o/oauth2/v2/auth".to_string(),
+            token_url: "https://oauth2.googleapis.com/token".to_string(),
+            revoke_url: "https://oauth2.googleapis.com/revoke".to_string(),
+            tokeninfo_url: "https://oauth2.googleapis.com/tokeninfo".to_string(),
+            upload_base_url: "https://www.googleapis.com".to_string(),
+            api_base_url: "https://www.googleapis.com".to_string(),
+        }
+    }
+}
+
+impl Endpoints {
+    /// Replaces the URLs whose variable is set.
+    pub fn with_env(mut self) -> Self {
+        let fields = [
+            ("YT_UPLOAD_AUTH_URL", &mut self.auth_url),
+            ("YT_UPLOAD_TOKEN_URL", &mut self.token_url),
+            ("YT_UPLOAD_REVOKE_URL", &mut self.revoke_url),
+            ("YT_UPLOAD_TOKENINFO_URL", &mut self.tokeninfo_url),
+            ("YT_UPLOAD_UPLOAD_BASE_URL", &mut self.upload_base_url),
+            ("YT_UPLOAD_API_BASE_URL", &mut self.api_base_url),
+        ];
+        for (var, field) in fields {
+            if let Some(url) = std::env::var(var).ok().filter(|url| !url.is_empty()) {
+                *field = url;
+            }
+        }
+        self.upload_base_url = self.upload_base_url.trim_end_matches('/').to_string();
+        self.api_base_url = self.api_base_url.trim_end_matches('/').to_string();
+        self
+    }
+}
+
+/// How requests identify themselves to Google, from the `client` section
+/// of the config file or `YT_UPLOAD_USER_AGENT` and
+/// `YT_UPLOAD_QUOTA_PROJECT`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct ClientIdentity {
+    /// Replaces `youtube-scheduler/<version>`
+    pub user_agent: Option<String>,
+    /// Cloud project billed for quota through `X-Goog-User-Project`
+    pub quota_project: Option<String>,
+}
+
+impl ClientIdentity {
+    /// Replaces the fields whose variable is set.
+    pub fn with_env(mut self) -> Self {
+        if let Ok(user_agent) = std::env::var("YT_UPLOAD_USER_AGENT") {
+            self.user_agent = Some(user_agent);
+        }
+        if let Ok(project) = std::env::var("YT_UPLOAD_QUOTA_PROJECT") {
+            self.quota_project = Some(project);
+        }
+        self.user_agent = self.user_agent.filter(|agent| !agent.is_empty());
+        self.quota_project = self.quota_project.filter(|project| !project.is_empty());
+        self
+    }
+
+    fn headers(&self) -> Result<HeaderMap, Box<dyn std::error::Error>> {
+        let default_agent = format!("{}/{}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            USER_AGENT,
+            HeaderValue::from_str(self.user_agent.as_deref().unwrap_or(&default_agent))
+                .map_err(|_| "Invalid characters in the User-Agent")?,
+        );
+        if let Some(project) = &self.quota_project {
+            headers.insert(
+                "X-Goog-User-Project",
+                HeaderValue::from_str(project).map_err(|_| "Invalid quota project")?,
+            );
+        }
+        Ok(headers)
+    }
+}
+
+/// HTTP version of requests to Google.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HttpVersion {
+    /// HTTP/2 where the server offers it during the TLS handshake,
+    /// HTTP/1.1 otherwise
+    #[default]
+    Auto,
+    /// HTTP/1.1 only, one connection per parallel request
+    Http1,
+    /// HTTP/2 without negotiating, for proxies known to speak it
+    Http2,
+    /// Upload data over HTTP/3 (QUIC), falling back to `auto` for the rest
+    /// of the run when QUIC fails. Needs a build with the `http3` feature
+    Http3,
+}
+
+/// Connection pool and protocol settings from the `http` section of the
+/// config file.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct HttpConfig {
+    pub version: HttpVersion,
+    /// Idle connections kept open per host for the next request
+    pub pool_max_idle_per_host: usize,
+    /// How long an idle connection is kept
+    pub pool_idle_timeout_secs: u64,
+    /// TCP keep-alive probes on open connections, 0 turns them off
+    pub tcp_keepalive_secs: u64,
+    /// Time allowed to connect and finish the TLS handshake
+    pub connect_timeout_secs: u64,
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self {
+            version: HttpVersion::Auto,
+            pool_max_idle_per_host: 8,
+            pool_idle_timeout_secs: 90,
+            tcp_keepalive_secs: 60,
+            connect_timeout_secs: 30,
+        }
+    }
+}
+
+impl HttpConfig {
+    fn builder(&self) -> reqwest::ClientBuilder {
+        let mut builder = Client::builder()
+            .pool_max_idle_per_host(self.pool_max_idle_per_host)
+            .pool_idle_timeout(Duration::from_secs(self.pool_idle_timeout_secs))
+            .connect_timeout(Duration::from_secs(self.connect_timeout_secs))
+            .tcp_keepalive(
+                (self.tcp_keepalive_secs > 0).then(|| Duration::from_secs(self.tcp_keepalive_secs)),
+            );
+        builder = match self.version {
+            // Only upload data goes over HTTP/3, see `upload_client`
+            HttpVersion::Auto | HttpVersion::Http3 => builder,
+            HttpVersion::Http1 => builder.http1_only(),
+            // Long uploads keep one stream busy, so the window grows with it
+            HttpVersion::Http2 => builder.http2_prior_knowledge().http2_adaptive_window(true),
+        };
+        // The http3 feature switches reqwest's default TLS to rustls
+        #[cfg(feature = "http3")]
+        let builder = builder.use_native_tls();
+        builder
+    }
+}
+
+/// Uses `endpoints`, `identity` and `http` with the environment on top for
+/// the rest of the process. Call before creating any uploader.
+pub fn init(endpoints: Endpoints, identity: ClientIdentity, http: HttpConfig) {
+    let _ = ENDPOINTS.set(endpoints.with_env());
+    let _ = IDENTITY.set(identity.with_env());
+    let _ = HTTP.set(http);
+}
+
+/// HTTP client for Google APIs, sending the configured identity. Built
+/// once, later calls share its connection pool.
+pub fn client() -> Result<Client, Box<dyn std::error::Error>> {
+    if let Some(client) = CLIENT.get() {
+        return Ok(client.clone());
+    }
+    let identity = IDENTITY.get_or_init(|| ClientIdentity::default().with_env());
+    let http = HTTP.get_or_init(HttpConfig::default);
+    let client = http
+        .builder()
+        .default_headers(identity.headers()?)
+        .build()?;
+    Ok(CLIENT.get_or_init(|| client).clone())
+}
+
+/// HTTP/3 client for upload data, `None` unless the `http` section asks
+/// for HTTP/3. Requests have to ask for HTTP/3 to use it.
+pub fn upload_client() -> Result<Option<Client>, Box<dyn std::error::Error>> {
+    let http = HTTP.get_or_init(HttpConfig::default);
+    if http.version != HttpVersion::Http3 {
+        return Ok(None);
+    }
+    #[cfg(feature = "http3")]
+    {
+        if let Some(client) = UPLOAD_CLIENT.get() {
+            return Ok(Some(client.clone()));
+        }
+        let identity = IDENTITY.get_or_init(|| ClientIdentity::default().with_env());
+        let client = http
+            .builder()
+            .use_rustls_tls()
+            .tls_built_in_root_certs(true)
+            .http3_prior_knowledge()
+            .default_headers(identity.headers()?)
+            .build()?;
+        Ok(Some(UPLOAD_CLIENT.get_or_init(|| client).clone()))
+    }
+    #[cfg(not(feature = "http3"))]
+    Err("HTTP/3 needs a build with the http3 feature".into())
+}
+
+/// The endpoints in effect: production URLs and the environment before
+/// `init`, and the simulator for the API once it runs.
+pub fn current() -> Endpoints {
+    let mut endpoints = ENDPOINTS
+        .get_or_init(|| Endpoints::default().with_env())
+        .clone();
+    if let Some(base_url) = simulator::base_url() {
+        endpoints.upload_base_url = base_url.to_string();
+        endpoints.api_base_url = base_url.to_string();
+    }
+    endpoints
+}
@@ -0,0 +1,80 @@
+use rand::Rng;
+use std::sync::OnceLock;
+
+/// Failures injected by `--inject-failure`, set by `init`.
+static FAULTS: OnceLock<Vec<Fault>> = OnceLock::new();
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FaultKind {
+    /// The request never reaches the server
+    Network,
+    /// The API reports the daily quota as exhausted
+    Quota,
+    /// The API answers with this HTTP status
+    Status(u16),
+}
+
+/// A failure injected into a share of API requests.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fault {
+    pub rate: f64,
+    pub kind: FaultKind,
+}
+
+/// Parses `rate=0.2,kind=network`, where kind is `network`, `quota` or an
+/// HTTP status such as `500`.
+pub fn parse_fault(spec: &str) -> Result<Fault, Box<dyn std::error::Error>> {
+    let mut rate = None;
+    let mut kind = None;
+    for field in spec.split(',').map(str::trim) {
+        match field.split_once('=') {
+            Some(("rate", value)) => {
+                let value: f64 = value
+                    .parse()
+                    .map_err(|_| format!("Invalid failure rate '{}'", value))?;
+                if !(0.0..=1.0).contains(&value) {
+                    return Err(format!("Failure rate {} is not between 0 and 1", value).into());
+                }
+                rate = Some(value);
+            }
+            Some(("kind", "network")) => kind = Some(FaultKind::Network),
+            Some(("kind", "quota")) => kind = Some(FaultKind::Quota),
+            Some(("kind", value)) => match value.parse() {
+                Ok(status @ 400..=599) => kind = Some(FaultKind::Status(status)),
+                _ => {
+                    return Err(format!(
+                        "Unknown failure kind '{}', expected network, quota or an HTTP status",
+                        value
+                    )
+                    .into())
+                }
+            },
+            _ => return Err(format!("Invalid failure field '{}' in '{}'", field, spec).into()),
+        }
+    }
+    Ok(Fault {
+        rate: rate.ok_or_else(|| format!("Missing rate= in '{}'", spec))?,
+        kind: kind.ok_or_else(|| format!("Missing kind= in '{}'", spec))?,
+    })
+}
+
+/// Enables the given failure specs for the rest of the process. Call once
+/// at startup.
+pub fn init(specs: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let faults = specs
+        .iter()
+        .map(|spec| parse_fault(spec))
+        .collect::<Result<Vec<_>, _>>()?;
+    let _ = FAULTS.set(faults);
+    Ok(())
+}
+
+/// Decides whether the next API request fails, and how.
+pub fn roll() -> Option<FaultKind> {
+    let mut rng = rand::thread_rng();
+    FAULTS
+        .get()?
+        .iter()
+        .find(|fault| rng.gen_bool(fault.rate))
+        .map(|fault| fault.kind)
+}
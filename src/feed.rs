@@ -0,0 +1,102 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::expand_tilde;
+
+const FEED_STATE_FILE: &str = "feed_items.json";
+
+/// A scheduled release listed in the "coming up" feed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FeedItem {
+    pub file: String,
+    pub title: String,
+    pub publish_at: DateTime<Utc>,
+    pub video_id: Option<String>,
+}
+
+fn feed_state_path(state_dir: &str) -> PathBuf {
+    Path::new(&expand_tilde(state_dir)).join(FEED_STATE_FILE)
+}
+
+/// Items from earlier runs, so the feed keeps releases from every batch.
+pub fn load_feed_items(state_dir: &str) -> Vec<FeedItem> {
+    fs::read_to_string(feed_state_path(state_dir))
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+/// Merges `new_items` into the stored items (replacing entries for the same
+/// file), drops releases that are already public and writes both the state
+/// and the RSS file.
+pub fn update_feed(
+    state_dir: &str,
+    feed_path: &str,
+    new_items: Vec<FeedItem>,
+    now: DateTime<Utc>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut items = load_feed_items(state_dir);
+    for item in new_items {
+        items.retain(|existing| existing.file != item.file);
+        items.push(item);
+    }
+    items.retain(|item| item.publish_at > now);
+    items.sort_by_key(|item| item.publish_at);
+
+    fs::create_dir_all(expand_tilde(state_dir))?;
+    fs::write(
+        feed_state_path(state_dir),
+        serde_json::to_string_pretty(&items)?,
+    )?;
+    fs::write(expand_tilde(feed_path), render_rss(&items, now))?;
+    Ok(())
+}
+
+pub fn render_rss(items: &[FeedItem], now: DateTime<Utc>) -> String {
+    let mut rss = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <rss version=\"2.0\">\n\
+         <channel>\n\
+         <title>Upcoming releases</title>\n\
+         <link>https://www.youtube.com/</link>\n\
+         <description>Scheduled YouTube uploads</description>\n",
+    );
+    rss.push_str(&format!(
+        "<lastBuildDate>{}</lastBuildDate>\n",
+        now.to_rfc2822()
+    ));
+
+    for item in items {
+        rss.push_str("<item>\n");
+        rss.push_str(&format!("<title>{}</title>\n", escape_xml(&item.title)));
+        rss.push_str(&format!(
+            "<pubDate>{}</pubDate>\n",
+            item.publish_at.to_rfc2822()
+        ));
+        match &item.video_id {
+            Some(video_id) => {
+                let url = format!("https://www.youtube.com/watch?v={}", video_id);
+                rss.push_str(&format!("<link>{}</link>\n", escape_xml(&url)));
+                rss.push_str(&format!("<guid>{}</guid>\n", escape_xml(&url)));
+            }
+            None => rss.push_str(&format!(
+                "<guid isPermaLink=\"false\">{}</guid>\n",
+                escape_xml(&item.file)
+            )),
+        }
+        rss.push_str("</item>\n");
+    }
+
+    rss.push_str("</channel>\n</rss>\n");
+    rss
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
@@ -0,0 +1,513 @@
+use clap::ValueEnum;
+use sha2::{Digest, Sha256};
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::UNIX_EPOCH;
+
+use crate::expand_tilde;
+use crate::ffprobe;
+use crate::spool;
+
+const AUDIO_EXTENSIONS: [&str; 7] = ["mp3", "m4a", "aac", "wav", "flac", "ogg", "opus"];
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TranscodeMode {
+    /// H.264 High / AAC-LC following YouTube's recommended upload settings
+    Recommended,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TranscodeQuality {
+    Fast,
+    Balanced,
+    High,
+}
+
+impl TranscodeQuality {
+    /// x264 preset and CRF for this quality level
+    fn encoder_settings(self) -> (&'static str, &'static str) {
+        match self {
+            TranscodeQuality::Fast => ("veryfast", "23"),
+            TranscodeQuality::Balanced => ("medium", "20"),
+            TranscodeQuality::High => ("slow", "18"),
+        }
+    }
+}
+
+/// Everything that may happen to a source file before it is uploaded.
+#[derive(Debug, Clone, Default)]
+pub struct PreprocessOptions {
+    pub prepend: Option<String>,
+    pub append: Option<String>,
+    pub cover: Option<String>,
+    pub transcode: Option<(TranscodeMode, TranscodeQuality)>,
+}
+
+impl PreprocessOptions {
+    pub fn applies_to(&self, video: &str) -> bool {
+        is_audio_file(video)
+            || self.prepend.is_some()
+            || self.append.is_some()
+            || self.transcode.is_some()
+    }
+}
+
+pub fn is_audio_file(path: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .is_some_and(|ext| AUDIO_EXTENSIONS.contains(&ext.as_str()))
+}
+
+pub fn ffmpeg_available() -> bool {
+    Command::new("ffmpeg")
+        .arg("-version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(false)
+}
+
+/// Directory holding preprocessed videos so re-runs can reuse them.
+pub fn cache_dir(state_dir: &str) -> PathBuf {
+    Path::new(&expand_tilde(state_dir)).join("cache")
+}
+
+/// Derives a cache key from the input files (path, size, mtime) and the
+/// operation, so changing any input or option invalidates the entry.
+pub fn cache_key(inputs: &[&str], operation: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let mut hasher = Sha256::new();
+    hasher.update(operation.as_bytes());
+    for input in inputs {
+        let file_meta = fs::metadata(input)
+            .map_err(|e| format!("Failed to read metadata of '{}': {}", input, e))?;
+        let mtime = file_meta
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        hasher.update(fs::canonicalize(input)?.to_string_lossy().as_bytes());
+        hasher.update(file_meta.len().to_le_bytes());
+        hasher.update(mtime.to_le_bytes());
+    }
+    let digest = hasher.finalize();
+    Ok(digest[..8].iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Returns the cached output path for `video` and `key`, and whether it
+/// already exists.
+fn cached_output(video: &str, key: &str, cache_dir: &Path) -> (PathBuf, bool) {
+    let stem = Path::new(video)
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy();
+    let output = cache_dir.join(format!("{}-{}.mp4", stem, key));
+    let exists = spool::reuse(&output);
+    (output, exists)
+}
+
+pub fn run_ffmpeg(args: &[OsString]) -> Result<(), Box<dyn std::error::Error>> {
+    let output = Command::new("ffmpeg")
+        .args(["-hide_banner", "-loglevel", "error", "-y"])
+        .args(args)
+        .output()
+        .map_err(|e| format!("Failed to run ffmpeg: {}", e))?;
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(format!(
+            "ffmpeg failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+        .into())
+    }
+}
+
+fn concat_output(
+    video: &str,
+    prepend: Option<&str>,
+    append: Option<&str>,
+    cache_dir: &Path,
+) -> Result<(PathBuf, bool), Box<dyn std::error::Error>> {
+    let inputs: Vec<&str> = prepend
+        .into_iter()
+        .chain(std::iter::once(video))
+        .chain(append)
+        .collect();
+    let key = cache_key(&inputs, "concat")?;
+    Ok(cached_output(video, &key, cache_dir))
+}
+
+fn audio_output(
+    audio: &str,
+    cover: Option<&str>,
+    cache_dir: &Path,
+) -> Result<(PathBuf, bool), Box<dyn std::error::Error>> {
+    let inputs: Vec<&str> = std::iter::once(audio).chain(cover).collect();
+    let key = cache_key(&inputs, "audio")?;
+    Ok(cached_output(audio, &key, cache_dir))
+}
+
+fn transcode_output(
+    video: &str,
+    mode: TranscodeMode,
+    quality: TranscodeQuality,
+    cache_dir: &Path,
+) -> Result<(PathBuf, bool), Box<dyn std::error::Error>> {
+    let (preset, crf) = quality.encoder_settings();
+    let key = cache_key(
+        &[video],
+        &format!("transcode-{:?}-{}-{}", mode, preset, crf),
+    )?;
+    Ok(cached_output(video, &key, cache_dir))
+}
+
+/// Joins an optional intro and outro around `video`. The clips are
+/// re-encoded through the concat filter, so they may use different codecs
+/// but must share the video's resolution.
+pub fn concat_clips(
+    video: &str,
+    prepend: Option<&str>,
+    append: Option<&str>,
+    cache_dir: &Path,
+) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let inputs: Vec<&str> = prepend
+        .into_iter()
+        .chain(std::iter::once(video))
+        .chain(append)
+        .collect();
+    let (output, cached) = concat_output(video, prepend, append, cache_dir)?;
+    if cached {
+        return Ok(output);
+    }
+    fs::create_dir_all(cache_dir)?;
+
+    let mut args: Vec<OsString> = Vec::new();
+    let mut filter = String::new();
+    for (i, input) in inputs.iter().enumerate() {
+        args.push("-i".into());
+        args.push(input.into());
+        filter.push_str(&format!("[{i}:v][{i}:a]"));
+    }
+    filter.push_str(&format!("concat=n={}:v=1:a=1[v][a]", inputs.len()));
+
+    let partial = spool::partial_path(&output);
+    args.extend(
+        ["-filter_complex", &filter, "-map", "[v]", "-map", "[a]"]
+            .iter()
+            .map(OsString::from),
+    );
+    args.push(partial.clone().into());
+    run_ffmpeg(&args)?;
+    fs::rename(&partial, &output)?;
+
+    Ok(output)
+}
+
+/// Renders an audio file into a video YouTube accepts: a still `cover`
+/// image when given, otherwise a waveform visualisation.
+pub fn render_audio(
+    audio: &str,
+    cover: Option<&str>,
+    cache_dir: &Path,
+) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let (output, cached) = audio_output(audio, cover, cache_dir)?;
+    if cached {
+        return Ok(output);
+    }
+    fs::create_dir_all(cache_dir)?;
+
+    let mut args: Vec<OsString> = Vec::new();
+    let audio_args: &[&str] = &["-c:a", "aac", "-b:a", "192k"];
+    match cover {
+        Some(cover) => {
+            args.extend(["-loop", "1", "-i", cover, "-i", audio].map(OsString::from));
+            args.extend(
+                [
+                    "-map",
+                    "0:v",
+                    "-map",
+                    "1:a",
+                    "-vf",
+                    "scale=trunc(iw/2)*2:trunc(ih/2)*2,format=yuv420p",
+                    "-c:v",
+                    "libx264",
+                    "-tune",
+                    "stillimage",
+                    "-shortest",
+                ]
+                .map(OsString::from),
+            );
+        }
+        None => {
+            args.extend(
+                [
+                    "-i",
+                    audio,
+                    "-filter_complex",
+                    "[0:a]showwaves=s=1280x720:mode=cline,format=yuv420p[v]",
+                    "-map",
+                    "[v]",
+                    "-map",
+                    "0:a",
+                    "-c:v",
+                    "libx264",
+                ]
+                .map(OsString::from),
+            );
+        }
+    }
+    args.extend(audio_args.iter().map(OsString::from));
+
+    let partial = spool::partial_path(&output);
+    args.push(partial.clone().into());
+    run_ffmpeg(&args)?;
+    fs::rename(&partial, &output)?;
+
+    Ok(output)
+}
+
+/// Re-encodes `video` with YouTube's recommended settings: H.264 High
+/// profile, closed GOP with 2 B-frames, AAC-LC 48 kHz stereo and the moov
+/// atom at the front.
+pub fn transcode(
+    video: &str,
+    mode: TranscodeMode,
+    quality: TranscodeQuality,
+    cache_dir: &Path,
+) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let (preset, crf) = quality.encoder_settings();
+    let (output, cached) = transcode_output(video, mode, quality, cache_dir)?;
+    if cached {
+        return Ok(output);
+    }
+    fs::create_dir_all(cache_dir)?;
+
+    let partial = spool::partial_path(&output);
+    let mut args: Vec<OsString> = [
+        "-i",
+        video,
+        "-c:v",
+        "libx264",
+        "-profile:v",
+        "high",
+        "-pix_fmt",
+        "yuv420p",
+        "-preset",
+        preset,
+        "-crf",
+        crf,
+        "-bf",
+        "2",
+        "-flags",
+        "+cgop",
+        "-c:a",
+        "aac",
+        "-b:a",
+        "384k",
+        "-ar",
+        "48000",
+        "-ac",
+        "2",
+        "-movflags",
+        "+faststart",
+    ]
+    .map(OsString::from)
+    .to_vec();
+    args.push(partial.clone().into());
+    run_ffmpeg(&args)?;
+    fs::rename(&partial, &output)?;
+
+    Ok(output)
+}
+
+/// Where an automatic thumbnail is taken from its video.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ThumbnailOffset {
+    Seconds(f64),
+    /// Share of the duration, from 0 to 100
+    Percent(f64),
+}
+
+impl ThumbnailOffset {
+    /// Parses a share like `10%` or a timestamp like `00:12:34`.
+    pub fn parse(offset: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let offset = offset.trim();
+        let invalid = || {
+            format!(
+                "Invalid thumbnail offset '{}', use e.g. 10% or 00:12:34",
+                offset
+            )
+        };
+        match offset.strip_suffix('%') {
+            Some(percent) => percent
+                .trim()
+                .parse::<f64>()
+                .ok()
+                .filter(|percent| (0.0..=100.0).contains(percent))
+                .map(ThumbnailOffset::Percent)
+                .ok_or_else(|| invalid().into()),
+            None => ffprobe::parse_timestamp(offset)
+                .filter(|seconds| *seconds >= 0.0)
+                .map(ThumbnailOffset::Seconds)
+                .ok_or_else(|| invalid().into()),
+        }
+    }
+}
+
+/// Extracts the frame at `offset` into `video` as a JPEG thumbnail, cached
+/// like preprocessed videos.
+pub fn extract_thumbnail(
+    video: &str,
+    offset: ThumbnailOffset,
+    cache_dir: &Path,
+) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let seconds = match offset {
+        ThumbnailOffset::Seconds(seconds) => seconds,
+        ThumbnailOffset::Percent(percent) => ffprobe::probe_duration(video)? * percent / 100.0,
+    };
+    let key = cache_key(&[video], &format!("thumbnail-{:.3}", seconds))?;
+    let output = cached_output(video, &key, cache_dir)
+        .0
+        .with_extension("jpg");
+    if spool::reuse(&output) {
+        return Ok(output);
+    }
+    fs::create_dir_all(cache_dir)?;
+
+    let seconds = format!("{:.3}", seconds);
+    let mut args: Vec<OsString> = ["-ss", &seconds, "-i", video, "-frames:v", "1", "-q:v", "2"]
+        .map(OsString::from)
+        .to_vec();
+    args.push(output.clone().into());
+    run_ffmpeg(&args)?;
+    if !output.exists() {
+        return Err(format!("No frame at {}s of '{}'", seconds, video).into());
+    }
+    Ok(output)
+}
+
+/// Bytes `preprocess` still has to write to the cache for `video`, taking
+/// every step's output to be about the size of its inputs. Steps whose
+/// output is cached need nothing.
+pub fn pending_cache_bytes(
+    video: &str,
+    options: &PreprocessOptions,
+    cache_dir: &Path,
+) -> Result<u64, Box<dyn std::error::Error>> {
+    Ok(walk_cache(video, options, cache_dir)?.needed)
+}
+
+/// Outputs of the preprocessing steps for `video` that are in the cache.
+pub fn cached_files(
+    video: &str,
+    options: &PreprocessOptions,
+    cache_dir: &Path,
+) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    Ok(walk_cache(video, options, cache_dir)?.cached)
+}
+
+/// The preprocessing steps of one video, followed through the cache.
+struct CacheWalk {
+    /// Input of the next step while every step so far is cached
+    current: Option<String>,
+    cached: Vec<PathBuf>,
+    needed: u64,
+}
+
+impl CacheWalk {
+    /// Moves past one step, given its cached output or `None` when an
+    /// earlier step has to run first. Returns the size of the step's output.
+    fn step(&mut self, output: Option<(PathBuf, bool)>, size: u64) -> u64 {
+        match output {
+            Some((output, true)) => {
+                let size = fs::metadata(&output).map(|m| m.len()).unwrap_or(0);
+                self.current = Some(output.to_string_lossy().into_owned());
+                self.cached.push(output);
+                size
+            }
+            _ => {
+                self.current = None;
+                self.needed += size;
+                size
+            }
+        }
+    }
+}
+
+fn walk_cache(
+    video: &str,
+    options: &PreprocessOptions,
+    cache_dir: &Path,
+) -> Result<CacheWalk, Box<dyn std::error::Error>> {
+    let file_size = |path: &str| fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+    let mut walk = CacheWalk {
+        current: Some(video.to_string()),
+        cached: Vec::new(),
+        needed: 0,
+    };
+    let mut size = file_size(video);
+
+    if is_audio_file(video) {
+        let cover = options.cover.as_deref();
+        let output = walk
+            .current
+            .as_deref()
+            .map(|input| audio_output(input, cover, cache_dir))
+            .transpose()?;
+        size = walk.step(output, size + cover.map_or(0, file_size));
+    }
+    if options.prepend.is_some() || options.append.is_some() {
+        let (prepend, append) = (options.prepend.as_deref(), options.append.as_deref());
+        let output = walk
+            .current
+            .as_deref()
+            .map(|input| concat_output(input, prepend, append, cache_dir))
+            .transpose()?;
+        let clips = prepend.map_or(0, file_size) + append.map_or(0, file_size);
+        size = walk.step(output, size + clips);
+    }
+    if let Some((mode, quality)) = options.transcode {
+        let output = walk
+            .current
+            .as_deref()
+            .map(|input| transcode_output(input, mode, quality, cache_dir))
+            .transpose()?;
+        walk.step(output, size);
+    }
+    Ok(walk)
+}
+
+/// Runs every applicable preprocessing step on `video` in order (audio
+/// rendering, intro/outro, transcode) and returns the file to upload.
+pub fn preprocess(
+    video: &str,
+    options: &PreprocessOptions,
+    cache_dir: &Path,
+) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let mut current = PathBuf::from(video);
+
+    if is_audio_file(video) {
+        crate::info!("Rendering audio {} into a video", video);
+        current = render_audio(video, options.cover.as_deref(), cache_dir)?;
+    }
+    if options.prepend.is_some() || options.append.is_some() {
+        crate::info!("Joining intro/outro for {}", video);
+        current = concat_clips(
+            &current.to_string_lossy(),
+            options.prepend.as_deref(),
+            options.append.as_deref(),
+            cache_dir,
+        )?;
+    }
+    if let Some((mode, quality)) = options.transcode {
+        crate::info!("Transcoding {}", video);
+        current = transcode(&current.to_string_lossy(), mode, quality, cache_dir)?;
+    }
+
+    Ok(current)
+}
@@ -0,0 +1,304 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use clap::ValueEnum;
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChapterSource {
+    /// Chapter markers embedded in the video container
+    FromContainer,
+    /// `<video>.chapters.txt` sidecar with one `MM:SS Title` per line
+    FromFile,
+    Off,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MetadataSource {
+    /// Titles from file names, descriptions from the profile
+    Generated,
+    /// Title, comment/description and date tags embedded in the container
+    Container,
+}
+
+/// How `--archive-mode` dates re-uploaded videos.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArchiveMode {
+    /// Recording date from the original timestamp
+    Date,
+    /// Recording date, and descriptions starting with "Originally recorded on <date>"
+    Note,
+}
+
+/// Metadata tags an editor may have written into the container.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ContainerTags {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub date: Option<DateTime<Utc>>,
+}
+
+/// Dimensions of the first video stream as displayed (rotation applied).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VideoInfo {
+    pub width: u64,
+    pub height: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chapter {
+    pub start_seconds: f64,
+    pub title: String,
+}
+
+pub fn ffprobe_available() -> bool {
+    Command::new("ffprobe")
+        .arg("-version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(false)
+}
+
+/// Runs ffprobe with JSON output and returns the parsed document.
+pub fn ffprobe_json(
+    path: &str,
+    show: &[&str],
+) -> Result<serde_json::Value, Box<dyn std::error::Error>> {
+    let output = Command::new("ffprobe")
+        .args(["-v", "error", "-print_format", "json"])
+        .args(show)
+        .arg(path)
+        .output()
+        .map_err(|e| format!("Failed to run ffprobe: {}", e))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "ffprobe failed on '{}': {}",
+            path,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+        .into());
+    }
+    Ok(serde_json::from_slice(&output.stdout)?)
+}
+
+pub fn probe_duration(path: &str) -> Result<f64, Box<dyn std::error::Error>> {
+    let probe = ffprobe_json(path, &["-show_format"])?;
+    probe["format"]["duration"]
+        .as_str()
+        .and_then(|d| d.parse().ok())
+        .ok_or_else(|| format!("No duration reported for '{}'", path).into())
+}
+
+pub fn probe_video_info(path: &str) -> Result<VideoInfo, Box<dyn std::error::Error>> {
+    let probe = ffprobe_json(path, &["-show_streams", "-select_streams", "v:0"])?;
+    let stream = &probe["streams"][0];
+    let (width, height) = match (stream["width"].as_u64(), stream["height"].as_u64()) {
+        (Some(width), Some(height)) => (width, height),
+        _ => return Err(format!("No video stream found in '{}'", path).into()),
+    };
+
+    // Phones record landscape frames with a rotation flag for portrait video
+    let rotation = stream["side_data_list"]
+        .as_array()
+        .and_then(|side_data| side_data.iter().find_map(|d| d["rotation"].as_i64()))
+        .or_else(|| stream["tags"]["rotate"].as_str()?.parse().ok())
+        .unwrap_or(0);
+
+    if rotation.abs() % 180 == 90 {
+        Ok(VideoInfo {
+            width: height,
+            height: width,
+        })
+    } else {
+        Ok(VideoInfo { width, height })
+    }
+}
+
+pub fn probe_chapters(path: &str) -> Result<Vec<Chapter>, Box<dyn std::error::Error>> {
+    let probe = ffprobe_json(path, &["-show_chapters"])?;
+    let chapters = probe["chapters"]
+        .as_array()
+        .map(|chapters| {
+            chapters
+                .iter()
+                .enumerate()
+                .filter_map(|(i, chapter)| {
+                    let start_seconds = chapter["start_time"].as_str()?.parse().ok()?;
+                    let title = chapter["tags"]["title"]
+                        .as_str()
+                        .map(str::to_string)
+                        .unwrap_or_else(|| format!("Chapter {}", i + 1));
+                    Some(Chapter {
+                        start_seconds,
+                        title,
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    Ok(chapters)
+}
+
+pub fn probe_tags(path: &str) -> Result<ContainerTags, Box<dyn std::error::Error>> {
+    let probe = ffprobe_json(path, &["-show_format"])?;
+    // Tag keys vary in case between muxers (TITLE in MKV, title in MP4)
+    let tag = |names: &[&str]| -> Option<String> {
+        let tags = probe["format"]["tags"].as_object()?;
+        names.iter().find_map(|name| {
+            tags.iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                .and_then(|(_, value)| value.as_str())
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+                .map(str::to_string)
+        })
+    };
+
+    Ok(ContainerTags {
+        title: tag(&["title"]),
+        description: tag(&["description", "comment", "synopsis"]),
+        date: tag(&["date", "creation_time", "date_recorded"]).and_then(|d| parse_tag_date(&d)),
+    })
+}
+
+/// When `path` was originally recorded: the container's date tag when
+/// ffprobe is available and finds one, otherwise the file's modification
+/// time, which copies and backups usually keep.
+pub fn original_date(path: &str) -> Result<DateTime<Utc>, Box<dyn std::error::Error>> {
+    if ffprobe_available() {
+        if let Some(date) = probe_tags(path)?.date {
+            return Ok(date);
+        }
+    }
+    Ok(fs::metadata(path)?.modified()?.into())
+}
+
+/// Accepts the date formats commonly found in tags: RFC 3339,
+/// `YYYY-MM-DD` and `YYYYMMDD`.
+pub fn parse_tag_date(date: &str) -> Option<DateTime<Utc>> {
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(date) {
+        return Some(datetime.with_timezone(&Utc));
+    }
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .or_else(|_| NaiveDate::parse_from_str(date, "%Y%m%d"))
+        .ok()
+        .and_then(|d| d.and_hms_opt(0, 0, 0))
+        .map(|d| d.and_utc())
+}
+
+/// Loads chapters for `video` from the requested source. A missing sidecar
+/// simply means the video has no chapters.
+pub fn load_chapters(
+    video: &str,
+    source: ChapterSource,
+) -> Result<Vec<Chapter>, Box<dyn std::error::Error>> {
+    match source {
+        ChapterSource::FromContainer => probe_chapters(video),
+        ChapterSource::FromFile => {
+            let path = chapters_file_path(video);
+            if path.exists() {
+                read_chapters_file(&path)
+            } else {
+                Ok(Vec::new())
+            }
+        }
+        ChapterSource::Off => Ok(Vec::new()),
+    }
+}
+
+/// Shifts chapters back by a prepended intro and adds a chapter for it.
+pub fn offset_chapters(chapters: Vec<Chapter>, intro_seconds: f64) -> Vec<Chapter> {
+    if chapters.is_empty() || intro_seconds <= 0.0 {
+        return chapters;
+    }
+    std::iter::once(Chapter {
+        start_seconds: 0.0,
+        title: "Intro".to_string(),
+    })
+    .chain(chapters.into_iter().map(|c| Chapter {
+        start_seconds: c.start_seconds + intro_seconds,
+        ..c
+    }))
+    .collect()
+}
+
+/// Path of the chapter sidecar for `video`, e.g. `ep1.mkv` -> `ep1.chapters.txt`
+pub fn chapters_file_path(video: &str) -> std::path::PathBuf {
+    Path::new(video).with_extension("chapters.txt")
+}
+
+pub fn read_chapters_file(path: &Path) -> Result<Vec<Chapter>, Box<dyn std::error::Error>> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read chapters from '{}': {}", path.display(), e))?;
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            let (timestamp, title) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            let start_seconds = parse_timestamp(timestamp)
+                .ok_or_else(|| format!("Invalid chapter timestamp '{}'", timestamp))?;
+            Ok(Chapter {
+                start_seconds,
+                title: title.trim().to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Parses `SS`, `MM:SS` or `HH:MM:SS` (seconds may be fractional).
+pub fn parse_timestamp(timestamp: &str) -> Option<f64> {
+    timestamp.split(':').try_fold(0.0, |total, part| {
+        Some(total * 60.0 + part.parse::<f64>().ok()?)
+    })
+}
+
+fn format_timestamp(seconds: f64, with_hours: bool) -> String {
+    let seconds = seconds as u64;
+    if with_hours {
+        format!(
+            "{}:{:02}:{:02}",
+            seconds / 3600,
+            (seconds / 60) % 60,
+            seconds % 60
+        )
+    } else {
+        format!("{:02}:{:02}", seconds / 60, seconds % 60)
+    }
+}
+
+/// Formats chapters the way YouTube detects them in a description. YouTube
+/// only shows chapters when the first starts at 00:00, there are at least
+/// three of them and each is 10 seconds or longer, so violations are warned
+/// about rather than silently ignored by YouTube.
+pub fn format_chapters(chapters: &[Chapter]) -> String {
+    let with_hours = chapters.iter().any(|c| c.start_seconds >= 3600.0);
+
+    if chapters.len() < 3 {
+        crate::warning!("YouTube needs at least 3 chapters to display them");
+    }
+    if chapters.first().is_some_and(|c| c.start_seconds >= 1.0) {
+        crate::warning!("first chapter doesn't start at 00:00, YouTube will ignore chapters");
+    }
+    if chapters
+        .windows(2)
+        .any(|pair| pair[1].start_seconds - pair[0].start_seconds < 10.0)
+    {
+        crate::warning!("chapters shorter than 10 seconds, YouTube will ignore chapters");
+    }
+
+    chapters
+        .iter()
+        .map(|c| {
+            format!(
+                "{} {}",
+                format_timestamp(c.start_seconds, with_hours),
+                c.title
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
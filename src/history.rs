@@ -0,0 +1,240 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::UNIX_EPOCH;
+
+use crate::expand_tilde;
+
+const HISTORY_FILE: &str = "uploaded.json";
+const FINGERPRINT_FILE: &str = "fingerprints.json";
+const SAMPLE_BYTES: u64 = 4 * 1024 * 1024;
+
+/// Fingerprint cache of this process, set by `init_fingerprint_cache`.
+static FINGERPRINTS: OnceLock<FingerprintCache> = OnceLock::new();
+
+struct FingerprintCache {
+    path: PathBuf,
+    entries: Mutex<HashMap<String, CachedFingerprint>>,
+}
+
+/// A fingerprint, valid while its file keeps the same size and mtime.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct CachedFingerprint {
+    size: u64,
+    modified_ns: u128,
+    fingerprint: String,
+}
+
+/// A file uploaded by an earlier run.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UploadRecord {
+    pub fingerprint: String,
+    pub video_id: String,
+    pub uploaded_at: DateTime<Utc>,
+    #[serde(default)]
+    pub publish_at: Option<DateTime<Utc>>,
+    /// Title suffix of the A/B rotation the video got
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title_variant: Option<String>,
+}
+
+/// Successful uploads keyed by canonical file path.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct UploadHistory {
+    uploads: HashMap<String, UploadRecord>,
+}
+
+fn history_path(state_dir: &str) -> PathBuf {
+    Path::new(&expand_tilde(state_dir)).join(HISTORY_FILE)
+}
+
+fn history_key(file: &str) -> String {
+    fs::canonicalize(file)
+        .map(|path| path.to_string_lossy().to_string())
+        .unwrap_or_else(|_| file.to_string())
+}
+
+/// Keeps fingerprints in the state directory for the rest of the process,
+/// so files unchanged since an earlier run are not read again.
+pub fn init_fingerprint_cache(state_dir: &str) {
+    let path = Path::new(&expand_tilde(state_dir)).join(FINGERPRINT_FILE);
+    let entries = fs::read_to_string(&path)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default();
+    let _ = FINGERPRINTS.set(FingerprintCache {
+        path,
+        entries: Mutex::new(entries),
+    });
+}
+
+/// Hashes the size plus the first, middle and last few MiB, which tells
+/// re-exports apart without reading whole multi-GB files. Cached by path,
+/// size and mtime once `init_fingerprint_cache` ran.
+pub fn fingerprint(file: &str) -> io::Result<String> {
+    let Some(cache) = FINGERPRINTS.get() else {
+        return hash_samples(file);
+    };
+    let metadata = fs::metadata(file)?;
+    let modified_ns = metadata
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_nanos());
+    let key = history_key(file);
+    let cached = cache.entries.lock().unwrap().get(&key).cloned();
+    if let Some(cached) =
+        cached.filter(|cached| cached.size == metadata.len() && cached.modified_ns == modified_ns)
+    {
+        return Ok(cached.fingerprint);
+    }
+
+    let fingerprint = hash_samples(file)?;
+    let mut entries = cache.entries.lock().unwrap();
+    entries.insert(
+        key,
+        CachedFingerprint {
+            size: metadata.len(),
+            modified_ns,
+            fingerprint: fingerprint.clone(),
+        },
+    );
+    // Best effort, a lost cache only costs hashing again
+    if let Some(parent) = cache.path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    if let Ok(json) = serde_json::to_string(&*entries) {
+        let _ = fs::write(&cache.path, json);
+    }
+    Ok(fingerprint)
+}
+
+/// Fingerprints `files` on up to `jobs` threads, filling the cache. Errors
+/// are left for whoever needs the fingerprint to report.
+pub fn prefetch_fingerprints(files: &[String], jobs: usize) {
+    let chunk_len = files.len().div_ceil(jobs.max(1)).max(1);
+    std::thread::scope(|scope| {
+        for chunk in files.chunks(chunk_len) {
+            scope.spawn(move || {
+                for file in chunk {
+                    let _ = fingerprint(file);
+                }
+            });
+        }
+    });
+}
+
+/// Position right after `cursor` in `files`, matching it by path or by a
+/// fingerprint prefix of at least 8 hex digits as in the upload history.
+pub fn position_after(files: &[String], cursor: &str) -> Option<usize> {
+    let cursor_key = history_key(cursor);
+    if let Some(i) = files
+        .iter()
+        .position(|file| history_key(file) == cursor_key)
+    {
+        return Some(i + 1);
+    }
+    if cursor.len() < 8 || !cursor.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let prefix = cursor.to_ascii_lowercase();
+    files
+        .iter()
+        .position(|file| {
+            fingerprint(file).is_ok_and(|fingerprint| fingerprint.starts_with(&prefix))
+        })
+        .map(|i| i + 1)
+}
+
+fn hash_samples(file: &str) -> io::Result<String> {
+    let mut input = File::open(file)?;
+    let size = input.metadata()?.len();
+    let mut hasher = Sha256::new();
+    hasher.update(size.to_le_bytes());
+
+    let mut buffer = Vec::new();
+    for offset in [0, size / 2, size.saturating_sub(SAMPLE_BYTES)] {
+        input.seek(SeekFrom::Start(offset))?;
+        buffer.clear();
+        (&mut input).take(SAMPLE_BYTES).read_to_end(&mut buffer)?;
+        hasher.update(&buffer);
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+impl UploadHistory {
+    /// Loads the history, treating a missing file as empty.
+    pub fn load(state_dir: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let path = history_path(state_dir);
+        match fs::read_to_string(&path) {
+            Ok(content) => Ok(serde_json::from_str(&content)
+                .map_err(|e| format!("Failed to parse '{}': {}", path.display(), e))?),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(format!("Failed to read '{}': {}", path.display(), e).into()),
+        }
+    }
+
+    /// Prefetches fingerprints of the `files` `find` would hash, those with
+    /// a record, on `jobs` threads.
+    pub fn prefetch(&self, files: &[String], jobs: usize) {
+        let recorded: Vec<String> = files
+            .iter()
+            .filter(|file| self.uploads.contains_key(&history_key(file)))
+            .cloned()
+            .collect();
+        prefetch_fingerprints(&recorded, jobs);
+    }
+
+    /// The earlier upload of `file`, if its path and content both match.
+    pub fn find(&self, file: &str) -> Option<&UploadRecord> {
+        let record = self.uploads.get(&history_key(file))?;
+        let current = fingerprint(file).ok()?;
+        (record.fingerprint == current).then_some(record)
+    }
+
+    /// Publish times of recorded uploads that are still in the future.
+    pub fn upcoming_publish_times(&self, now: DateTime<Utc>) -> Vec<DateTime<Utc>> {
+        let mut times: Vec<_> = self
+            .uploads
+            .values()
+            .filter_map(|record| record.publish_at)
+            .filter(|publish_at| *publish_at > now)
+            .collect();
+        times.sort();
+        times
+    }
+
+    /// Adds a successful upload and writes the history back.
+    pub fn record(
+        state_dir: &str,
+        file: &str,
+        video_id: &str,
+        publish_at: DateTime<Utc>,
+        title_variant: Option<&str>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut history = Self::load(state_dir)?;
+        history.uploads.insert(
+            history_key(file),
+            UploadRecord {
+                fingerprint: fingerprint(file)?,
+                video_id: video_id.to_string(),
+                uploaded_at: Utc::now(),
+                publish_at: Some(publish_at),
+                title_variant: title_variant.map(str::to_string),
+            },
+        );
+
+        let path = history_path(state_dir);
+        fs::create_dir_all(path.parent().unwrap_or(Path::new(".")))?;
+        fs::write(&path, serde_json::to_string_pretty(&history)?)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,242 @@
+use chrono::{DateTime, Utc};
+use reqwest::header::HeaderMap;
+use reqwest::{RequestBuilder, Response};
+use serde_json::{json, Value};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+use url::Url;
+
+use crate::clock;
+use crate::expand_tilde;
+use crate::redact::{self, REDACTED, SECRET_HEADERS, SECRET_PARAMS};
+
+/// Capture file of this process, set by `init`.
+static CAPTURE: OnceLock<Capture> = OnceLock::new();
+
+struct Capture {
+    path: PathBuf,
+    /// HAR is one document, so its entries are kept and rewritten every
+    /// time; anything else gets one NDJSON line per exchange
+    har: Option<Mutex<Vec<Value>>>,
+}
+
+/// One request and its response or error, without bodies.
+struct Exchange {
+    started_at: DateTime<Utc>,
+    duration_ms: u64,
+    method: String,
+    url: String,
+    request_headers: Vec<(String, String)>,
+    request_body_bytes: Option<usize>,
+    status: Option<u16>,
+    response_headers: Vec<(String, String)>,
+    error: Option<String>,
+}
+
+/// Records every Google API call to `path` for the rest of the process, as
+/// HAR when the file name ends in `.har` and NDJSON otherwise. Credentials
+/// are redacted and bodies left out.
+pub fn init(path: &str) -> io::Result<()> {
+    let path = PathBuf::from(expand_tilde(path));
+    let har = path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("har"));
+    fs::write(&path, "")?;
+    let _ = CAPTURE.set(Capture {
+        path,
+        har: har.then(|| Mutex::new(Vec::new())),
+    });
+    Ok(())
+}
+
+/// Sends `request` like `RequestBuilder::send`, recording it with
+/// `--debug-http`.
+pub async fn send(request: RequestBuilder) -> reqwest::Result<Response> {
+    let (client, request) = request.build_split();
+    let request = request?;
+    let Some(capture) = CAPTURE.get() else {
+        let result = client.execute(request).await;
+        if let Ok(response) = &result {
+            clock::check_response(response.headers());
+        }
+        return result;
+    };
+
+    let started_at = Utc::now();
+    let started = Instant::now();
+    let method = request.method().to_string();
+    let url = redact_url(request.url().as_str());
+    let request_headers = redact_headers(request.headers());
+    let request_body_bytes = request
+        .body()
+        .and_then(|body| body.as_bytes())
+        .map(<[u8]>::len);
+
+    let result = client.execute(request).await;
+    if let Ok(response) = &result {
+        clock::check_response(response.headers());
+    }
+    let exchange = Exchange {
+        started_at,
+        duration_ms: started.elapsed().as_millis() as u64,
+        method,
+        url,
+        request_headers,
+        request_body_bytes,
+        status: result.as_ref().ok().map(|r| r.status().as_u16()),
+        response_headers: result
+            .as_ref()
+            .map(|r| redact_headers(r.headers()))
+            .unwrap_or_default(),
+        error: result
+            .as_ref()
+            .err()
+            .map(|e| redact::redact(&e.to_string())),
+    };
+    if let Err(e) = capture.record(&exchange) {
+        crate::warning!(
+            "Could not write HTTP capture {}: {}",
+            capture.path.display(),
+            e
+        );
+    }
+    result
+}
+
+impl Capture {
+    fn record(&self, exchange: &Exchange) -> io::Result<()> {
+        match &self.har {
+            Some(entries) => {
+                let mut entries = entries.lock().unwrap();
+                entries.push(exchange.to_har_entry());
+                let har = json!({
+                    "log": {
+                        "version": "1.2",
+                        "creator": {
+                            "name": env!("CARGO_PKG_NAME"),
+                            "version": env!("CARGO_PKG_VERSION")
+                        },
+                        "entries": *entries
+                    }
+                });
+                fs::write(&self.path, serde_json::to_string_pretty(&har)?)
+            }
+            None => {
+                let mut file = OpenOptions::new().append(true).open(&self.path)?;
+                writeln!(file, "{}", exchange.to_json())
+            }
+        }
+    }
+}
+
+impl Exchange {
+    fn to_json(&self) -> Value {
+        let headers = |headers: &[(String, String)]| {
+            headers
+                .iter()
+                .map(|(name, value)| (name.clone(), Value::from(value.as_str())))
+                .collect::<serde_json::Map<_, _>>()
+        };
+        json!({
+            "started_at": self.started_at,
+            "duration_ms": self.duration_ms,
+            "method": self.method,
+            "url": self.url,
+            "request_headers": headers(&self.request_headers),
+            "request_body_bytes": self.request_body_bytes,
+            "status": self.status,
+            "response_headers": headers(&self.response_headers),
+            "error": self.error,
+        })
+    }
+
+    fn to_har_entry(&self) -> Value {
+        let headers = |headers: &[(String, String)]| {
+            headers
+                .iter()
+                .map(|(name, value)| json!({ "name": name, "value": value }))
+                .collect::<Vec<_>>()
+        };
+        let query: Vec<_> = Url::parse(&self.url)
+            .map(|url| {
+                url.query_pairs()
+                    .map(|(name, value)| json!({ "name": name, "value": value }))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let mut entry = json!({
+            "startedDateTime": self.started_at,
+            "time": self.duration_ms,
+            "request": {
+                "method": self.method,
+                "url": self.url,
+                "httpVersion": "HTTP/1.1",
+                "cookies": [],
+                "headers": headers(&self.request_headers),
+                "queryString": query,
+                "headersSize": -1,
+                "bodySize": self.request_body_bytes.map_or(-1, |bytes| bytes as i64)
+            },
+            "response": {
+                // HAR uses status 0 for requests without a response
+                "status": self.status.unwrap_or(0),
+                "statusText": "",
+                "httpVersion": "HTTP/1.1",
+                "cookies": [],
+                "headers": headers(&self.response_headers),
+                "content": { "size": -1, "mimeType": "" },
+                "redirectURL": "",
+                "headersSize": -1,
+                "bodySize": -1
+            },
+            "cache": {},
+            "timings": { "send": 0, "wait": self.duration_ms, "receive": 0 }
+        });
+        if let Some(error) = &self.error {
+            entry["_error"] = Value::from(error.as_str());
+        }
+        entry
+    }
+}
+
+/// `url` with the values of credential parameters replaced.
+pub fn redact_url(url: &str) -> String {
+    let Ok(mut parsed) = Url::parse(url) else {
+        return url.to_string();
+    };
+    if parsed.query().is_none() {
+        return url.to_string();
+    }
+    let pairs: Vec<(String, String)> = parsed
+        .query_pairs()
+        .map(|(name, value)| {
+            let value = if SECRET_PARAMS.contains(&name.to_ascii_lowercase().as_str()) {
+                REDACTED.to_string()
+            } else {
+                value.into_owned()
+            };
+            (name.into_owned(), value)
+        })
+        .collect();
+    parsed.query_pairs_mut().clear().extend_pairs(pairs);
+    parsed.to_string()
+}
+
+fn redact_headers(headers: &HeaderMap) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let value = if SECRET_HEADERS.contains(&name.as_str()) {
+                REDACTED.to_string()
+            } else if name == "location" {
+                redact_url(value.to_str().unwrap_or_default())
+            } else {
+                value.to_str().unwrap_or("[binary]").to_string()
+            };
+            (name.to_string(), value)
+        })
+        .collect()
+}
@@ -0,0 +1,230 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufRead, BufReader, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use crate::expand_tilde;
+use crate::redact;
+use crate::report::BatchStats;
+
+const JOURNAL_FILE: &str = "journal.ndjson";
+
+/// Journal path and run ID of this process, set by `init`.
+static JOURNAL: OnceLock<(PathBuf, String)> = OnceLock::new();
+
+/// Something that happened during a run, one JSON line per event.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    ScheduleComputed {
+        videos: usize,
+        first: Option<DateTime<Utc>>,
+        last: Option<DateTime<Utc>>,
+    },
+    Authenticated {
+        /// `stored`, `refreshed` or `authorized`
+        method: String,
+    },
+    UploadStarted {
+        file: String,
+    },
+    UploadSucceeded {
+        file: String,
+        video_id: String,
+    },
+    UploadFailed {
+        file: String,
+        error: String,
+    },
+    ApiError {
+        endpoint: String,
+        status: u16,
+        message: String,
+    },
+    BatchFinished(BatchStats),
+    ProcessingProblem {
+        file: String,
+        video_id: String,
+        problem: String,
+    },
+    Reuploaded {
+        file: String,
+        /// The deleted video
+        old_video_id: String,
+        video_id: String,
+        reason: String,
+    },
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Event::ScheduleComputed {
+                videos,
+                first,
+                last,
+            } => {
+                write!(f, "schedule computed for {} videos", videos)?;
+                if let (Some(first), Some(last)) = (first, last) {
+                    write!(
+                        f,
+                        " ({} to {})",
+                        first.format("%Y-%m-%d %H:%M"),
+                        last.format("%Y-%m-%d %H:%M")
+                    )?;
+                }
+                Ok(())
+            }
+            Event::Authenticated { method } => write!(f, "authenticated ({})", method),
+            Event::UploadStarted { file } => write!(f, "upload started: {}", file),
+            Event::UploadSucceeded { file, video_id } => {
+                write!(f, "upload succeeded: {} (ID: {})", file, video_id)
+            }
+            Event::UploadFailed { file, error } => {
+                write!(f, "upload failed: {}: {}", file, error)
+            }
+            Event::ApiError {
+                endpoint,
+                status,
+                message,
+            } => write!(f, "API error from {}: {} {}", endpoint, status, message),
+            Event::BatchFinished(stats) => write!(
+                f,
+                "batch finished: {} uploaded, {} failed, {} bytes sent",
+                stats.succeeded, stats.failed, stats.bytes
+            ),
+            Event::ProcessingProblem {
+                file,
+                video_id,
+                problem,
+            } => write!(
+                f,
+                "processing problem: {} (ID: {}): {}",
+                file, video_id, problem
+            ),
+            Event::Reuploaded {
+                file,
+                old_video_id,
+                video_id,
+                reason,
+            } => write!(
+                f,
+                "re-uploaded: {} (ID: {}, replaces {} after {})",
+                file, video_id, old_video_id, reason
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub at: DateTime<Utc>,
+    /// Groups the entries of one invocation
+    pub run: String,
+    #[serde(flatten)]
+    pub event: Event,
+}
+
+impl fmt::Display for JournalEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} [{}] {}",
+            self.at.format("%Y-%m-%d %H:%M:%S"),
+            self.run,
+            self.event
+        )
+    }
+}
+
+pub fn journal_path(state_dir: &str) -> PathBuf {
+    Path::new(&expand_tilde(state_dir)).join(JOURNAL_FILE)
+}
+
+/// Starts journaling to the state dir for the rest of the process.
+pub fn init(state_dir: &str) {
+    let run = format!(
+        "{}-{}",
+        Utc::now().format("%Y%m%dT%H%M%S"),
+        std::process::id()
+    );
+    let _ = JOURNAL.set((journal_path(state_dir), run));
+}
+
+/// Appends `event` to the journal and emits it with `--output json`. Does
+/// nothing before `init`, and a journal that can't be written is warned
+/// about but never stops a run.
+pub fn record(event: Event) {
+    let Some((path, run)) = JOURNAL.get() else {
+        return;
+    };
+    let entry = JournalEntry {
+        at: Utc::now(),
+        run: run.clone(),
+        event,
+    };
+    crate::output::emit(&entry);
+    if let Err(e) = append(path, &entry) {
+        crate::warning!("Could not write journal {}: {}", path.display(), e);
+    }
+}
+
+fn append(path: &Path, entry: &JournalEntry) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", redact::redact(&serde_json::to_string(entry)?))
+}
+
+/// Reads every entry, skipping lines that aren't valid entries (e.g. a
+/// partial line from a crashed run).
+pub fn read_entries(state_dir: &str) -> io::Result<Vec<JournalEntry>> {
+    let content = match fs::read_to_string(journal_path(state_dir)) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    Ok(content
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+/// Prints entries appended after the current end of the journal until the
+/// process is interrupted.
+pub fn follow(state_dir: &str, raw: bool) -> io::Result<()> {
+    let path = journal_path(state_dir);
+    let mut position = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+    loop {
+        std::thread::sleep(Duration::from_secs(1));
+        let Ok(mut file) = fs::File::open(&path) else {
+            continue;
+        };
+        if file.metadata()?.len() < position {
+            // The journal was truncated or replaced, start over
+            position = 0;
+        }
+        file.seek(SeekFrom::Start(position))?;
+        let mut reader = BufReader::new(file);
+        let mut line = String::new();
+        // Only consume complete lines, a half-written one is read next time
+        while reader.read_line(&mut line)? > 0 && line.ends_with('\n') {
+            position += line.len() as u64;
+            print_line(line.trim_end(), raw);
+            line.clear();
+        }
+    }
+}
+
+pub fn print_line(line: &str, raw: bool) {
+    if raw {
+        println!("{}", line);
+    } else if let Ok(entry) = serde_json::from_str::<JournalEntry>(line) {
+        println!("{}", entry);
+    }
+}
@@ -1,13 +1,94 @@
-use chrono::{DateTime, Duration, Utc};
-use clap::{command, Parser};
+use chrono::{DateTime, Datelike, Days, Duration, NaiveTime, Utc, Weekday};
+use clap::{Parser, Subcommand, ValueEnum};
+use serde_json::json;
 use std::fs::{self};
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+
+pub mod auth;
+pub mod calendar;
+pub mod captions;
+pub mod chunks;
+pub mod clock;
+pub mod config;
+pub mod control;
+pub mod data_budget;
+pub mod description_pool;
+pub mod download;
+pub mod email;
+pub mod endpoints;
+pub mod fault;
+pub mod feed;
+pub mod ffmpeg;
+pub mod ffprobe;
+pub mod history;
+pub mod http_trace;
+pub mod journal;
+pub mod links;
+pub mod lint;
+pub mod output;
+pub mod pending;
+pub mod plan;
+pub mod preflight;
+pub mod redact;
+pub mod report;
+pub mod runner;
+pub mod sanitize;
+pub mod screening;
+pub mod series;
+pub mod shared_schedule;
+pub mod simulator;
+pub mod site;
+pub mod skip_dates;
+pub mod slot_file;
+pub mod spool;
+pub mod telegram;
+pub mod template;
+pub mod thumbnail;
+pub mod timezone;
+pub mod upload_window;
+pub mod watch;
+pub mod watchdog;
+pub mod youtube;
+
+use auth::TokenBackend;
+use captions::CaptionSource;
+use chunks::{ChunkOptions, IoBackend};
+use config::ClientConfig;
+use data_budget::BudgetPeriod;
+use description_pool::PoolOrder;
+use ffmpeg::{PreprocessOptions, TranscodeMode, TranscodeQuality};
+use ffprobe::{ArchiveMode, ChapterSource, MetadataSource};
+use output::{ColorChoice, OutputFormat, Verbosity};
+use pending::Action;
+use plan::{parse_positions, Selection};
+use preflight::PreflightMode;
+use sanitize::CaseStyle;
+use site::SiteFlavor;
+use skip_dates::SkipDates;
+use upload_window::UploadWindow;
+use youtube::License;
+
+const EXIT_CODES_HELP: &str = "Exit codes:
+  0  success
+  1  unexpected error
+  2  some uploads failed
+  3  authentication error
+  4  API quota or daily upload limit exhausted
+  5  invalid arguments, metadata or videos
+  6  cancelled at the confirmation prompt";
 
 #[derive(Parser, Debug)]
 #[command(author= "LinlyBoi",
           version = "0.9",
           about = "Upload and schedule YouTube videos",
-          long_about = None)]
+          long_about = None,
+          after_help = EXIT_CODES_HELP,
+          subcommand_negates_reqs = true)]
 pub struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     #[arg(
         short = 'v',
         long,
@@ -15,16 +96,16 @@ pub struct Args {
         help = "Comma-separated list of video file paths",
         required = true
     )]
-    videos: String,
+    videos: Option<String>,
 
     #[arg(
         short = 'i',
         long,
         value_name = "DURATION",
-        help = "Time interval between uploads (e.g., 2h, 30m, 1d)",
-        required = true
+        help = "Time interval between uploads (e.g., 2h, 30m, 1d), or a repeating list like 1d,3d,3d. Defaults to the profile's interval",
+        required_unless_present_any = ["schedule_from", "schedule_template"]
     )]
-    interval: String,
+    interval: Option<String>,
 
     #[arg(
         short = 'c',
@@ -36,6 +117,34 @@ pub struct Args {
     )]
     oauth_config: String,
 
+    #[arg(
+        long = "token-store",
+        value_name = "BACKEND",
+        help = "Where OAuth tokens are kept between runs",
+        value_enum,
+        default_value_t = TokenBackend::File
+    )]
+    token_store: TokenBackend,
+
+    #[arg(
+        long = "refresh-token",
+        value_name = "TOKEN",
+        help = "Mint access tokens from this refresh token without reading or writing the token store (default: $YT_UPLOAD_REFRESH_TOKEN). Client id and secret can come from $YT_UPLOAD_CLIENT_ID and $YT_UPLOAD_CLIENT_SECRET instead of --oauth-config"
+    )]
+    refresh_token: Option<String>,
+
+    #[arg(
+        long = "client",
+        value_name = "NAME",
+        conflicts_with_all = ["oauth_config", "token_store", "refresh_token", "state_dir", "profile"],
+        help = "Agency client from the config file, whose OAuth config, token file, profile and state dir are used"
+    )]
+    client: Option<String>,
+
+    /// Token file of the agency client, set by `use_client`
+    #[arg(skip)]
+    token_file: Option<String>,
+
     #[arg(
         short = 'm',
         long = "metadata",
@@ -67,12 +176,843 @@ pub struct Args {
     dry_run: bool,
 
     #[arg(
-        long = "description-file",
-        value_name = "DESCRIPTION_FILE",
-        help = "File random description to assign videos (For fun), not used if video has metadata",
-        default_value = "~/org/quotes.org"
+        long = "calendar",
+        help = "Also show the schedule as month grids with the number of publishes per day",
+        action = clap::ArgAction::SetTrue
+    )]
+    calendar: bool,
+
+    #[arg(
+        long = "require-approved-plan",
+        value_name = "PLAN_FILE",
+        help = "With --dry-run, write the resolved plan and its hash to PLAN_FILE for review. Otherwise upload into the slots of PLAN_FILE and refuse unless the plan still matches its hash"
+    )]
+    require_approved_plan: Option<String>,
+
+    #[arg(
+        long = "manifest-key",
+        value_name = "KEY_FILE",
+        help = "File with a key shared by planner and uploader. schedule manifest signs with it, upload --manifest checks the signature with it and refuses modified manifests"
+    )]
+    manifest_key: Option<String>,
+
+    #[arg(
+        long = "allow-unsigned-manifest",
+        help = "Let upload --manifest run manifests written without --manifest-key"
+    )]
+    allow_unsigned_manifest: bool,
+
+    #[arg(
+        long = "simulate",
+        help = "Run against a built-in fake YouTube API configured by the simulator section of the config file. State is kept apart in <state-dir>/simulate"
+    )]
+    simulate: bool,
+
+    #[arg(
+        long = "inject-failure",
+        value_name = "SPEC",
+        help = "Fail a share of API requests to test retries and notifications, e.g. rate=0.2,kind=network (kind: network, quota or an HTTP status). Repeatable"
+    )]
+    inject_failure: Vec<String>,
+
+    #[arg(
+        long = "debug-http",
+        value_name = "FILE",
+        help = "Record every Google API request and response to FILE for bug reports, as HAR if it ends in .har and NDJSON otherwise. Tokens and bodies are left out"
+    )]
+    debug_http: Option<String>,
+
+    #[arg(
+        long = "description-pool",
+        alias = "description-file",
+        value_name = "FILE",
+        help = "File of one-line descriptions to assign videos, not used if video has metadata"
+    )]
+    description_pool: Option<String>,
+
+    #[arg(
+        long = "description-order",
+        value_name = "ORDER",
+        help = "How lines are picked from --description-pool",
+        value_enum,
+        default_value_t = PoolOrder::Random
+    )]
+    description_order: PoolOrder,
+
+    #[arg(
+        long = "state-dir",
+        value_name = "DIR",
+        help = "Directory for state kept between runs",
+        default_value = "~/.youtube_scheduler"
+    )]
+    state_dir: String,
+
+    #[arg(
+        long = "config",
+        value_name = "CONFIG_FILE",
+        help = "Configuration file (JSON) with metadata profiles",
+        default_value = "~/.config/youtube-scheduler/config.json"
+    )]
+    config: String,
+
+    #[arg(
+        short = 'p',
+        long = "profile",
+        value_name = "NAME",
+        help = "Metadata profile from the config file used for videos without metadata",
+        default_value = config::DEFAULT_PROFILE
+    )]
+    profile: String,
+
+    #[arg(
+        long = "prepend",
+        value_name = "CLIP",
+        help = "Intro clip joined before every video (requires ffmpeg)"
+    )]
+    prepend: Option<String>,
+
+    #[arg(
+        long = "append",
+        value_name = "CLIP",
+        help = "Outro clip joined after every video (requires ffmpeg)"
+    )]
+    append: Option<String>,
+
+    #[arg(
+        long = "cover",
+        value_name = "IMAGE",
+        help = "Still image used when rendering audio files into videos (default: waveform)"
+    )]
+    cover: Option<String>,
+
+    #[arg(
+        long = "transcode",
+        value_name = "MODE",
+        help = "Re-encode sources before upload (requires ffmpeg)",
+        value_enum
+    )]
+    transcode: Option<TranscodeMode>,
+
+    #[arg(
+        long = "transcode-quality",
+        value_name = "QUALITY",
+        help = "Encoder quality preset used by --transcode",
+        value_enum,
+        default_value_t = TranscodeQuality::Balanced
+    )]
+    transcode_quality: TranscodeQuality,
+
+    #[arg(
+        long = "preprocess-jobs",
+        value_name = "N",
+        help = "Number of ffmpeg jobs running alongside uploads",
+        default_value_t = 2,
+        value_parser = clap::value_parser!(u16).range(1..)
+    )]
+    preprocess_jobs: u16,
+
+    #[arg(
+        long = "download-jobs",
+        value_name = "N",
+        help = "Number of URL sources downloading alongside preprocessing and uploads",
+        default_value_t = 2,
+        value_parser = clap::value_parser!(u16).range(1..)
+    )]
+    download_jobs: u16,
+
+    #[arg(
+        long = "keep-artifacts",
+        help = "Keep preprocessed videos, thumbnails and transcripts of uploaded videos in the cache dir \
+                instead of removing them, `clean` purges them later"
+    )]
+    keep_artifacts: bool,
+
+    #[arg(
+        long = "hash-jobs",
+        value_name = "N",
+        help = "Number of threads fingerprinting videos for the upload history",
+        default_value_t = 4,
+        value_parser = clap::value_parser!(u16).range(1..)
+    )]
+    hash_jobs: u16,
+
+    #[arg(
+        long = "auto-thumbnail",
+        help = "Set a frame of each video as its thumbnail, taken with ffmpeg at --thumbnail-at \
+                or the video's thumbnail_at in the metadata file"
+    )]
+    auto_thumbnail: bool,
+
+    #[arg(
+        long = "thumbnail-at",
+        value_name = "OFFSET",
+        help = "Where auto-thumbnails are taken: a share of the video like 10% or a timestamp \
+                like 00:12:34",
+        default_value = "10%"
+    )]
+    thumbnail_at: String,
+
+    #[arg(
+        long = "metadata-source",
+        value_name = "SOURCE",
+        help = "Where defaults for videos without a metadata file come from",
+        value_enum,
+        default_value_t = MetadataSource::Generated
+    )]
+    metadata_source: MetadataSource,
+
+    #[arg(
+        long = "archive-mode",
+        value_name = "PRESET",
+        help = "Re-uploading an old archive: set recording dates from the files' original \
+                timestamps (date), and also note them in descriptions (note)",
+        value_enum
+    )]
+    archive_mode: Option<ArchiveMode>,
+
+    #[arg(
+        long = "captions",
+        value_name = "SOURCE",
+        help = "Upload captions from <video>.srt sidecars, with auto transcribing videos \
+                without one using the captions section of the config file",
+        value_enum,
+        default_value_t = CaptionSource::Off
+    )]
+    captions: CaptionSource,
+
+    #[arg(
+        long = "chapters",
+        value_name = "SOURCE",
+        help = "Append a chapter list to descriptions (from-container requires ffprobe)",
+        value_enum,
+        default_value_t = ChapterSource::Off
+    )]
+    chapters: ChapterSource,
+
+    #[arg(
+        long = "preflight",
+        value_name = "MODE",
+        help = "Check resolution and aspect ratio of videos before uploading (requires ffprobe)",
+        value_enum,
+        default_value_t = PreflightMode::Warn
+    )]
+    preflight: PreflightMode,
+
+    #[arg(
+        long = "max-size",
+        value_name = "SIZE",
+        help = "Refuse to start when a video is larger than this (e.g. 128GB)",
+        default_value = "256GB"
+    )]
+    max_size: String,
+
+    #[arg(
+        long = "max-duration",
+        value_name = "DURATION",
+        help = "Refuse to start when a video is longer than this (requires ffprobe)",
+        default_value = "12h"
+    )]
+    max_duration: String,
+
+    #[arg(
+        short = 'y',
+        long = "yes",
+        help = "Upload without asking for confirmation",
+        action = clap::ArgAction::SetTrue
+    )]
+    yes: bool,
+
+    #[arg(
+        long = "allow-immediate-public",
+        help = "Upload videos that are public without a publish time, which otherwise needs typing 'public' at a prompt",
+        action = clap::ArgAction::SetTrue
+    )]
+    allow_immediate_public: bool,
+
+    #[arg(
+        long = "non-interactive",
+        value_name = "POLICY",
+        help = "What to do at the confirmation prompt when stdin is not a terminal",
+        value_enum,
+        default_value_t = NonInteractivePolicy::Fail
+    )]
+    non_interactive: NonInteractivePolicy,
+
+    #[arg(
+        short = 'q',
+        long = "quiet",
+        help = "Only print errors",
+        action = clap::ArgAction::SetTrue,
+        conflicts_with = "verbose"
+    )]
+    quiet: bool,
+
+    #[arg(
+        short = 'D',
+        long = "verbose",
+        help = "Print more detail, repeat (-DD) for debug output. -v is --videos",
+        action = clap::ArgAction::Count
+    )]
+    verbose: u8,
+
+    #[arg(
+        long = "color",
+        value_name = "WHEN",
+        help = "Colorize output",
+        value_enum,
+        default_value_t = ColorChoice::Auto
+    )]
+    color: ColorChoice,
+
+    #[arg(
+        long = "output",
+        value_name = "FORMAT",
+        help = "Output format, json writes one event per line to stdout",
+        value_enum,
+        default_value_t = OutputFormat::Text
+    )]
+    output: OutputFormat,
+
+    #[arg(
+        long = "progress-interval",
+        value_name = "DURATION",
+        help = "Minimum time between upload_progress events with --output json",
+        default_value = "1s"
+    )]
+    progress_interval: String,
+
+    #[arg(
+        long = "heartbeat",
+        value_name = "DURATION",
+        help = "Time between \"Still uploading\" lines with percent done, rate and ETA during long uploads, 0 turns them off",
+        default_value = "5m"
+    )]
+    heartbeat: String,
+
+    #[arg(
+        long = "token-refresh-margin",
+        value_name = "DURATION",
+        help = "Refresh the access token when it expires within DURATION, raise it for uploads longer than the token lives",
+        default_value = "5m"
+    )]
+    token_refresh_margin: String,
+
+    #[arg(
+        long = "clock-skew-tolerance",
+        value_name = "DURATION",
+        help = "Warn when the local clock differs from Google's servers by more than DURATION",
+        default_value = "2m"
+    )]
+    clock_skew_tolerance: String,
+
+    #[arg(
+        long = "chunk-size",
+        value_name = "SIZE",
+        help = "Bytes sent per upload request, a multiple of 256KiB up to 1GiB (e.g. 64MiB). \
+                Uploads hold at most chunk size x (read-ahead + 3) in memory",
+        default_value = "8MiB"
+    )]
+    chunk_size: String,
+
+    #[arg(
+        long = "read-ahead",
+        value_name = "CHUNKS",
+        help = "Chunks read from disk ahead of the one being sent; raise for slow disks",
+        default_value_t = 1
+    )]
+    read_ahead: usize,
+
+    #[arg(
+        long = "io-backend",
+        value_name = "BACKEND",
+        help = "How upload chunks are read from disk, compare with `bench --disk`",
+        value_enum,
+        default_value_t = IoBackend::Buffered
+    )]
+    io_backend: IoBackend,
+
+    #[arg(
+        long = "calendar-id",
+        value_name = "CALENDAR",
+        help = "Google Calendar to add an event to for each scheduled video (e.g. primary)"
+    )]
+    calendar_id: Option<String>,
+
+    #[arg(
+        long = "skip-dates",
+        value_name = "FILE",
+        help = "File of dates (YYYY-MM-DD) or ranges (YYYY-MM-DD..YYYY-MM-DD) to never publish on, one per line (days in --timezone, UTC by default)"
+    )]
+    skip_dates: Option<String>,
+
+    #[arg(
+        long = "schedule-from",
+        value_name = "FILE",
+        conflicts_with_all = ["interval", "start_time", "timestamp_file"],
+        help = "Publish at the event starts of an .ics file or the date-times in the first column of a CSV file, instead of at intervals. Past and already taken slots are left out"
+    )]
+    schedule_from: Option<String>,
+
+    #[arg(
+        long = "schedule-template",
+        value_name = "NAME",
+        conflicts_with_all = ["interval", "schedule_from"],
+        help = "Publish on the weekdays and times of a template from the schedule_templates section of the config file, instead of at intervals"
+    )]
+    schedule_template: Option<String>,
+
+    #[arg(
+        long = "timezone",
+        value_name = "ZONE",
+        help = "IANA timezone (e.g. Europe/Berlin) in which whole-day intervals keep the start's wall-clock time across DST changes"
+    )]
+    timezone: Option<String>,
+
+    #[arg(
+        long = "upload-window",
+        value_name = "HH:MM-HH:MM",
+        help = "Only start uploads during these hours (in --timezone, UTC by default), e.g. \
+                01:00-07:00; uploads wait for the window, publish times are unaffected"
+    )]
+    upload_window: Option<String>,
+
+    #[arg(
+        long = "min-spacing",
+        value_name = "DURATION",
+        help = "Keep publish times at least this far from those of other profiles sharing the \
+                state dir (e.g. 2h), for channels whose premieres shouldn't compete. Every \
+                profile involved needs it"
+    )]
+    min_spacing: Option<String>,
+
+    #[arg(
+        long = "min-lead-time",
+        value_name = "DURATION",
+        help = "Minimum time between now and the first publish slot",
+        default_value = "15m"
+    )]
+    min_lead_time: String,
+
+    #[arg(
+        long = "max-horizon",
+        value_name = "DURATION",
+        help = "Refuse schedules with publish slots further than this from now, e.g. 6mo"
+    )]
+    max_horizon: Option<String>,
+
+    #[arg(
+        long = "lead-time-policy",
+        value_name = "POLICY",
+        help = "What to do when slots are sooner than --min-lead-time",
+        value_enum,
+        default_value_t = LeadTimePolicy::Fail
+    )]
+    lead_time_policy: LeadTimePolicy,
+
+    #[arg(
+        long = "publish-as",
+        value_name = "PRIVACY",
+        help = "Privacy videos get at their publish slot. Unlisted videos stay private \
+                until `run-pending` or watch mode changes them",
+        value_enum,
+        default_value_t = PublishPrivacy::Public
+    )]
+    publish_as: PublishPrivacy,
+
+    #[arg(
+        long = "comment-at-publish",
+        value_name = "TEXT",
+        help = "Comment to post on each video at its publish slot through `run-pending` or \
+                watch mode. The API cannot pin it"
+    )]
+    comment_at_publish: Option<String>,
+
+    #[arg(
+        long = "playlist-at-publish",
+        value_name = "PLAYLIST_ID",
+        help = "Playlist to add each video to at its publish slot through `run-pending` or \
+                watch mode"
+    )]
+    playlist_at_publish: Option<String>,
+
+    #[arg(
+        long = "force-all",
+        help = "Upload every video, even those a previous run already uploaded"
+    )]
+    force_all: bool,
+
+    #[arg(
+        long = "only",
+        value_name = "POSITIONS",
+        help = "Upload only these positions of the printed schedule (e.g. 3,5,7 or 2-4)"
+    )]
+    only: Option<String>,
+
+    #[arg(
+        long = "skip",
+        value_name = "POSITIONS",
+        help = "Leave out these positions of the printed schedule (e.g. 1-2)"
+    )]
+    skip: Option<String>,
+
+    #[arg(
+        long = "limit",
+        value_name = "N",
+        help = "Upload at most N videos, after --only and --skip"
+    )]
+    limit: Option<usize>,
+
+    #[arg(
+        long = "start-after",
+        value_name = "PATH_OR_HASH",
+        help = "Leave out --videos up to and including this file, given by path or by \
+                the fingerprint recorded in the upload history"
+    )]
+    start_after: Option<String>,
+
+    #[arg(
+        long = "stop-after",
+        value_name = "N",
+        help = "End the batch after N successful uploads, leaving the rest for the next run"
+    )]
+    stop_after: Option<usize>,
+
+    #[arg(
+        long = "data-budget",
+        value_name = "SIZE",
+        help = "Stop before a video that would take the bytes sent past SIZE (e.g. 50GB), leaving the rest for the next run"
+    )]
+    data_budget: Option<String>,
+
+    #[arg(
+        long = "data-budget-period",
+        value_name = "PERIOD",
+        help = "Whether --data-budget counts this run or every run of the day",
+        value_enum,
+        default_value_t = BudgetPeriod::Run
+    )]
+    data_budget_period: BudgetPeriod,
+
+    #[arg(
+        long = "wait-processing",
+        value_name = "DURATION",
+        help = "After the batch, follow YouTube's processing of the uploaded videos for up to DURATION (e.g. 6h) and alert through Telegram and --email-to when one is rejected or still processing"
+    )]
+    wait_processing: Option<String>,
+
+    #[arg(
+        long = "reupload-rejected",
+        value_name = "N",
+        requires = "wait_processing",
+        default_value_t = 0,
+        help = "With --wait-processing, delete a video whose processing failed for a transient reason, e.g. an aborted upload, and upload it again into the same slot, up to N times per video"
+    )]
+    reupload_rejected: u32,
+
+    #[arg(
+        long = "fill-gaps",
+        help = "Skip slots already taken by videos scheduled on the channel (needs sign-in, also for --dry-run)"
+    )]
+    fill_gaps: bool,
+
+    #[arg(
+        long = "min-file-age",
+        value_name = "DURATION",
+        help = "Refuse videos modified less than this long ago or still open for writing (watch mode defaults to 1m)"
+    )]
+    min_file_age: Option<String>,
+
+    #[arg(
+        long = "check-channel-titles",
+        help = "Warn when a title matches one of the channel's recent uploads"
+    )]
+    check_channel_titles: bool,
+
+    #[arg(
+        long = "feed",
+        value_name = "FILE",
+        help = "RSS file listing upcoming releases, updated after each run"
+    )]
+    feed: Option<String>,
+
+    #[arg(
+        long = "site-dir",
+        value_name = "DIR",
+        help = "Write a Markdown post with front matter for every uploaded video into DIR"
+    )]
+    site_dir: Option<String>,
+
+    #[arg(
+        long = "export-notes",
+        value_name = "DIR",
+        help = "Write a Markdown note of the batch with a task per scheduled video into DIR"
+    )]
+    export_notes: Option<String>,
+
+    #[arg(
+        long = "site-flavor",
+        value_name = "GENERATOR",
+        help = "Static site generator the --site-dir posts are named for",
+        value_enum,
+        default_value_t = SiteFlavor::Hugo
+    )]
+    site_flavor: SiteFlavor,
+
+    #[arg(
+        long = "email-to",
+        value_name = "ADDRESS",
+        help = "Email a summary after the batch (SMTP settings from the config file)",
+        value_delimiter = ','
+    )]
+    email_to: Vec<String>,
+
+    #[arg(
+        long = "telegram",
+        help = "Send upload notifications to Telegram and accept /status, /pause, /resume and /retry (telegram section in the config file)"
+    )]
+    telegram: bool,
+
+    #[arg(
+        long = "failed-dir",
+        value_name = "DIR",
+        help = "Move videos that fail permanently into this directory with a .error.json report"
+    )]
+    failed_dir: Option<String>,
+
+    #[arg(
+        long = "quarantine",
+        value_name = "MODE",
+        help = "How failed videos are placed into --failed-dir",
+        value_enum,
+        default_value_t = QuarantineMode::Move
+    )]
+    quarantine: QuarantineMode,
+
+    #[arg(
+        long = "made-for-kids",
+        value_name = "BOOL",
+        help = "Declare every video as made for kids or not, instead of the profile's setting"
+    )]
+    made_for_kids: Option<bool>,
+
+    #[arg(
+        long = "license",
+        value_name = "LICENSE",
+        help = "License of every video, instead of the profile's setting",
+        value_enum
+    )]
+    license: Option<License>,
+
+    #[arg(
+        long = "category",
+        value_name = "ID",
+        help = "Category ID of every video, instead of the profile's or metadata file's"
+    )]
+    category: Option<String>,
+
+    #[arg(
+        long = "title-case",
+        value_name = "STYLE",
+        help = "Case style applied to titles generated from file names",
+        value_enum,
+        default_value_t = CaseStyle::None
     )]
-    description_file: String
+    title_case: CaseStyle,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Plan upload schedules without uploading
+    #[command(subcommand)]
+    Schedule(ScheduleCommand),
+    /// Upload a previously saved plan or manifest unchanged. Options such
+    /// as --oauth-config go before the subcommand.
+    Upload {
+        #[arg(
+            long = "plan",
+            value_name = "NAME",
+            required_unless_present = "manifest",
+            help = "Name of the saved plan"
+        )]
+        plan: Option<String>,
+        #[arg(
+            long = "manifest",
+            value_name = "FILE",
+            conflicts_with = "plan",
+            help = "Manifest written by schedule manifest, refused if a video changed since"
+        )]
+        manifest: Option<String>,
+    },
+    /// Inspect the journal of past runs kept in the state dir
+    #[command(subcommand)]
+    Journal(JournalCommand),
+    /// Inspect the stored authorization
+    #[command(subcommand)]
+    Auth(AuthCommand),
+    /// Check metadata and schedule without signing in or uploading. Exits
+    /// with the validation error code when any problem is found.
+    Lint {
+        #[arg(
+            short = 'm',
+            long = "metadata",
+            value_name = "METADATA_FILE",
+            help = "JSON file containing video metadata, generated from the profile if omitted"
+        )]
+        metadata: Option<String>,
+        #[arg(
+            short = 'v',
+            long = "videos",
+            value_name = "VIDEOS",
+            help = "Comma-separated list of video files"
+        )]
+        videos: String,
+        #[arg(
+            short = 'i',
+            long = "interval",
+            value_name = "DURATION",
+            help = "Also check the schedule for this interval"
+        )]
+        interval: Option<String>,
+        #[arg(
+            short = 's',
+            long = "start-time",
+            value_name = "START_TIME",
+            help = "Start time for first upload (ISO 8601 format)"
+        )]
+        start_time: Option<String>,
+    },
+    /// Measure real upload throughput to YouTube. Data is sent into an
+    /// upload session that is cancelled, so no video is created, though
+    /// the API may still count it against the quota like an upload.
+    Bench {
+        #[arg(
+            long = "size",
+            value_name = "SIZE",
+            help = "Amount of data to send, rounded up to whole chunks of --chunk-size",
+            default_value = "64MiB"
+        )]
+        size: String,
+
+        #[arg(
+            long = "disk",
+            value_name = "FILE",
+            help = "Measure reading FILE with each --io-backend instead of uploading"
+        )]
+        disk: Option<String>,
+    },
+    /// Run the actions queued for publish time (--publish-as unlisted,
+    /// --comment-at-publish, --playlist-at-publish) whose time has come.
+    /// Run it from cron, watch mode does this on every poll.
+    #[command(alias = "apply-pending")]
+    RunPending,
+    /// Keep uploading new videos as they appear in a folder. Titles and
+    /// descriptions come from the profile, subfolders listed under
+    /// `watch_folders` in the config use their own profile and cadence.
+    Watch {
+        #[arg(long = "dir", value_name = "DIR", help = "Folder to watch")]
+        dir: String,
+        #[arg(
+            long = "poll-interval",
+            value_name = "DURATION",
+            help = "How often to look for new files",
+            default_value = "30s"
+        )]
+        poll_interval: String,
+        #[arg(
+            long = "claim",
+            help = "Move files into claimed/<worker>/ before uploading, so several workers can share the folder"
+        )]
+        claim: bool,
+        #[arg(
+            long = "worker-id",
+            value_name = "NAME",
+            help = "Name of this worker's claim folder [default: hostname]"
+        )]
+        worker_id: Option<String>,
+    },
+    /// Delete preprocessed videos, thumbnails and transcripts from the
+    /// cache dir in the state dir. Those of uploaded videos go right away,
+    /// those of failed uploads stay for the retry until cleaned.
+    Clean {
+        #[arg(
+            long = "older-than",
+            value_name = "DURATION",
+            help = "Only delete files not used for this long",
+            default_value = "7d"
+        )]
+        older_than: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum JournalCommand {
+    /// Print the whole journal
+    Show {
+        #[arg(long = "raw", help = "Print the NDJSON lines as stored")]
+        raw: bool,
+    },
+    /// Print the most recent entries
+    Tail {
+        #[arg(
+            short = 'n',
+            long = "lines",
+            value_name = "N",
+            default_value_t = 20,
+            help = "Number of entries to print"
+        )]
+        lines: usize,
+        #[arg(short = 'f', long = "follow", help = "Keep printing new entries")]
+        follow: bool,
+        #[arg(long = "raw", help = "Print the NDJSON lines as stored")]
+        raw: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum AuthCommand {
+    /// Show the token's expiry, granted scopes and the channel it uploads to
+    Status,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ScheduleCommand {
+    /// Save videos, metadata and publish slots as a named plan for review.
+    /// Takes the same options as a regular run, given before the subcommand.
+    Save { name: String },
+    /// Write videos, metadata, publish slots and file hashes to a manifest
+    /// without signing in, for `upload --manifest` on another machine.
+    /// Takes the same options as a regular run, except --fill-gaps.
+    Manifest { path: String },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NonInteractivePolicy {
+    /// Continue as if the prompt was confirmed
+    Proceed,
+    /// Exit immediately instead of waiting for input
+    Fail,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LeadTimePolicy {
+    /// Refuse the batch when a slot is too soon
+    Fail,
+    /// Move the schedule to the next slots far enough ahead
+    Shift,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PublishPrivacy {
+    /// Scheduled with publishAt, YouTube makes the video public
+    Public,
+    /// Uploaded private and made unlisted at the slot through videos.update
+    Unlisted,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QuarantineMode {
+    Move,
+    Symlink,
 }
 
 impl Args {
@@ -80,18 +1020,59 @@ impl Args {
         self.timestamp_file.as_ref()
     }
 
+    pub fn command(&self) -> Option<&Command> {
+        self.command.as_ref()
+    }
+
+    /// Empty when a subcommand made `--videos` optional and it wasn't given
     pub fn videos(&self) -> &str {
-        &self.videos
+        self.videos.as_deref().unwrap_or_default()
     }
 
     pub fn interval(&self) -> &str {
-        &self.interval
+        self.interval.as_deref().unwrap_or_default()
     }
 
     pub fn dry_run(&self) -> bool {
         self.dry_run
     }
 
+    pub fn calendar(&self) -> bool {
+        self.calendar
+    }
+
+    pub fn require_approved_plan(&self) -> Option<&String> {
+        self.require_approved_plan.as_ref()
+    }
+
+    pub fn manifest_key(&self) -> Option<&String> {
+        self.manifest_key.as_ref()
+    }
+
+    pub fn allow_unsigned_manifest(&self) -> bool {
+        self.allow_unsigned_manifest
+    }
+
+    pub fn simulate(&self) -> bool {
+        self.simulate
+    }
+
+    pub fn inject_failure(&self) -> &[String] {
+        &self.inject_failure
+    }
+
+    pub fn debug_http(&self) -> Option<&String> {
+        self.debug_http.as_ref()
+    }
+
+    /// Moves all state of a simulated run into a subdirectory, so fake
+    /// video IDs never end up in the real upload history.
+    pub fn isolate_simulation_state(&mut self) {
+        if self.simulate {
+            self.state_dir = format!("{}/simulate", self.state_dir.trim_end_matches('/'));
+        }
+    }
+
     pub fn start_time(&self) -> Option<&String> {
         self.start_time.as_ref()
     }
@@ -99,21 +1080,443 @@ impl Args {
     pub fn oauth_config(&self) -> &str {
         &self.oauth_config
     }
+
+    pub fn token_store(&self) -> TokenBackend {
+        self.token_store
+    }
+
+    pub fn client(&self) -> Option<&String> {
+        self.client.as_ref()
+    }
+
+    pub fn token_file(&self) -> Option<&String> {
+        self.token_file.as_ref()
+    }
+
+    /// Switches to the OAuth config, token file, profile and state dir of
+    /// an agency client.
+    pub fn use_client(&mut self, client: &ClientConfig) {
+        self.oauth_config = client.oauth_config.clone();
+        self.token_file = Some(client.token_file.clone());
+        self.profile = client.profile.clone();
+        self.state_dir = client.state_dir.clone();
+    }
+
+    /// `--refresh-token`, falling back to `YT_UPLOAD_REFRESH_TOKEN`.
+    pub fn refresh_token(&self) -> Option<String> {
+        self.refresh_token
+            .clone()
+            .or_else(|| std::env::var(auth::REFRESH_TOKEN_VAR).ok())
+            .filter(|token| !token.trim().is_empty())
+    }
+
     pub fn metadata(&self) -> Option<&String> {
         self.metadata.as_ref()
     }
 
-    pub fn description_file(&self) -> &str {
-        &self.description_file
+    pub fn description_pool(&self) -> Option<&String> {
+        self.description_pool.as_ref()
+    }
+
+    pub fn description_order(&self) -> PoolOrder {
+        self.description_order
+    }
+
+    pub fn state_dir(&self) -> &str {
+        &self.state_dir
+    }
+
+    pub fn config(&self) -> &str {
+        &self.config
+    }
+
+    pub fn profile(&self) -> &str {
+        &self.profile
+    }
+
+    pub fn preprocess_options(&self) -> PreprocessOptions {
+        PreprocessOptions {
+            prepend: self.prepend.clone(),
+            append: self.append.clone(),
+            cover: self.cover.clone(),
+            transcode: self.transcode.map(|mode| (mode, self.transcode_quality)),
+        }
+    }
+
+    pub fn preprocess_jobs(&self) -> usize {
+        self.preprocess_jobs as usize
+    }
+
+    pub fn download_jobs(&self) -> usize {
+        self.download_jobs as usize
+    }
+
+    pub fn keep_artifacts(&self) -> bool {
+        self.keep_artifacts
+    }
+
+    pub fn hash_jobs(&self) -> usize {
+        self.hash_jobs as usize
+    }
+
+    pub fn metadata_source(&self) -> MetadataSource {
+        self.metadata_source
+    }
+
+    pub fn auto_thumbnail(&self) -> bool {
+        self.auto_thumbnail
+    }
+
+    pub fn thumbnail_at(&self) -> &str {
+        &self.thumbnail_at
+    }
+
+    pub fn archive_mode(&self) -> Option<ArchiveMode> {
+        self.archive_mode
+    }
+
+    pub fn captions(&self) -> CaptionSource {
+        self.captions
+    }
+
+    pub fn chapters(&self) -> ChapterSource {
+        self.chapters
+    }
+
+    pub fn preflight(&self) -> PreflightMode {
+        self.preflight
+    }
+
+    pub fn max_size(&self) -> &str {
+        &self.max_size
+    }
+
+    pub fn max_duration(&self) -> &str {
+        &self.max_duration
+    }
+
+    pub fn yes(&self) -> bool {
+        self.yes
+    }
+
+    pub fn allow_immediate_public(&self) -> bool {
+        self.allow_immediate_public
+    }
+
+    pub fn non_interactive(&self) -> NonInteractivePolicy {
+        self.non_interactive
+    }
+
+    pub fn verbosity(&self) -> Verbosity {
+        match (self.quiet, self.verbose) {
+            (true, _) => Verbosity::Quiet,
+            (false, 0) => Verbosity::Normal,
+            (false, 1) => Verbosity::Verbose,
+            (false, _) => Verbosity::Debug,
+        }
+    }
+
+    pub fn color(&self) -> ColorChoice {
+        self.color
+    }
+
+    pub fn output(&self) -> OutputFormat {
+        self.output
+    }
+
+    pub fn progress_interval(&self) -> &str {
+        &self.progress_interval
+    }
+
+    pub fn heartbeat(&self) -> &str {
+        &self.heartbeat
+    }
+
+    pub fn token_refresh_margin(&self) -> Result<Duration, Box<dyn std::error::Error>> {
+        let margin = parse_duration(&self.token_refresh_margin)
+            .map_err(|e| format!("Invalid --token-refresh-margin: {}", e))?;
+        if margin >= Duration::minutes(auth::ACCESS_TOKEN_LIFETIME_MINUTES) {
+            return Err(format!(
+                "--token-refresh-margin {} must be shorter than the {} minutes access tokens last",
+                self.token_refresh_margin,
+                auth::ACCESS_TOKEN_LIFETIME_MINUTES
+            )
+            .into());
+        }
+        Ok(margin)
+    }
+
+    pub fn clock_skew_tolerance(&self) -> Result<Duration, Box<dyn std::error::Error>> {
+        Ok(parse_duration(&self.clock_skew_tolerance)
+            .map_err(|e| format!("Invalid --clock-skew-tolerance: {}", e))?)
+    }
+
+    pub fn chunk_options(&self) -> Result<ChunkOptions, Box<dyn std::error::Error>> {
+        Ok(
+            ChunkOptions::new(parse_size(&self.chunk_size)?, self.read_ahead)?
+                .with_backend(self.io_backend),
+        )
+    }
+
+    pub fn skip_dates(&self) -> Option<&String> {
+        self.skip_dates.as_ref()
+    }
+
+    pub fn schedule_from(&self) -> Option<&str> {
+        self.schedule_from.as_deref()
+    }
+
+    pub fn schedule_template(&self) -> Option<&str> {
+        self.schedule_template.as_deref()
+    }
+
+    pub fn timezone(&self) -> Option<&String> {
+        self.timezone.as_ref()
+    }
+
+    pub fn upload_window(&self) -> Result<Option<UploadWindow>, Box<dyn std::error::Error>> {
+        self.upload_window
+            .as_deref()
+            .map(UploadWindow::parse)
+            .transpose()
+    }
+
+    pub fn min_spacing(&self) -> Option<&str> {
+        self.min_spacing.as_deref()
+    }
+
+    pub fn min_lead_time(&self) -> &str {
+        &self.min_lead_time
+    }
+
+    pub fn max_horizon(&self) -> Option<&str> {
+        self.max_horizon.as_deref()
+    }
+
+    pub fn lead_time_policy(&self) -> LeadTimePolicy {
+        self.lead_time_policy
+    }
+
+    pub fn playlist_at_publish(&self) -> Option<&str> {
+        self.playlist_at_publish.as_deref()
     }
+
+    pub fn publish_as(&self) -> PublishPrivacy {
+        self.publish_as
+    }
+
+    /// Actions to queue for each uploaded video, run at its publish slot.
+    pub fn publish_actions(&self) -> Vec<Action> {
+        let mut actions = Vec::new();
+        if self.publish_as == PublishPrivacy::Unlisted {
+            actions.push(Action::SetPrivacy {
+                privacy: "unlisted".to_string(),
+            });
+        }
+        if let Some(playlist_id) = &self.playlist_at_publish {
+            actions.push(Action::AddToPlaylist {
+                playlist_id: playlist_id.clone(),
+            });
+        }
+        if let Some(text) = &self.comment_at_publish {
+            actions.push(Action::PostComment { text: text.clone() });
+        }
+        actions
+    }
+
+    pub fn force_all(&self) -> bool {
+        self.force_all
+    }
+
+    pub fn start_after(&self) -> Option<&str> {
+        self.start_after.as_deref()
+    }
+
+    pub fn stop_after(&self) -> Option<usize> {
+        self.stop_after
+    }
+
+    pub fn data_budget(&self) -> Result<Option<u64>, Box<dyn std::error::Error>> {
+        self.data_budget
+            .as_deref()
+            .map(|size| {
+                parse_size(size).map_err(|e| format!("Invalid --data-budget: {}", e).into())
+            })
+            .transpose()
+    }
+
+    pub fn data_budget_period(&self) -> BudgetPeriod {
+        self.data_budget_period
+    }
+
+    pub fn reupload_rejected(&self) -> u32 {
+        self.reupload_rejected
+    }
+
+    pub fn wait_processing(&self) -> Result<Option<Duration>, Box<dyn std::error::Error>> {
+        self.wait_processing
+            .as_deref()
+            .map(|duration| {
+                parse_duration(duration)
+                    .map_err(|e| format!("Invalid --wait-processing: {}", e).into())
+            })
+            .transpose()
+    }
+
+    pub fn selection(&self) -> Result<Selection, Box<dyn std::error::Error>> {
+        Ok(Selection {
+            only: self.only.as_deref().map(parse_positions).transpose()?,
+            skip: match &self.skip {
+                Some(skip) => parse_positions(skip)?,
+                None => Vec::new(),
+            },
+            limit: self.limit,
+        })
+    }
+
+    pub fn fill_gaps(&self) -> bool {
+        self.fill_gaps
+    }
+
+    pub fn min_file_age(&self) -> Option<&String> {
+        self.min_file_age.as_ref()
+    }
+
+    pub fn check_channel_titles(&self) -> bool {
+        self.check_channel_titles
+    }
+
+    pub fn calendar_id(&self) -> Option<&String> {
+        self.calendar_id.as_ref()
+    }
+
+    pub fn feed(&self) -> Option<&String> {
+        self.feed.as_ref()
+    }
+
+    pub fn site_dir(&self) -> Option<&String> {
+        self.site_dir.as_ref()
+    }
+
+    pub fn export_notes(&self) -> Option<&String> {
+        self.export_notes.as_ref()
+    }
+
+    pub fn site_flavor(&self) -> SiteFlavor {
+        self.site_flavor
+    }
+
+    pub fn email_to(&self) -> &[String] {
+        &self.email_to
+    }
+
+    pub fn telegram(&self) -> bool {
+        self.telegram
+    }
+
+    pub fn failed_dir(&self) -> Option<&String> {
+        self.failed_dir.as_ref()
+    }
+
+    pub fn quarantine(&self) -> QuarantineMode {
+        self.quarantine
+    }
+
+    pub fn made_for_kids(&self) -> Option<bool> {
+        self.made_for_kids
+    }
+
+    pub fn license(&self) -> Option<License> {
+        self.license
+    }
+
+    pub fn category(&self) -> Option<&str> {
+        self.category.as_deref()
+    }
+
+    pub fn title_case(&self) -> CaseStyle {
+        self.title_case
+    }
+}
+
+/// Process exit codes. These are part of the CLI contract for scripts, so
+/// existing values must never change meaning.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitStatus {
+    Success = 0,
+    Error = 1,
+    PartialFailure = 2,
+    AuthError = 3,
+    QuotaExhausted = 4,
+    ValidationError = 5,
+    Cancelled = 6,
+}
+
+impl From<ExitStatus> for std::process::ExitCode {
+    fn from(status: ExitStatus) -> Self {
+        std::process::ExitCode::from(status as u8)
+    }
+}
+
+/// An error tagged with the exit status it should produce.
+#[derive(Debug)]
+pub struct Failure {
+    pub status: ExitStatus,
+    message: String,
+}
+
+impl Failure {
+    pub fn new(status: ExitStatus, message: impl std::fmt::Display) -> Self {
+        Self {
+            status,
+            message: message.to_string(),
+        }
+    }
+
+    pub fn auth(message: impl std::fmt::Display) -> Self {
+        Self::new(ExitStatus::AuthError, message)
+    }
+
+    pub fn validation(message: impl std::fmt::Display) -> Self {
+        Self::new(ExitStatus::ValidationError, message)
+    }
+}
+
+impl std::fmt::Display for Failure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for Failure {}
+
+/// Exit status for an error, `Error` unless it was tagged with a `Failure`.
+pub fn exit_status_of(error: &(dyn std::error::Error + 'static)) -> ExitStatus {
+    error
+        .downcast_ref::<Failure>()
+        .map(|failure| failure.status)
+        .unwrap_or(ExitStatus::Error)
+}
+
+/// Whether prompts can be answered. Under cron or CI stdin is not a
+/// terminal and reading from it would block forever.
+pub fn is_interactive() -> bool {
+    std::io::stdin().is_terminal()
 }
 
 pub fn parse_duration(duration_str: &str) -> Result<Duration, Box<dyn std::error::Error>> {
     let duration_str = duration_str.to_lowercase();
 
-    if duration_str.ends_with("h") {
+    if duration_str.ends_with("s") {
+        let seconds: i64 = duration_str.trim_end_matches("s").parse()?;
+        Ok(Duration::seconds(seconds))
+    } else if duration_str.ends_with("h") {
         let hours: i64 = duration_str.trim_end_matches("h").parse()?;
         Ok(Duration::hours(hours))
+    } else if duration_str.ends_with("mo") {
+        // Months are counted as 30 days
+        let months: i64 = duration_str.trim_end_matches("mo").parse()?;
+        Ok(Duration::days(30 * months))
     } else if duration_str.ends_with("m") {
         let minutes: i64 = duration_str.trim_end_matches("m").parse()?;
         Ok(Duration::minutes(minutes))
@@ -127,14 +1530,192 @@ pub fn parse_duration(duration_str: &str) -> Result<Duration, Box<dyn std::error
     }
 }
 
+/// Parses a single interval or a comma-separated list that repeats, e.g.
+/// `1d,3d,3d` for Monday, Tuesday and Friday.
+pub fn parse_intervals(intervals: &str) -> Result<Vec<Duration>, Box<dyn std::error::Error>> {
+    intervals
+        .split(',')
+        .map(|interval| {
+            parse_duration(interval.trim())
+                .map_err(|e| format!("Invalid interval '{}': {}", interval.trim(), e).into())
+        })
+        .collect()
+}
+
+/// Parses sizes like `128GB`, `500MiB` or `1048576` (bytes).
+pub fn parse_size(size_str: &str) -> Result<u64, Box<dyn std::error::Error>> {
+    let size_str = size_str.trim().to_uppercase();
+    let split = size_str
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(size_str.len());
+    let (number, unit) = size_str.split_at(split);
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("Invalid size: {}", size_str))?;
+
+    let multiplier: u64 = match unit.trim() {
+        "" | "B" => 1,
+        "KB" => 1_000,
+        "MB" => 1_000_000,
+        "GB" => 1_000_000_000,
+        "TB" => 1_000_000_000_000,
+        "KIB" => 1 << 10,
+        "MIB" => 1 << 20,
+        "GIB" => 1 << 30,
+        "TIB" => 1 << 40,
+        other => return Err(format!("Unknown size unit: {}", other).into()),
+    };
+    Ok((number * multiplier as f64) as u64)
+}
+
+/// Cadence slots searched for free ones before giving up
+const MAX_SCHEDULE_SLOTS: usize = 1_000_000;
+/// Days searched for free template slots before giving up
+const MAX_SCHEDULE_DAYS: u64 = 100 * 366;
+
 pub fn generate_schedule(
     video_count: usize,
     interval: Duration,
     start_time: Option<DateTime<Utc>>,
     timestamp_file: Option<&str>,
 ) -> Result<Vec<DateTime<Utc>>, Box<dyn std::error::Error>> {
-    let mut schedule = Vec::new();
+    let start = schedule_start(start_time, timestamp_file)?;
+    fill_schedule_gaps(
+        video_count,
+        &[interval],
+        start,
+        &SlotConstraints::default(),
+        false,
+    )
+}
+
+/// Publish slots `fill_schedule_gaps` must leave out.
+#[derive(Debug, Default, Clone)]
+pub struct SlotConstraints {
+    /// Publish times of videos already scheduled on the channel
+    pub occupied: Vec<DateTime<Utc>>,
+    pub skip_dates: SkipDates,
+    /// Earliest acceptable publish time
+    pub not_before: Option<DateTime<Utc>>,
+    /// Publish times of other profiles, which slots keep `min_spacing` from
+    pub other_profiles: Vec<DateTime<Utc>>,
+    pub min_spacing: Duration,
+}
+
+impl SlotConstraints {
+    /// Whether `slot` is usable on a cadence whose shortest step is `shortest`.
+    /// A slot counts as taken when an occupied publish time is within half
+    /// of that step.
+    fn allows(&self, slot: DateTime<Utc>, shortest: Duration) -> bool {
+        let half = shortest / 2;
+        self.not_before.is_none_or(|not_before| slot >= not_before)
+            && !self.skip_dates.contains(slot)
+            && !self
+                .occupied
+                .iter()
+                .any(|taken| *taken >= slot - half && *taken < slot + shortest - half)
+    }
+
+    /// `slot`, or the earliest time after it at least `min_spacing` away
+    /// from every publish time of other profiles.
+    fn spaced(&self, mut slot: DateTime<Utc>) -> DateTime<Utc> {
+        let mut others = self.other_profiles.clone();
+        others.sort();
+        for other in others {
+            if slot > other - self.min_spacing && slot < other + self.min_spacing {
+                slot = other + self.min_spacing;
+            }
+        }
+        slot
+    }
+}
+
+/// The first `video_count` of `slots` that are not before `not_before` and
+/// not already taken by a video in `occupied`.
+pub fn imported_schedule(
+    video_count: usize,
+    mut slots: Vec<DateTime<Utc>>,
+    not_before: DateTime<Utc>,
+    occupied: &[DateTime<Utc>],
+) -> Result<Vec<DateTime<Utc>>, Box<dyn std::error::Error>> {
+    slots.sort();
+    slots.dedup();
+    let total = slots.len();
+    let schedule: Vec<_> = slots
+        .into_iter()
+        .filter(|slot| *slot >= not_before && !occupied.contains(slot))
+        .take(video_count)
+        .collect();
+    if schedule.len() < video_count {
+        return Err(format!(
+            "Only {} of {} imported slots are free and upcoming, {} videos need one each",
+            schedule.len(),
+            total,
+            video_count
+        )
+        .into());
+    }
+    Ok(schedule)
+}
+
+/// Places videos on the `times` of each of `days` (every day when empty)
+/// in local time, from `start` on, leaving out slots the constraints rule
+/// out and moving those too close to other profiles' videos back.
+pub fn weekly_schedule(
+    video_count: usize,
+    days: &[Weekday],
+    times: &[NaiveTime],
+    start: DateTime<Utc>,
+    constraints: &SlotConstraints,
+) -> Result<Vec<DateTime<Utc>>, Box<dyn std::error::Error>> {
+    let first = timezone::local_date(start);
+    let candidates = (0..MAX_SCHEDULE_DAYS)
+        .filter_map(|i| first.checked_add_days(Days::new(i)))
+        .filter(|date| days.is_empty() || days.contains(&date.weekday()))
+        .flat_map(|date| {
+            times
+                .iter()
+                .filter_map(move |time| timezone::from_local(date.and_time(*time)))
+        })
+        .filter(|slot| *slot >= start);
+    // Occupied times count against the nearest slot of the week
+    let shortest = candidates
+        .clone()
+        .zip(candidates.clone().skip(1))
+        .take(7 * times.len())
+        .map(|(a, b)| b - a)
+        .min()
+        .unwrap_or(Duration::days(1));
+
+    let mut last: Option<DateTime<Utc>> = None;
+    let schedule: Vec<_> = candidates
+        .map(|slot| constraints.spaced(slot))
+        .filter(|slot| {
+            let free = last.is_none_or(|last| *slot > last) && constraints.allows(*slot, shortest);
+            if free {
+                last = Some(*slot);
+            }
+            free
+        })
+        .take(video_count)
+        .collect();
+    if schedule.len() < video_count {
+        return Err(format!(
+            "Only found {} free publish slots for {} videos",
+            schedule.len(),
+            video_count
+        )
+        .into());
+    }
+    Ok(schedule)
+}
 
+/// First publish slot: from the timestamp file, the start time, or an hour
+/// from now.
+pub fn schedule_start(
+    start_time: Option<DateTime<Utc>>,
+    timestamp_file: Option<&str>,
+) -> Result<DateTime<Utc>, Box<dyn std::error::Error>> {
     let start = if let Some(file_path) = timestamp_file {
         // Read timestamp from file
         let expanded_path = expand_tilde(file_path);
@@ -153,12 +1734,61 @@ pub fn generate_schedule(
     } else {
         Utc::now() + Duration::hours(1)
     };
+    Ok(start)
+}
 
-    for i in 0..video_count {
-        let scheduled_time = start + interval * i as i32;
-        schedule.push(scheduled_time);
-    }
+/// Places videos on the cadence starting at `start` and stepping through
+/// `intervals` in turn, leaving out slots the constraints rule out and
+/// moving those too close to other profiles' videos back. With
+/// `anchor_days`, intervals of whole days keep the local wall-clock time of
+/// `start` instead of a fixed UTC offset.
+pub fn fill_schedule_gaps(
+    video_count: usize,
+    intervals: &[Duration],
+    start: DateTime<Utc>,
+    constraints: &SlotConstraints,
+    anchor_days: bool,
+) -> Result<Vec<DateTime<Utc>>, Box<dyn std::error::Error>> {
+    let shortest = *intervals.iter().min().ok_or("No interval given")?;
+    let anchored = anchor_days
+        && intervals
+            .iter()
+            .all(|interval| *interval == Duration::days(interval.num_days()));
+    let mut last: Option<DateTime<Utc>> = None;
+    let schedule: Vec<_> = intervals
+        .iter()
+        .cycle()
+        .take(MAX_SCHEDULE_SLOTS)
+        .scan(Duration::zero(), |offset, interval| {
+            let current = if anchored {
+                timezone::add_wall_clock(start, *offset)
+            } else {
+                start + *offset
+            };
+            *offset += *interval;
+            Some(current)
+        })
+        .filter(|slot| constraints.allows(*slot, shortest))
+        .map(|slot| constraints.spaced(slot))
+        // A moved slot may run into the next one or rule-out period
+        .filter(|slot| {
+            let free = last.is_none_or(|last| *slot > last) && constraints.allows(*slot, shortest);
+            if free {
+                last = Some(*slot);
+            }
+            free
+        })
+        .take(video_count)
+        .collect();
 
+    if schedule.len() < video_count {
+        return Err(format!(
+            "Only found {} free publish slots for {} videos",
+            schedule.len(),
+            video_count
+        )
+        .into());
+    }
     Ok(schedule)
 }
 
@@ -174,13 +1804,89 @@ pub fn expand_tilde(path: &str) -> String {
     }
 }
 
-pub fn print_schedule(video_files: &Vec<String>, schedule: &Vec<DateTime<Utc>>) {
+/// Lists the publish slots, followed by month grids with `calendar`.
+/// Slots in the local night are called out, they are rarely meant.
+pub fn print_schedule(video_files: &[String], schedule: &[DateTime<Utc>], calendar: bool) {
+    let now = Utc::now();
+    let mut night = Vec::new();
     for (i, (video_file, scheduled_time)) in video_files.iter().zip(schedule.iter()).enumerate() {
-        println!(
-            "{}. {} -> {}",
+        let night_mark = if timezone::is_night(*scheduled_time) {
+            night.push(i + 1);
+            " [night]"
+        } else {
+            ""
+        };
+        info!(
+            "{}. {} -> {} ({}){}",
             i + 1,
             video_file,
-            scheduled_time.format("%Y-%m-%d %H:%M:%S UTC")
+            scheduled_time.format("%Y-%m-%d %H:%M:%S UTC"),
+            timezone::describe_relative(*scheduled_time, now),
+            night_mark
+        );
+    }
+    if !night.is_empty() {
+        let positions: Vec<String> = night.iter().map(|n| format!("#{}", n)).collect();
+        warning!(
+            "{} slot(s) publish between {:02}:00 and {:02}:00 {}: {}, check --interval, --start-time and --timezone",
+            night.len(),
+            timezone::NIGHT_HOURS.start,
+            timezone::NIGHT_HOURS.end,
+            timezone::label(),
+            positions.join(", ")
         );
     }
+    if calendar {
+        let dates: Vec<_> = schedule.iter().map(|t| timezone::local_date(*t)).collect();
+        info!("");
+        for line in calendar::month_grid(&dates) {
+            info!("{}", line);
+        }
+    }
+}
+
+/// Moves (or symlinks) a failed video into `failed_dir` and writes a
+/// `<name>.error.json` sidecar next to it. Returns the quarantined path.
+pub fn quarantine_failed(
+    video_file: &str,
+    failed_dir: &str,
+    mode: QuarantineMode,
+    error: &str,
+) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let failed_dir = PathBuf::from(expand_tilde(failed_dir));
+    fs::create_dir_all(&failed_dir)
+        .map_err(|e| format!("Failed to create '{}': {}", failed_dir.display(), e))?;
+
+    let file_name = Path::new(video_file)
+        .file_name()
+        .ok_or_else(|| format!("Not a file path: {}", video_file))?;
+    let target = failed_dir.join(file_name);
+
+    match mode {
+        QuarantineMode::Move => {
+            if fs::rename(video_file, &target).is_err() {
+                // Rename fails across filesystems, fall back to copy + remove
+                fs::copy(video_file, &target)?;
+                fs::remove_file(video_file)?;
+            }
+        }
+        QuarantineMode::Symlink => {
+            let source = fs::canonicalize(video_file)?;
+            #[cfg(unix)]
+            std::os::unix::fs::symlink(&source, &target)?;
+            #[cfg(not(unix))]
+            fs::copy(&source, &target)?;
+        }
+    }
+
+    let mut sidecar = target.clone().into_os_string();
+    sidecar.push(".error.json");
+    let report = json!({
+        "file": video_file,
+        "error": error,
+        "failed_at": Utc::now().to_rfc3339(),
+    });
+    fs::write(&sidecar, serde_json::to_string_pretty(&report)?)?;
+
+    Ok(target)
 }
@@ -0,0 +1,80 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use url::Url;
+
+use crate::template::render_template;
+
+/// A link listed at the end of every description, e.g. a social profile
+/// or a sponsor.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Link {
+    pub label: String,
+    pub url: String,
+    /// Leaves the URL without UTM parameters, e.g. for sites that reject
+    /// unknown query parameters
+    #[serde(default)]
+    pub plain: bool,
+}
+
+/// UTM parameters added to every link. Values may use `{video_slug}`, so
+/// traffic can be told apart per video.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct UtmParameters {
+    pub source: Option<String>,
+    pub medium: Option<String>,
+    pub campaign: Option<String>,
+    pub content: Option<String>,
+}
+
+/// `text` in lowercase with runs of anything but letters and digits
+/// turned into single hyphens, e.g. `Boss Fight #3` to `boss-fight-3`.
+pub fn slugify(text: &str) -> String {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// One `Label: URL` line per link, with the UTM parameters filled in for
+/// the video `video_slug`. Parameters already in a URL are replaced.
+pub fn link_block(
+    links: &[Link],
+    utm: Option<&UtmParameters>,
+    video_slug: &str,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let values = HashMap::from([("video_slug", video_slug.to_string())]);
+    let parameters: Vec<(&str, String)> = utm
+        .map(|utm| {
+            [
+                ("utm_source", &utm.source),
+                ("utm_medium", &utm.medium),
+                ("utm_campaign", &utm.campaign),
+                ("utm_content", &utm.content),
+            ]
+            .into_iter()
+            .filter_map(|(key, value)| Some((key, render_template(value.as_deref()?, &values))))
+            .collect()
+        })
+        .unwrap_or_default();
+
+    let mut lines = Vec::new();
+    for link in links {
+        let mut url = Url::parse(&link.url)
+            .map_err(|e| format!("Invalid URL '{}' of link '{}': {}", link.url, link.label, e))?;
+        if !link.plain && !parameters.is_empty() {
+            let kept: Vec<(String, String)> = url
+                .query_pairs()
+                .filter(|(key, _)| !parameters.iter().any(|(utm_key, _)| key == utm_key))
+                .map(|(key, value)| (key.into_owned(), value.into_owned()))
+                .collect();
+            url.query_pairs_mut()
+                .clear()
+                .extend_pairs(kept)
+                .extend_pairs(&parameters);
+        }
+        lines.push(format!("{}: {}", link.label, url));
+    }
+    Ok(lines.join("\n"))
+}
@@ -0,0 +1,167 @@
+use chrono::DateTime;
+
+use crate::sanitize::{MAX_DESCRIPTION_BYTES, MAX_TITLE_CHARS};
+
+/// YouTube's limit on the tags field, counted as it is sent: tags joined
+/// by commas, with quotes around tags containing spaces.
+pub const MAX_TAGS_CHARS: usize = 500;
+
+const PRIVACY_STATUSES: [&str; 3] = ["private", "unlisted", "public"];
+
+pub fn check_title(title: &str) -> Vec<String> {
+    let mut issues = Vec::new();
+    if title.trim().is_empty() {
+        issues.push("title is empty".to_string());
+    }
+    if title.contains(['<', '>']) {
+        issues.push("title contains < or >, which YouTube rejects".to_string());
+    }
+    let chars = title.chars().count();
+    if chars > MAX_TITLE_CHARS {
+        issues.push(format!(
+            "title is {} characters, the limit is {}",
+            chars, MAX_TITLE_CHARS
+        ));
+    }
+    issues
+}
+
+pub fn check_description(description: &str) -> Vec<String> {
+    let mut issues = Vec::new();
+    if description.contains(['<', '>']) {
+        issues.push("description contains < or >, which YouTube rejects".to_string());
+    }
+    if description.len() > MAX_DESCRIPTION_BYTES {
+        issues.push(format!(
+            "description is {} bytes, the limit is {}",
+            description.len(),
+            MAX_DESCRIPTION_BYTES
+        ));
+    }
+    issues
+}
+
+pub fn check_tags(tags: &[String]) -> Vec<String> {
+    let mut issues = Vec::new();
+    if tags.iter().any(|tag| tag.contains(['<', '>'])) {
+        issues.push("tags contain < or >, which YouTube rejects".to_string());
+    }
+    let length: usize = tags
+        .iter()
+        .map(|tag| tag.chars().count() + if tag.contains(' ') { 2 } else { 0 })
+        .sum::<usize>()
+        + tags.len().saturating_sub(1);
+    if length > MAX_TAGS_CHARS {
+        issues.push(format!(
+            "tags are {} characters together, the limit is {}",
+            length, MAX_TAGS_CHARS
+        ));
+    }
+    issues
+}
+
+pub fn check_category(category_id: &str) -> Vec<String> {
+    if category_id.is_empty() || !category_id.chars().all(|c| c.is_ascii_digit()) {
+        vec![format!("category_id '{}' is not a numeric ID", category_id)]
+    } else {
+        Vec::new()
+    }
+}
+
+pub fn check_privacy(privacy_status: &str) -> Vec<String> {
+    if PRIVACY_STATUSES.contains(&privacy_status) {
+        Vec::new()
+    } else {
+        vec![format!(
+            "privacy_status '{}' is not one of {}",
+            privacy_status,
+            PRIVACY_STATUSES.join(", ")
+        )]
+    }
+}
+
+/// Accepts BCP-47 style codes such as `en`, `de-AT` or `zh-Hant`.
+pub fn check_language(language: &str) -> Vec<String> {
+    let mut parts = language.split('-');
+    let primary_ok = parts
+        .next()
+        .is_some_and(|p| (2..=3).contains(&p.len()) && p.chars().all(|c| c.is_ascii_lowercase()));
+    let rest_ok =
+        parts.all(|p| (1..=8).contains(&p.len()) && p.chars().all(|c| c.is_ascii_alphanumeric()));
+    if primary_ok && rest_ok {
+        Vec::new()
+    } else {
+        vec![format!(
+            "language '{}' is not a language code like 'en' or 'de-AT'",
+            language
+        )]
+    }
+}
+
+pub fn check_recording_date(recording_date: &str) -> Vec<String> {
+    if DateTime::parse_from_rfc3339(recording_date).is_ok() {
+        Vec::new()
+    } else {
+        vec![format!(
+            "recording_date '{}' is not an RFC 3339 timestamp",
+            recording_date
+        )]
+    }
+}
+
+/// Flags publish times YouTube would not act on: it ignores publishAt
+/// unless the video is private.
+pub fn check_publish_at(privacy_status: &str, publish_at: Option<&str>) -> Vec<String> {
+    let Some(publish_at) = publish_at else {
+        return Vec::new();
+    };
+    let mut issues = Vec::new();
+    if DateTime::parse_from_rfc3339(publish_at).is_err() {
+        issues.push(format!(
+            "scheduled_start_time '{}' is not an RFC 3339 timestamp",
+            publish_at
+        ));
+    }
+    if privacy_status != "private" {
+        issues.push(format!(
+            "scheduled_start_time is ignored unless privacy_status is private, not '{}'",
+            privacy_status
+        ));
+    }
+    issues
+}
+
+/// Titles compare equal when they only differ in case or whitespace.
+fn title_key(title: &str) -> String {
+    title
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// Pairs of indices `(earlier, later)` whose titles are the same.
+pub fn duplicate_titles<S: AsRef<str>>(titles: &[S]) -> Vec<(usize, usize)> {
+    let mut seen = std::collections::HashMap::new();
+    let mut duplicates = Vec::new();
+    for (i, title) in titles.iter().enumerate() {
+        if let Some(&first) = seen.get(&title_key(title.as_ref())) {
+            duplicates.push((first, i));
+        } else {
+            seen.insert(title_key(title.as_ref()), i);
+        }
+    }
+    duplicates
+}
+
+/// Indices of `titles` that match one of `existing`.
+pub fn titles_in<S: AsRef<str>, T: AsRef<str>>(titles: &[S], existing: &[T]) -> Vec<usize> {
+    let existing: std::collections::HashSet<String> =
+        existing.iter().map(|t| title_key(t.as_ref())).collect();
+    titles
+        .iter()
+        .enumerate()
+        .filter(|(_, title)| existing.contains(&title_key(title.as_ref())))
+        .map(|(i, _)| i)
+        .collect()
+}
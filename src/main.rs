@@ -1,26 +1,2462 @@
 use chrono::{DateTime, Utc};
-use clap::Parser;
-use youtube::{create_default_metadata, load_oauth_config, load_video_metadata, YouTubeUploader};
+use clap::{Parser, ValueEnum};
+use serde_json::json;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::{Arc, Mutex};
+use std::time::{Instant, SystemTime};
+use tokio::sync::{oneshot, OwnedSemaphorePermit, Semaphore};
+use youtube_scheduler::auth::{self, FileTokenStore, MemoryTokenStore, TokenStore};
+use youtube_scheduler::captions::{CaptionSource, CaptionsConfig};
+use youtube_scheduler::chunks::{ChunkReader, IoBackend};
+use youtube_scheduler::config::{load_config, Config, ScheduleTemplate};
+use youtube_scheduler::control::{CancellationToken, PauseControl};
+use youtube_scheduler::data_budget::DataBudget;
+use youtube_scheduler::description_pool::{DescriptionPool, PoolOrder};
+use youtube_scheduler::feed::FeedItem;
+use youtube_scheduler::ffmpeg::{PreprocessOptions, ThumbnailOffset};
+use youtube_scheduler::ffprobe::{ChapterSource, MetadataSource};
+use youtube_scheduler::history::{self, UploadHistory};
+use youtube_scheduler::journal::{self, Event};
+use youtube_scheduler::lint;
+use youtube_scheduler::pending::{Action, PendingAction, PendingQueue};
+use youtube_scheduler::plan::{
+    Manifest, ManifestEntry, Plan, PlanEntry, ResolvedEntry, ScheduleChange, Selection,
+};
+use youtube_scheduler::preflight::PreflightMode;
+use youtube_scheduler::report::{
+    format_duration, format_size, render_note, render_summary, BatchProgress, BatchStats,
+    CaptionResult, Outcome, ProgressReporter, UploadResult,
+};
+use youtube_scheduler::screening::{Screener, ScreeningAction};
+use youtube_scheduler::series::{Episode, SeriesIndex};
+use youtube_scheduler::shared_schedule::{ScheduledVideo, SharedSchedule};
+use youtube_scheduler::skip_dates::SkipDates;
+use youtube_scheduler::spool::Artifacts;
+use youtube_scheduler::telegram::TelegramBot;
+use youtube_scheduler::watchdog::ProcessingState;
+use youtube_scheduler::youtube::{
+    create_default_metadata, load_oauth_config, load_video_metadata, Channel, OAuthConfig,
+    UploadError, VideoMetadata, YouTubeUploader,
+};
 use youtube_scheduler::*;
 
 #[cfg(test)]
 mod test;
-mod youtube;
+
+/// Number of recent channel uploads checked by --check-channel-titles
+const CHANNEL_TITLE_HISTORY: usize = 200;
+/// Number of recent channel uploads searched for scheduled videos by --fill-gaps
+const CHANNEL_SCHEDULE_HISTORY: usize = 200;
+
+/// Videos, their resolved metadata and publish slots, ready for upload.
+struct Batch {
+    video_files: Vec<String>,
+    metadata: Vec<VideoMetadata>,
+    schedule: Vec<DateTime<Utc>>,
+    description_pool: Option<DescriptionPool>,
+    /// Profile the videos were prepared with
+    profile: String,
+}
+
+/// Where the videos of a batch come from.
+enum BatchSource {
+    /// --videos and --metadata
+    Args,
+    /// Files found by watch mode, already checked for stability, with the
+    /// profile of the folder they were found in
+    Watched { files: Vec<String>, profile: String },
+}
 
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args = Args::parse();
-    let video_files: Vec<String> = args
-        .videos()
-        .split(',')
-        .map(|s| s.trim().to_string())
+async fn main() -> ExitCode {
+    let mut args = match Args::try_parse() {
+        Ok(args) => args,
+        Err(e) => {
+            let _ = e.print();
+            return if e.use_stderr() {
+                ExitStatus::ValidationError.into()
+            } else {
+                ExitStatus::Success.into()
+            };
+        }
+    };
+
+    output::init(args.verbosity(), args.color(), args.output());
+    if let Err(e) = fault::init(args.inject_failure()) {
+        error!("Error: {}", e);
+        return ExitStatus::ValidationError.into();
+    }
+    match args.clock_skew_tolerance() {
+        Ok(tolerance) => clock::init(tolerance),
+        Err(e) => {
+            error!("Error: {}", e);
+            return ExitStatus::ValidationError.into();
+        }
+    }
+    if let Some(path) = args.debug_http() {
+        if let Err(e) = http_trace::init(path) {
+            error!("Error: Could not create HTTP capture {}: {}", path, e);
+            return ExitStatus::ValidationError.into();
+        }
+    }
+    if let Some(name) = args.client().cloned() {
+        let client = load_config(args.config()).and_then(|config| config.client(&name));
+        // Credentials from the environment would override the client's own
+        let overriding: Vec<&str> = auth::CREDENTIAL_VARS
+            .into_iter()
+            .filter(|var| std::env::var_os(var).is_some())
+            .collect();
+        match client {
+            Ok(_) if !overriding.is_empty() => {
+                error!(
+                    "Error: --client can't be used while {} is set",
+                    overriding.join(", ")
+                );
+                return ExitStatus::ValidationError.into();
+            }
+            Ok(client) => args.use_client(&client),
+            Err(e) => {
+                error!("Error: {}", e);
+                return ExitStatus::ValidationError.into();
+            }
+        }
+    }
+    // A broken config is reported by whatever needs it later
+    if let Ok(config) = load_config(args.config()) {
+        endpoints::init(config.endpoints, config.client, config.http);
+    }
+    if args.simulate() {
+        args.isolate_simulation_state();
+        let started = match load_config(args.config()) {
+            Ok(config) => simulator::start(config.simulator)
+                .await
+                .map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+        if let Err(e) = started {
+            error!("Error: Could not start the simulator: {}", e);
+            return ExitStatus::ValidationError.into();
+        }
+        warning!("simulating the YouTube API, nothing is uploaded");
+    }
+    // A template's timezone applies unless --timezone overrides it
+    let template_zone = match schedule_template(&args) {
+        Ok(template) => template.and_then(|template| template.timezone),
+        Err(e) => {
+            error!("Error: {}", e);
+            return ExitStatus::ValidationError.into();
+        }
+    };
+    if let Some(zone) = args.timezone().or(template_zone.as_ref()) {
+        if let Err(e) = timezone::init(zone) {
+            error!("Error: {}", e);
+            return ExitStatus::ValidationError.into();
+        }
+    }
+
+    match run(&args).await {
+        Ok(status) => status.into(),
+        Err(e) => {
+            error!("Error: {}", e);
+            exit_status_of(e.as_ref()).into()
+        }
+    }
+}
+
+async fn run(args: &Args) -> Result<ExitStatus, Box<dyn std::error::Error>> {
+    match args.command() {
+        Some(Command::Schedule(ScheduleCommand::Save { name })) => {
+            let occupied = channel_schedule(args).await?;
+            return save_named_plan(args, name, &occupied);
+        }
+        Some(Command::Schedule(ScheduleCommand::Manifest { path })) => {
+            return save_manifest(args, path).await
+        }
+        Some(Command::Journal(command)) => return show_journal(args, command),
+        Some(Command::Watch {
+            dir,
+            poll_interval,
+            claim,
+            worker_id,
+        }) => {
+            let worker = claim.then(|| worker_id.clone().unwrap_or_else(watch::hostname));
+            return run_watch(args, dir, poll_interval, worker.as_deref()).await;
+        }
+        Some(Command::RunPending) => return run_pending(args).await,
+        Some(Command::Auth(AuthCommand::Status)) => return auth_status(args).await,
+        Some(Command::Clean { older_than }) => return run_clean(args, older_than),
+        Some(Command::Bench {
+            disk: Some(file), ..
+        }) => return run_disk_bench(args, file).await,
+        Some(Command::Bench { size, .. }) => return run_bench(args, size).await,
+        Some(Command::Lint {
+            metadata,
+            videos,
+            interval,
+            start_time,
+        }) => {
+            return run_lint(
+                args,
+                metadata.as_deref(),
+                videos,
+                interval.as_deref(),
+                start_time.as_deref(),
+            )
+        }
+        _ => {}
+    }
+    journal::init(args.state_dir());
+    history::init_fingerprint_cache(args.state_dir());
+
+    let oauth_config = load_oauth_config(args.oauth_config()).map_err(Failure::auth)?;
+    let batch = match args.command() {
+        Some(Command::Upload {
+            manifest: Some(path),
+            ..
+        }) => load_manifest(args, path),
+        Some(Command::Upload {
+            plan: Some(plan), ..
+        }) => load_named_plan(args, plan),
+        _ => {
+            if !args.dry_run() {
+                download_sources(args).await?;
+            }
+            let occupied = channel_schedule(args).await?;
+            prepare_batch(args, BatchSource::Args, &occupied)
+        }
+    }
+    .map_err(Failure::validation)?;
+
+    if batch.video_files.is_empty() {
+        info!("Every video was already uploaded, pass --force-all to upload again.");
+        return Ok(ExitStatus::Success);
+    }
+
+    let selection = args.selection().map_err(Failure::validation)?;
+    let batch = if selection.is_empty() {
+        batch
+    } else {
+        let total = batch.video_files.len();
+        let batch = select_entries(batch, &selection);
+        info!(
+            "Selected {} of {} videos with --only/--skip/--limit",
+            batch.video_files.len(),
+            total
+        );
+        if batch.video_files.is_empty() {
+            return Err(Failure::validation("No videos left after --only/--skip/--limit").into());
+        }
+        batch
+    };
+
+    //Display schedule
+    info!("Upload Schedule:");
+    info!("================");
+    print_schedule(&batch.video_files, &batch.schedule, args.calendar());
+
+    let entries = plan::schedule_entries(&batch.video_files, &batch.schedule);
+    if let Some(previous) = plan::load_last_schedule(args.state_dir()) {
+        print_schedule_diff(&plan::diff_schedules(&previous, &entries));
+    }
+    plan::save_last_schedule(args.state_dir(), &entries)?;
+    journal::record(Event::ScheduleComputed {
+        videos: batch.video_files.len(),
+        first: batch.schedule.first().copied(),
+        last: batch.schedule.last().copied(),
+    });
+
+    let entries = resolved_plan(&batch);
+    // Going public right away can't be undone, --yes doesn't cover it
+    let public_now = immediate_public(&batch);
+    let public_problem = format!(
+        "{} video(s) would be public as soon as they are uploaded: {}",
+        public_now.len(),
+        public_now.join(", ")
+    );
+    if args.dry_run() {
+        if !public_now.is_empty() && !args.allow_immediate_public() {
+            warning!(
+                "{}, uploading needs --allow-immediate-public",
+                public_problem
+            );
+        }
+        let hash = plan::plan_hash(&entries);
+        info!("\nPlan hash: {}", hash);
+        output::emit(&json!({
+            "at": Utc::now(),
+            "event": "dry_run_plan",
+            "plan_hash": hash,
+            "quota_cost": entries.iter().map(|entry| entry.quota_cost).sum::<u64>(),
+            "entries": entries,
+        }));
+        if let Some(path) = args.require_approved_plan() {
+            plan::write_plan_for_approval(path, entries)?;
+            info!("Wrote the plan for approval to {}", path);
+        }
+        info!("\nDry run complete. No videos were uploaded.");
+        return Ok(ExitStatus::Success);
+    }
+    if let Some(path) = args.require_approved_plan() {
+        plan::check_approved_plan(path, &entries).map_err(Failure::validation)?;
+        verbose!("Plan matches the approved plan in {}", path);
+    }
+
+    if !public_now.is_empty() && !args.allow_immediate_public() {
+        if !is_interactive() {
+            return Err(Failure::validation(format!(
+                "{}. Pass --allow-immediate-public",
+                public_problem
+            ))
+            .into());
+        }
+        warning!("{}", public_problem);
+        output::print("Type 'public' to upload them anyway: ");
+        let mut input = String::new();
+        std::io::stdin().read_line(&mut input)?;
+        if input.trim() != "public" {
+            info!("Upload cancelled.");
+            return Ok(ExitStatus::Cancelled);
+        }
+    }
+
+    // Confirm before proceeding
+    if args.yes() {
+        verbose!("Skipping confirmation (--yes)");
+    } else if !is_interactive() {
+        match args.non_interactive() {
+            NonInteractivePolicy::Proceed => {
+                info!("stdin is not a terminal, proceeding without confirmation");
+            }
+            NonInteractivePolicy::Fail => {
+                return Err(Failure::validation(
+                    "stdin is not a terminal, pass --yes or --non-interactive proceed to upload",
+                )
+                .into());
+            }
+        }
+    } else {
+        output::print("\nProceed with upload? (y/N): ");
+        let mut input = String::new();
+        std::io::stdin().read_line(&mut input)?;
+        if !input.trim().to_lowercase().starts_with('y') {
+            info!("Upload cancelled.");
+            return Ok(ExitStatus::Cancelled);
+        }
+    }
+
+    let session = Session::start(args)?;
+    upload_batch(args, &oauth_config, batch, &session).await
+}
+
+/// Parts of an upload run that outlive a single batch in watch mode.
+struct Session {
+    pause: PauseControl,
+    progress: Arc<Mutex<BatchProgress>>,
+    telegram: Option<TelegramBot>,
+}
+
+impl Session {
+    fn start(args: &Args) -> Result<Self, Box<dyn std::error::Error>> {
+        let pause = PauseControl::new();
+        let progress = Arc::new(Mutex::new(BatchProgress::default()));
+        pause.listen_for_signals()?;
+        verbose!(
+            "Send SIGUSR1 to pause after the current video, SIGUSR2 to resume (pid {})",
+            std::process::id()
+        );
+        let telegram = if args.telegram() {
+            let telegram_config = load_config(args.config())?.telegram.ok_or_else(|| {
+                Failure::validation("--telegram needs a telegram section in the config file")
+            })?;
+            let bot = TelegramBot::new(&telegram_config).map_err(Failure::validation)?;
+            bot.listen(pause.clone(), progress.clone());
+            Some(bot)
+        } else {
+            None
+        };
+        Ok(Self {
+            pause,
+            progress,
+            telegram,
+        })
+    }
+}
+
+/// Preprocesses and uploads a confirmed batch, then reports the results.
+async fn upload_batch(
+    args: &Args,
+    oauth_config: &OAuthConfig,
+    mut batch: Batch,
+    session: &Session,
+) -> Result<ExitStatus, Box<dyn std::error::Error>> {
+    // Time passed while confirming, and saved plans may be old
+    check_lead_time(
+        &batch.video_files,
+        &batch.schedule,
+        Utc::now() + parse_duration(args.min_lead_time())?,
+    )
+    .map_err(Failure::validation)?;
+    // Edited plans and metadata files can ask for a publish time YouTube
+    // would ignore
+    let queued_privacy = args
+        .publish_actions()
+        .iter()
+        .any(|a| matches!(a, Action::SetPrivacy { .. }));
+    for problem in check_publish_privacy(&batch.video_files, &mut batch.metadata, queued_privacy) {
+        warning!("{}", problem);
+    }
+    let progress_interval = parse_duration(args.progress_interval())
+        .and_then(|d| Ok(d.to_std()?))
+        .map_err(Failure::validation)?;
+    let heartbeat = parse_duration(args.heartbeat())
+        .and_then(|d| Ok(d.to_std()?))
+        .map_err(Failure::validation)?;
+    let heartbeat = (!heartbeat.is_zero()).then_some(heartbeat);
+    let chunk_options = args.chunk_options().map_err(Failure::validation)?;
+    let upload_window = args.upload_window().map_err(Failure::validation)?;
+    let mut data_budget = match args.data_budget().map_err(Failure::validation)? {
+        Some(limit) => Some(DataBudget::load(
+            limit,
+            args.data_budget_period(),
+            args.state_dir(),
+            timezone::local_date(Utc::now()),
+        )?),
+        None => None,
+    };
+
+    if let Some(pool) = &batch.description_pool {
+        pool.save_cursor(args.state_dir())?;
+    }
+
+    // Fingerprint the videos for the upload history while the first ones
+    // upload, so recording each success does not wait on the disk
+    let hash_files = batch.video_files.clone();
+    let hash_jobs = args.hash_jobs();
+    tokio::task::spawn_blocking(move || history::prefetch_fingerprints(&hash_files, hash_jobs));
+
+    // Download and preprocess videos in the background so uploads can
+    // start as soon as the first file is ready
+    let preprocess_options = args.preprocess_options();
+    if batch
+        .video_files
+        .iter()
+        .any(|f| preprocess_options.applies_to(f))
+        && !ffmpeg::ffmpeg_available()
+    {
+        return Err(Failure::validation(
+            "Audio files, --prepend/--append and --transcode require ffmpeg in PATH",
+        )
+        .into());
+    }
+    // Encodes write a copy of every video, fail now rather than halfway
+    let cache_dir = ffmpeg::cache_dir(args.state_dir());
+    // Unreadable files fail on their own once their turn comes
+    let needed: u64 = batch
+        .video_files
+        .iter()
+        .map(|f| ffmpeg::pending_cache_bytes(f, &preprocess_options, &cache_dir).unwrap_or(0))
+        .sum();
+    if needed > 0 {
+        if let Some(available) = preflight::available_space(&cache_dir)? {
+            if let Some(problem) = preflight::check_free_space(&cache_dir, needed, available) {
+                return Err(Failure::validation(format!(
+                    "Not enough disk space for preprocessing: {}",
+                    problem
+                ))
+                .into());
+            }
+        }
+    }
+    if args.auto_thumbnail() {
+        ThumbnailOffset::parse(args.thumbnail_at()).map_err(Failure::validation)?;
+        if !ffmpeg::ffmpeg_available() {
+            return Err(Failure::validation("--auto-thumbnail requires ffmpeg in PATH").into());
+        }
+    }
+    let captions_config = match args.captions() {
+        CaptionSource::Auto => {
+            let config = load_config(args.config())?.captions.ok_or_else(|| {
+                Failure::validation("--captions auto needs a captions section in the config file")
+            })?;
+            let wants_audio = config
+                .transcribe_command
+                .iter()
+                .any(|arg| arg.contains("{audio}"));
+            if wants_audio && !ffmpeg::ffmpeg_available() {
+                return Err(Failure::validation(
+                    "{audio} in the transcription command requires ffmpeg in PATH",
+                )
+                .into());
+            }
+            Some(config)
+        }
+        CaptionSource::Sidecar | CaptionSource::Off => None,
+    };
+    let jobs = StageJobs {
+        download: args.download_jobs(),
+        preprocess: args.preprocess_jobs(),
+    };
+    let mut pending_files: Vec<_> = spawn_pipeline(
+        &batch.video_files,
+        &batch.metadata,
+        preprocess_options.clone(),
+        cache_dir.clone(),
+        jobs,
+        DownloadChecks::from_args(args).map_err(Failure::validation)?,
+    )
+    .into_iter()
+    .map(Some)
+    .collect();
+    let mut upload_files: Vec<Option<Result<String, String>>> = vec![None; batch.video_files.len()];
+
+    // Create uploader and authenticate
+    let mut uploader = YouTubeUploader::with_token_store(oauth_config, token_store(args))
+        .map_err(Failure::auth)?;
+    uploader.set_chunk_options(chunk_options);
+    uploader.set_refresh_margin(args.token_refresh_margin().map_err(Failure::validation)?);
+    if args.calendar_id().is_some() {
+        uploader.add_scope(youtube::CALENDAR_SCOPE);
+    }
+    if args.check_channel_titles() {
+        uploader.add_scope(youtube::READONLY_SCOPE);
+    }
+    if args.captions() != CaptionSource::Off {
+        uploader.add_scope(youtube::COMMENT_SCOPE);
+    }
+    if batch
+        .metadata
+        .iter()
+        .any(|m| m.series().is_some() || m.has_post_upload_placeholders())
+    {
+        uploader.add_scope(youtube::MANAGE_SCOPE);
+    }
+    // Asked for now so run-pending can run unattended later
+    for action in args.publish_actions() {
+        uploader.add_scope(action_scope(&action));
+    }
+    let expected_channel = load_config(args.config())?
+        .profile(&batch.profile)?
+        .channel_id;
+    if expected_channel.is_some() {
+        uploader.add_scope(youtube::READONLY_SCOPE);
+    }
+    let wait_processing = args.wait_processing().map_err(Failure::validation)?;
+    if wait_processing.is_some() {
+        uploader.add_scope(youtube::READONLY_SCOPE);
+    }
+    if args.reupload_rejected() > 0 {
+        uploader.add_scope(youtube::MANAGE_SCOPE);
+    }
+
+    info!("Authenticating with YouTube...");
+    uploader.authenticate().await.map_err(Failure::auth)?;
+    check_channel(&uploader, expected_channel.as_deref(), &batch.profile).await?;
+
+    if args.check_channel_titles() {
+        warn_channel_duplicates(&uploader, &batch).await;
+    }
+
+    let total = batch.video_files.len();
+    let pause = &session.pause;
+    let progress = &session.progress;
+    let telegram = &session.telegram;
+    *progress.lock().unwrap() = BatchProgress::new(total);
+    if let Some(bot) = telegram {
+        bot.notify(&format!("Starting upload of {} videos", total))
+            .await;
+    }
+
+    // Upload videos
+    info!("\nUploading videos...");
+    let mut feed_items = Vec::new();
+    let mut quota_exhausted = false;
+    let mut uploaded = 0;
+    let mut attempts = 0;
+    let mut stats = BatchStats::default();
+    let mut reauthorized = HashSet::new();
+    let mut reuploads = ReuploadSources {
+        files: HashMap::new(),
+        profile: &batch.profile,
+        captions_config: captions_config.as_ref(),
+    };
+    let started = std::time::Instant::now();
+    let mut queue: VecDeque<usize> = (0..total).collect();
+    loop {
+        if pause.take_retry_request() {
+            let failed = progress.lock().unwrap().failed_indices();
+            info!("Retrying {} failed videos", failed.len());
+            for index in failed {
+                if !queue.contains(&index) {
+                    queue.push_back(index);
+                }
+            }
+        }
+        let Some(i) = queue.pop_front() else {
+            break;
+        };
+        let video_file = &batch.video_files[i];
+        let video_metadata = &batch.metadata[i];
+        let publish_at = batch.schedule[i];
+
+        if pause.is_paused() {
+            info!("Paused, send SIGUSR2 to resume");
+            pause.wait_while_paused().await;
+            // The access token may have expired while paused
+            uploader.authenticate().await.map_err(Failure::auth)?;
+        }
+
+        if let Some(window) = upload_window {
+            let opens = window.next_open(Utc::now());
+            if opens > Utc::now() {
+                info!(
+                    "Waiting for the upload window, opens {}",
+                    opens.format("%Y-%m-%d %H:%M UTC")
+                );
+                tokio::time::sleep((opens - Utc::now()).to_std().unwrap_or_default()).await;
+                uploader.authenticate().await.map_err(Failure::auth)?;
+            }
+        }
+
+        attempts += 1;
+        if attempts % 8 == 0 {
+            verbose!("Refreshing token");
+            uploader.authenticate().await.map_err(Failure::auth)?;
+        }
+
+        // Lets the pipeline start on another video once this one is done
+        let mut _place = None;
+        if let Some(pending_file) = pending_files[i].take() {
+            upload_files[i] = Some(match pending_file.await {
+                Ok((result, place)) => {
+                    _place = place;
+                    result
+                }
+                Err(_) => Err("task failed".to_string()),
+            });
+        }
+        if let (Some(budget), Some(Ok(upload_file))) = (&data_budget, &upload_files[i]) {
+            let size = fs::metadata(upload_file).map_or(0, |m| m.len());
+            if !budget.fits(size) {
+                if size > budget.limit() {
+                    error!(
+                        "{} ({}) is larger than --data-budget {} and cannot be uploaded with it",
+                        video_file,
+                        format_size(size),
+                        format_size(budget.limit())
+                    );
+                } else {
+                    info!(
+                        "Data budget reached: {} of {} sent and {} needs {}. \
+                         Stopping, the next run uploads the {} video(s) left",
+                        format_size(budget.used()),
+                        format_size(budget.limit()),
+                        video_file,
+                        format_size(size),
+                        queue.len() + 1
+                    );
+                }
+                break;
+            }
+        }
+        {
+            let mut progress = progress.lock().unwrap();
+            progress.current = Some(video_file.clone());
+            if progress.results[i].is_some() {
+                stats.retries += 1;
+            }
+        }
+        let reporter = ProgressReporter::new(video_file, progress_interval, heartbeat);
+        let result = match &upload_files[i] {
+            Some(Ok(upload_file)) => {
+                info!("Uploading {} ({}/{})", video_file, i + 1, total);
+                journal::record(Event::UploadStarted {
+                    file: video_file.clone(),
+                });
+                stats.quota += youtube::UPLOAD_QUOTA_COST;
+                // The CLI aborts uploads by exiting, so nothing cancels this
+                uploader
+                    .upload_video_with_progress(
+                        upload_file,
+                        video_metadata,
+                        &CancellationToken::new(),
+                        &|progress| reporter.report(progress),
+                    )
+                    .await
+            }
+            Some(Err(e)) => Err(format!("Preparing the file failed: {}", e).into()),
+            None => Err("Preprocessing result missing".into()),
+        };
+        if let Some((transfer, seconds)) = reporter.transfer() {
+            stats.add_transfer(&transfer, seconds);
+            if let Some(budget) = &mut data_budget {
+                if let Err(e) = budget.record(transfer.bytes_sent, timezone::local_date(Utc::now()))
+                {
+                    warning!("Could not record the data sent: {}", e);
+                }
+            }
+        }
+        // A token revoked mid-run: authorize again, inline when
+        // interactive, and resume with the same video
+        if let Err(e) = &result {
+            if !reauthorized.contains(&i)
+                && e.downcast_ref::<UploadError>()
+                    .is_some_and(UploadError::is_unauthorized)
+            {
+                warning!("{}", e);
+                info!(
+                    "Authenticating again, {} videos left to upload",
+                    queue.len() + 1
+                );
+                uploader.reauthenticate().await.map_err(Failure::auth)?;
+                // Authorizing again may have picked another account
+                check_channel(&uploader, expected_channel.as_deref(), &batch.profile).await?;
+                reauthorized.insert(i);
+                progress.lock().unwrap().current = None;
+                queue.push_front(i);
+                continue;
+            }
+        }
+
+        let mut caption_results = Vec::new();
+        let mut artifacts = Artifacts::new(cache_dir.clone());
+        if let Ok(files) = ffmpeg::cached_files(video_file, &preprocess_options, &cache_dir) {
+            files.iter().for_each(|file| artifacts.add(file));
+        }
+        let outcome = match &result {
+            Ok(response) => {
+                success!(
+                    "✓ Successfully uploaded: {} (ID: {})",
+                    video_file,
+                    response.id
+                );
+                journal::record(Event::UploadSucceeded {
+                    file: video_file.clone(),
+                    video_id: response.id.clone(),
+                });
+                if let Some(Ok(upload_file)) = &upload_files[i] {
+                    if args.reupload_rejected() > 0 {
+                        reuploads.files.insert(
+                            video_file.clone(),
+                            (upload_file.clone(), video_metadata.clone()),
+                        );
+                    }
+                }
+                let video = ScheduledVideo {
+                    profile: batch.profile.clone(),
+                    file: video_file.clone(),
+                    video_id: response.id.clone(),
+                    publish_at,
+                };
+                caption_results = finish_upload(
+                    args,
+                    &uploader,
+                    captions_config.as_ref(),
+                    &video,
+                    video_metadata,
+                    &mut artifacts,
+                    &mut stats.quota,
+                )
+                .await;
+                feed_items.push(FeedItem {
+                    file: video_file.clone(),
+                    title: video_metadata.title().to_string(),
+                    publish_at,
+                    video_id: Some(response.id.clone()),
+                });
+                if !args.keep_artifacts() {
+                    artifacts.remove();
+                }
+                Outcome::Uploaded {
+                    video_id: response.id.clone(),
+                }
+            }
+            Err(e) => {
+                error!("✗ Failed to upload {}: {}", video_file, e);
+                for file in artifacts.files() {
+                    verbose!("Keeping {} for the retry", file.display());
+                }
+                journal::record(Event::UploadFailed {
+                    file: video_file.clone(),
+                    error: e.to_string(),
+                });
+                Outcome::Failed {
+                    error: e.to_string(),
+                    permanent: e
+                        .downcast_ref::<UploadError>()
+                        .is_some_and(UploadError::is_permanent),
+                }
+            }
+        };
+
+        if let Some(bot) = telegram {
+            let message = match &outcome {
+                Outcome::Uploaded { video_id } => format!(
+                    "✓ {} uploaded, publishes {}\nhttps://youtu.be/{}",
+                    video_file,
+                    publish_at.format("%Y-%m-%d %H:%M UTC"),
+                    video_id
+                ),
+                Outcome::Failed { error, .. } => {
+                    format!("✗ {} failed: {}", video_file, error)
+                }
+            };
+            bot.notify(&message).await;
+        }
+        {
+            let mut progress = progress.lock().unwrap();
+            progress.current = None;
+            progress.results[i] = Some(UploadResult {
+                file: video_file.clone(),
+                title: video_metadata.title().to_string(),
+                publish_at,
+                outcome,
+                captions: caption_results,
+            });
+        }
+
+        if let Err(e) = result {
+            if e.downcast_ref::<UploadError>()
+                .is_some_and(UploadError::is_quota_exhausted)
+            {
+                error!("Stopping batch, the remaining uploads would fail as well.");
+                quota_exhausted = true;
+                break;
+            }
+        } else {
+            uploaded += 1;
+            if args.stop_after() == Some(uploaded) && !queue.is_empty() {
+                info!(
+                    "Stopping after {} uploads, continue with --start-after \"{}\"",
+                    uploaded, video_file
+                );
+                break;
+            }
+        }
+    }
+
+    // Failed videos are only moved once no retry can pick them up anymore
+    let results = progress.lock().unwrap().finished();
+    // Simulated failures say nothing about the files themselves, and
+    // files that failed for a passing reason can go again as they are
+    if let Some(failed_dir) = args.failed_dir().filter(|_| !args.simulate()) {
+        for result in &results {
+            if let Outcome::Failed {
+                error,
+                permanent: true,
+            } = &result.outcome
+            {
+                match quarantine_failed(&result.file, failed_dir, args.quarantine(), error) {
+                    Ok(path) => info!("Quarantined {} to {}", result.file, path.display()),
+                    Err(qe) => error!("Could not quarantine {}: {}", result.file, qe),
+                }
+            }
+        }
+    }
+
+    info!("\nUpload process completed!");
+    stats.succeeded = results.iter().filter(|r| r.is_success()).count();
+    stats.failed = results.len() - stats.succeeded;
+    stats.duration_secs = started.elapsed().as_secs();
+    info!("{}", stats.render());
+    journal::record(Event::BatchFinished(stats));
+    if let Some(feed_path) = args.feed() {
+        match feed::update_feed(args.state_dir(), feed_path, feed_items, Utc::now()) {
+            Ok(()) => verbose!("Updated feed {}", feed_path),
+            Err(e) => warning!("Could not update feed {}: {}", feed_path, e),
+        }
+    }
+    if let Some(dir) = args.export_notes() {
+        match export_note(dir, &results, Utc::now()) {
+            Ok(path) => info!("Wrote batch note {}", path.display()),
+            Err(e) => warning!("Could not write the batch note into {}: {}", dir, e),
+        }
+    }
+    if !args.email_to().is_empty() {
+        send_summary_email(args, &results).await;
+    }
+    if let Some(bot) = telegram {
+        bot.notify(&render_summary(&results)).await;
+    }
+    let processing_problems = match wait_processing {
+        Some(limit) => {
+            watch_processing(args, &mut uploader, telegram, &results, &reuploads, limit).await
+        }
+        None => 0,
+    };
+
+    if quota_exhausted {
+        return Ok(ExitStatus::QuotaExhausted);
+    }
+    let failures = results.iter().filter(|r| !r.is_success()).count() + processing_problems;
+    if failures > 0 {
+        error!("{} of {} uploads failed.", failures, total);
+        return Ok(ExitStatus::PartialFailure);
+    }
+    Ok(ExitStatus::Success)
+}
+
+/// Runs everything that follows the upload of `video`: queues its publish
+/// actions, records it, sets the thumbnail, adds it to its series, fills in
+/// the links, writes the site post, uploads captions and adds the calendar
+/// event. Counts the quota used into `quota` and returns the caption results.
+async fn finish_upload(
+    args: &Args,
+    uploader: &YouTubeUploader,
+    captions_config: Option<&CaptionsConfig>,
+    video: &ScheduledVideo,
+    metadata: &VideoMetadata,
+    artifacts: &mut Artifacts,
+    quota: &mut u64,
+) -> Vec<CaptionResult> {
+    let actions: Vec<PendingAction> = args
+        .publish_actions()
+        .into_iter()
+        .map(|action| PendingAction {
+            video_id: video.video_id.clone(),
+            file: video.file.clone(),
+            at: video.publish_at,
+            action,
+        })
+        .collect();
+    if !actions.is_empty() {
+        if let Err(e) = PendingQueue::add(args.state_dir(), actions) {
+            warning!("Could not queue publish actions of {}: {}", video.file, e);
+        }
+    }
+    if let Err(e) = UploadHistory::record(
+        args.state_dir(),
+        &video.file,
+        &video.video_id,
+        video.publish_at,
+        metadata.title_variant(),
+    ) {
+        warning!("Could not record upload of {}: {}", video.file, e);
+    }
+    if let Some(image) = metadata.thumbnail() {
+        if let Err(e) = uploader
+            .set_thumbnail(&video.video_id, Path::new(image))
+            .await
+        {
+            warning!("Could not set the thumbnail of {}: {}", video.file, e);
+        }
+    // Audio files show their cover, there is no frame to take
+    } else if args.auto_thumbnail() && !ffmpeg::is_audio_file(&video.file) {
+        let offset = metadata.thumbnail_at().unwrap_or(args.thumbnail_at());
+        if let Err(e) = set_auto_thumbnail(
+            args,
+            uploader,
+            &video.video_id,
+            &video.file,
+            offset,
+            artifacts,
+        )
+        .await
+        {
+            warning!("Could not set the thumbnail of {}: {}", video.file, e);
+        }
+    }
+    if args.min_spacing().is_some() {
+        if let Err(e) = SharedSchedule::record(args.state_dir(), video.clone(), Utc::now()) {
+            warning!("Could not share the publish time of {}: {}", video.file, e);
+        }
+    }
+    let series_playlist = match add_to_series(args, uploader, &video.video_id, metadata).await {
+        Ok(playlist_id) => playlist_id,
+        Err(e) => {
+            warning!("Could not update the series of {}: {}", video.file, e);
+            None
+        }
+    };
+    let playlist_id = series_playlist.as_deref().or(args.playlist_at_publish());
+    if metadata.has_post_upload_placeholders() {
+        let mut resolved = metadata.clone();
+        resolved.resolve_post_upload(&video.video_id, playlist_id);
+        verbose!("Filling in the links in the description of {}", video.file);
+        *quota += youtube::UPDATE_QUOTA_COST;
+        if let Err(e) = uploader.update_snippet(&video.video_id, &resolved).await {
+            warning!(
+                "Could not fill in the links in the description of {}: {}",
+                video.file,
+                e
+            );
+        }
+    }
+    if let Some(site_dir) = args.site_dir() {
+        let mut resolved = metadata.clone();
+        resolved.resolve_post_upload(&video.video_id, playlist_id);
+        match site::write_post(
+            site_dir,
+            args.site_flavor(),
+            &video.file,
+            &resolved,
+            &video.video_id,
+            video.publish_at,
+        ) {
+            Ok(path) => verbose!("Wrote post {}", path.display()),
+            Err(e) => warning!("Could not write the post of {}: {}", video.file, e),
+        }
+    }
+    let mut caption_results = Vec::new();
+    if args.captions() != CaptionSource::Off {
+        caption_results = upload_captions(
+            args,
+            uploader,
+            captions_config,
+            &video.video_id,
+            &video.file,
+            metadata,
+            artifacts,
+        )
+        .await;
+        *quota += youtube::CAPTION_QUOTA_COST
+            * caption_results.iter().filter(|c| c.error.is_none()).count() as u64;
+    }
+    if let Some(calendar_id) = args.calendar_id() {
+        if let Err(e) = uploader
+            .upsert_calendar_event(calendar_id, &video.video_id, metadata, video.publish_at)
+            .await
+        {
+            warning!("Could not add calendar event for {}: {}", video.file, e);
+        }
+    }
+    caption_results
+}
+
+/// Uploads videos as they appear in `dir`, once they stopped changing for
+/// --min-file-age. Files that fail are retried only after they change.
+async fn run_watch(
+    args: &Args,
+    dir: &str,
+    poll_interval: &str,
+    worker: Option<&str>,
+) -> Result<ExitStatus, Box<dyn std::error::Error>> {
+    let poll_interval = parse_duration(poll_interval)
+        .and_then(|d| Ok(d.to_std()?))
+        .map_err(Failure::validation)?;
+    let min_age = parse_duration(args.min_file_age().map_or("1m", |s| s.as_str()))
+        .and_then(|d| Ok(d.to_std()?))
+        .map_err(Failure::validation)?;
+
+    // The folder itself uses --profile, configured subfolders their own
+    let config = load_config(args.config()).map_err(Failure::validation)?;
+    let mut folders = vec![(dir.to_string(), args.profile().to_string())];
+    for (subdir, profile) in &config.watch_folders {
+        config.profile(profile).map_err(Failure::validation)?;
+        if let Some(client) = args.client().filter(|_| profile != args.profile()) {
+            return Err(Failure::validation(format!(
+                "Watch folder '{}' uses profile '{}', client '{}' may only use '{}'",
+                subdir,
+                profile,
+                client,
+                args.profile()
+            ))
+            .into());
+        }
+        let folder = Path::new(dir).join(subdir);
+        if !folder.is_dir() {
+            return Err(Failure::validation(format!(
+                "Watch folder '{}' does not exist",
+                folder.display()
+            ))
+            .into());
+        }
+        folders.push((folder.to_string_lossy().to_string(), profile.clone()));
+    }
+    folders[1..].sort();
+
+    journal::init(args.state_dir());
+    history::init_fingerprint_cache(args.state_dir());
+    let oauth_config = load_oauth_config(args.oauth_config()).map_err(Failure::auth)?;
+    let session = if args.dry_run() {
+        None
+    } else {
+        Some(Session::start(args)?)
+    };
+
+    for (folder, profile) in &folders {
+        info!("Watching {} for new videos (profile {})", folder, profile);
+    }
+    if let Some(worker) = worker {
+        info!("Claiming files as worker {}", worker);
+    }
+    let upload_window = args.upload_window().map_err(Failure::validation)?;
+    let mut failed: HashMap<String, Option<SystemTime>> = HashMap::new();
+    loop {
+        if !args.dry_run() {
+            if let Err(e) = run_due_actions(args, &oauth_config).await {
+                error!("Error: Could not run pending actions: {}", e);
+            }
+        }
+        // New files wait for the window where they are
+        let closed = upload_window.filter(|window| !args.dry_run() && !window.contains(Utc::now()));
+        if let Some(window) = closed {
+            verbose!(
+                "Outside the upload window, opens {}",
+                window.next_open(Utc::now()).format("%Y-%m-%d %H:%M UTC")
+            );
+            tokio::time::sleep(poll_interval).await;
+            continue;
+        }
+        for (folder, profile) in &folders {
+            let history = UploadHistory::load(args.state_dir())?;
+            let claim_dir = worker.map(|worker| watch::claim_dir(folder, worker));
+            // Files claimed before a restart are picked up again
+            let mut candidates = watch::scan_dir(folder)?;
+            if let Some(claim_dir) = claim_dir.as_ref().filter(|dir| dir.is_dir()) {
+                candidates.extend(watch::scan_dir(&claim_dir.to_string_lossy())?);
+            }
+            let mut ready = Vec::new();
+            for video_file in candidates {
+                let modified = fs::metadata(&video_file).and_then(|m| m.modified()).ok();
+                if history.find(&video_file).is_some()
+                    || failed.get(&video_file).is_some_and(|m| *m == modified)
+                {
+                    continue;
+                }
+                match watch::unstable_reason(&video_file, min_age) {
+                    Ok(None) => match &claim_dir {
+                        Some(claim_dir)
+                            if !args.dry_run()
+                                && !Path::new(&video_file).starts_with(claim_dir) =>
+                        {
+                            match watch::claim(&video_file, claim_dir) {
+                                Ok(Some(claimed)) => ready.push(claimed),
+                                Ok(None) => {
+                                    verbose!("{} was claimed by another worker", video_file)
+                                }
+                                Err(e) => warning!("Could not claim {}: {}", video_file, e),
+                            }
+                        }
+                        _ => ready.push(video_file),
+                    },
+                    Ok(Some(reason)) => verbose!("Waiting for {}: {}", video_file, reason),
+                    Err(e) => warning!("Could not check {}: {}", video_file, e),
+                }
+            }
+            if ready.is_empty() {
+                continue;
+            }
+
+            let mut occupied = history.upcoming_publish_times(Utc::now());
+            occupied.extend(channel_schedule(args).await?);
+            let source = BatchSource::Watched {
+                files: ready.clone(),
+                profile: profile.clone(),
+            };
+            match prepare_batch(args, source, &occupied) {
+                Ok(batch) => {
+                    info!("\nUpload Schedule:");
+                    info!("================");
+                    print_schedule(&batch.video_files, &batch.schedule, args.calendar());
+                    journal::record(Event::ScheduleComputed {
+                        videos: batch.video_files.len(),
+                        first: batch.schedule.first().copied(),
+                        last: batch.schedule.last().copied(),
+                    });
+                    if let Some(session) = &session {
+                        let status = upload_batch(args, &oauth_config, batch, session).await?;
+                        if status == ExitStatus::QuotaExhausted {
+                            return Ok(status);
+                        }
+                    }
+                }
+                Err(e) => error!("Error: {}", e),
+            }
+
+            if session.is_some() {
+                let history = UploadHistory::load(args.state_dir())?;
+                for video_file in ready {
+                    if history.find(&video_file).is_none() {
+                        let modified = fs::metadata(&video_file).and_then(|m| m.modified()).ok();
+                        failed.insert(video_file, modified);
+                    }
+                }
+            }
+        }
+
+        if args.dry_run() {
+            info!("\nDry run complete. No videos were uploaded.");
+            return Ok(ExitStatus::Success);
+        }
+        tokio::time::sleep(poll_interval).await;
+    }
+}
+
+/// Takes the frame at `offset` of `video_file` and sets it as the
+/// thumbnail of `video_id`.
+async fn set_auto_thumbnail(
+    args: &Args,
+    uploader: &YouTubeUploader,
+    video_id: &str,
+    video_file: &str,
+    offset: &str,
+    artifacts: &mut Artifacts,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let offset = ThumbnailOffset::parse(offset)?;
+    let video_file = video_file.to_string();
+    let cache_dir = ffmpeg::cache_dir(args.state_dir());
+    let (frame, image) = tokio::task::spawn_blocking(move || {
+        ffmpeg::extract_thumbnail(&video_file, offset, &cache_dir)
+            .and_then(|frame| Ok((frame.clone(), thumbnail::prepare(&frame, &cache_dir)?)))
+            .map_err(|e| e.to_string())
+    })
+    .await??;
+    verbose!("Setting thumbnail {}", image.display());
+    uploader.set_thumbnail(video_id, &image).await?;
+    artifacts.add(&frame);
+    artifacts.add(&image);
+    Ok(())
+}
+
+/// Records an uploaded episode, adds it to its series' playlist, created
+/// with the first episode, and rewrites the playlist's episode index.
+/// Returns the playlist's ID.
+async fn add_to_series(
+    args: &Args,
+    uploader: &YouTubeUploader,
+    video_id: &str,
+    video_metadata: &VideoMetadata,
+) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    let (Some(name), Some(number)) = (video_metadata.series(), video_metadata.episode()) else {
+        return Ok(None);
+    };
+    let mut index = SeriesIndex::load(args.state_dir())?;
+    let series = index.get_mut(name);
+    series.record(Episode {
+        number,
+        title: video_metadata.title().to_string(),
+        video_id: video_id.to_string(),
+    });
+    let description = series.playlist_description();
+    let playlist_id = match series.playlist_id.clone() {
+        Some(playlist_id) => playlist_id,
+        None => {
+            let playlist_id = match uploader.find_playlist(name).await? {
+                Some(playlist_id) => playlist_id,
+                None => {
+                    info!("Creating playlist \"{}\"", name);
+                    uploader.create_playlist(name, &description).await?
+                }
+            };
+            series.playlist_id = Some(playlist_id.clone());
+            playlist_id
+        }
+    };
+    // Saved before the playlist calls, so a failure doesn't renumber or
+    // create the playlist a second time
+    index.save(args.state_dir())?;
+    uploader.add_to_playlist(&playlist_id, video_id).await?;
+    uploader
+        .update_playlist(&playlist_id, name, &description)
+        .await?;
+    Ok(Some(playlist_id))
+}
+
+/// Adds every caption sidecar of `video_file` to `video_id`, or without
+/// any the SRT transcribed with `transcription`, when given. Tracks fail
+/// one by one without stopping the others, transcripts of added ones go
+/// to `artifacts`.
+async fn upload_captions(
+    args: &Args,
+    uploader: &YouTubeUploader,
+    transcription: Option<&CaptionsConfig>,
+    video_id: &str,
+    video_file: &str,
+    video_metadata: &VideoMetadata,
+    artifacts: &mut Artifacts,
+) -> Vec<CaptionResult> {
+    let default_language = video_metadata.language().unwrap_or("en");
+    let mut tracks: Vec<(String, Result<PathBuf, String>)> =
+        match captions::find_sidecars(video_file) {
+            Ok(sidecars) => sidecars
+                .into_iter()
+                .map(|sidecar| {
+                    let language = sidecar.language.as_deref().unwrap_or(default_language);
+                    (language.to_string(), Ok(sidecar.path))
+                })
+                .collect(),
+            Err(e) => vec![(default_language.to_string(), Err(e.to_string()))],
+        };
+    if let Some(config) = transcription.filter(|_| tracks.is_empty()) {
+        info!("Transcribing {}", video_file);
+        let language = video_metadata
+            .language()
+            .unwrap_or(&config.language)
+            .to_string();
+        let config = config.clone();
+        let video_file = video_file.to_string();
+        let cache_dir = ffmpeg::cache_dir(args.state_dir());
+        let srt = tokio::task::spawn_blocking(move || {
+            captions::transcribe(&video_file, &config, &cache_dir).map_err(|e| e.to_string())
+        })
+        .await
+        .unwrap_or_else(|e| Err(format!("task failed: {}", e)));
+        tracks.push((language, srt));
+    }
+
+    let mut results = Vec::new();
+    for (language, srt) in tracks {
+        let added = match srt {
+            Ok(srt) => {
+                verbose!("Adding {} captions from {}", language, srt.display());
+                let added = uploader
+                    .upload_caption(video_id, &language, &srt)
+                    .await
+                    .map_err(|e| e.to_string());
+                if added.is_ok() {
+                    artifacts.add(&srt);
+                }
+                added
+            }
+            Err(e) => Err(e),
+        };
+        if let Err(e) = &added {
+            warning!(
+                "Could not add {} captions to {}: {}",
+                language,
+                video_file,
+                e
+            );
+        }
+        results.push(CaptionResult {
+            language,
+            error: added.err(),
+        });
+    }
+    results
+}
+
+/// OAuth scope an action's API call needs.
+fn action_scope(action: &Action) -> &'static str {
+    match action {
+        Action::PostComment { .. } => youtube::COMMENT_SCOPE,
+        Action::SetPrivacy { .. } | Action::AddToPlaylist { .. } => youtube::MANAGE_SCOPE,
+    }
+}
+
+/// Runs the queued actions that are due. Failed ones stay queued for the
+/// next run; returns how many failed.
+async fn run_due_actions(
+    args: &Args,
+    oauth_config: &OAuthConfig,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    let mut queue = PendingQueue::load(args.state_dir())?;
+    let due = queue.due(Utc::now());
+    if due.is_empty() {
+        return Ok(0);
+    }
+
+    let mut uploader = YouTubeUploader::with_token_store(oauth_config, token_store(args))
+        .map_err(Failure::auth)?;
+    uploader.set_refresh_margin(args.token_refresh_margin().map_err(Failure::validation)?);
+    for pending in &due {
+        uploader.add_scope(action_scope(&pending.action));
+    }
+    uploader.authenticate().await.map_err(Failure::auth)?;
+    let mut failures = 0;
+    for pending in due {
+        let result = match &pending.action {
+            Action::SetPrivacy { privacy } => {
+                uploader.set_privacy(&pending.video_id, privacy).await
+            }
+            Action::PostComment { text } => uploader.post_comment(&pending.video_id, text).await,
+            Action::AddToPlaylist { playlist_id } => {
+                uploader
+                    .add_to_playlist(playlist_id, &pending.video_id)
+                    .await
+            }
+        };
+        match result {
+            Ok(()) => {
+                success!("✓ {}: {}", pending.file, pending.action);
+                queue.remove(&pending);
+                queue.save(args.state_dir())?;
+            }
+            Err(e) => {
+                error!("✗ {}: could not {}: {}", pending.file, pending.action, e);
+                failures += 1;
+            }
+        }
+    }
+    Ok(failures)
+}
+
+async fn run_pending(args: &Args) -> Result<ExitStatus, Box<dyn std::error::Error>> {
+    journal::init(args.state_dir());
+    let oauth_config = load_oauth_config(args.oauth_config()).map_err(Failure::auth)?;
+    let failures = run_due_actions(args, &oauth_config).await?;
+    let waiting = PendingQueue::load(args.state_dir())?.len() - failures;
+    info!("{} actions waiting for their time", waiting);
+    if failures > 0 {
+        return Ok(ExitStatus::PartialFailure);
+    }
+    Ok(ExitStatus::Success)
+}
+
+/// Downloads the --videos given as URLs before planning, for the options
+/// that read the files to plan. Otherwise they download in the pipeline
+/// while earlier videos upload.
+async fn download_sources(args: &Args) -> Result<(), Box<dyn std::error::Error>> {
+    let plans_from_contents = args.metadata_source() == MetadataSource::Container
+        || args.archive_mode().is_some()
+        || args.chapters() != ChapterSource::Off;
+    if !plans_from_contents {
+        return Ok(());
+    }
+    let cache_dir = ffmpeg::cache_dir(args.state_dir());
+    let client = reqwest::Client::new();
+    for url in args.videos().split(',').map(str::trim) {
+        if download::is_remote(url) {
+            download::download(&client, url, &cache_dir).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Deletes the files in the cache dir not used for `older_than`.
+fn run_clean(args: &Args, older_than: &str) -> Result<ExitStatus, Box<dyn std::error::Error>> {
+    let older_than = parse_duration(older_than)
+        .ok()
+        .and_then(|d| d.to_std().ok())
+        .ok_or_else(|| Failure::validation(format!("Invalid --older-than '{}'", older_than)))?;
+    let cache_dir = ffmpeg::cache_dir(args.state_dir());
+    let summary = spool::clean(&cache_dir, SystemTime::now() - older_than, args.dry_run())?;
+    info!(
+        "{} {} files ({:.1} MB) from {}",
+        if args.dry_run() {
+            "Would remove"
+        } else {
+            "Removed"
+        },
+        summary.files,
+        summary.bytes as f64 / 1e6,
+        cache_dir.display()
+    );
+    Ok(ExitStatus::Success)
+}
+
+/// Reports titles and descriptions the screening flags, failing when it
+/// blocks them.
+fn screen_metadata(
+    screener: &Screener,
+    video_files: &[String],
+    metadata: &[VideoMetadata],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut flagged = 0;
+    for (video_file, metadata) in video_files.iter().zip(metadata) {
+        let findings = screener.screen(metadata.title(), metadata.description())?;
+        for finding in &findings {
+            match screener.action {
+                ScreeningAction::Warn => warning!("{}: {}", video_file, finding),
+                ScreeningAction::Block => error!("{}: {}", video_file, finding),
+            }
+        }
+        if !findings.is_empty() {
+            flagged += 1;
+        }
+    }
+    if flagged > 0 && screener.action == ScreeningAction::Block {
+        return Err(format!(
+            "Screening flagged {} video(s), nothing was uploaded",
+            flagged
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// Videos of `batch` that go public the moment they are uploaded.
+fn immediate_public(batch: &Batch) -> Vec<&str> {
+    batch
+        .video_files
+        .iter()
+        .zip(&batch.metadata)
+        .filter(|(_, metadata)| {
+            metadata.privacy_status == "public" && metadata.scheduled_start_time.is_none()
+        })
+        .map(|(file, _)| file.as_str())
+        .collect()
+}
+
+/// Refuses to upload when the token belongs to another channel than
+/// `expected`, the channel pinned by `profile`.
+async fn check_channel(
+    uploader: &YouTubeUploader,
+    expected: Option<&str>,
+    profile: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(expected) = expected else {
+        return Ok(());
+    };
+    let channel = uploader.my_channel().await.map_err(|e| {
+        Failure::auth(format!(
+            "Could not check the channel pinned by profile '{}': {}",
+            profile, e
+        ))
+    })?;
+    channel_mismatch(&channel, expected, profile).map_or(Ok(()), |e| Err(Failure::auth(e).into()))
+}
+
+/// Why uploading to `channel` is refused, `None` when it is `expected`.
+fn channel_mismatch(channel: &Channel, expected: &str, profile: &str) -> Option<String> {
+    (channel.id != expected).then(|| {
+        format!(
+            "Signed in to channel '{}' ({}), but profile '{}' uploads to {}. \
+             Sign in with the right Google account or change the profile's channel_id",
+            channel.title, channel.id, profile, expected
+        )
+    })
+}
+
+/// Prints which profile, token and channel uploads would use, so a
+/// switched Google account shows before anything is uploaded.
+async fn auth_status(args: &Args) -> Result<ExitStatus, Box<dyn std::error::Error>> {
+    let oauth_config = load_oauth_config(args.oauth_config()).map_err(Failure::auth)?;
+    let mut uploader = YouTubeUploader::with_token_store(&oauth_config, token_store(args))
+        .map_err(Failure::auth)?;
+    uploader.add_scope(youtube::READONLY_SCOPE);
+
+    info!("Profile: {}", args.profile());
+    if let Some(client) = args.client() {
+        info!("Client: {}", client);
+    }
+    let store = match (args.refresh_token(), args.token_file()) {
+        (Some(_), _) => "refresh token from --refresh-token or the environment".to_string(),
+        (None, Some(path)) => format!("file {}", path),
+        (None, None) => format!("{:?}", args.token_store()).to_lowercase(),
+    };
+    info!("Token store: {}", store);
+    if !args.simulate() {
+        let Some(tokens) = uploader.stored_tokens().await.map_err(Failure::auth)? else {
+            warning!("Not authorized yet, the next upload asks for authorization");
+            return Ok(ExitStatus::AuthError);
+        };
+        match tokens.expires_at {
+            Some(expires_at) => info!(
+                "Access token: {} {} ({})",
+                if expires_at > Utc::now() {
+                    "valid until"
+                } else {
+                    "expired"
+                },
+                expires_at.format("%Y-%m-%d %H:%M UTC"),
+                timezone::describe_relative(expires_at, Utc::now())
+            ),
+            None => info!("Access token: expiry unknown, refreshed before use"),
+        }
+        if tokens.refresh_token.is_some() {
+            info!("Refresh token: stored");
+        } else {
+            warning!("No refresh token stored, authorization ends with the access token");
+        }
+    }
+
+    uploader.authenticate().await.map_err(Failure::auth)?;
+    match uploader.granted_scopes().await {
+        Ok(scopes) => {
+            info!("Granted scopes:");
+            for scope in scopes {
+                info!("  {}", scope);
+            }
+        }
+        Err(e) => warning!("Could not look up the granted scopes: {}", e),
+    }
+    let expected = load_config(args.config())?
+        .profile(args.profile())?
+        .channel_id;
+    match uploader.my_channel().await {
+        Ok(channel) => {
+            info!("Channel: {} ({})", channel.title, channel.id);
+            if let Some(problem) = expected
+                .as_deref()
+                .and_then(|expected| channel_mismatch(&channel, expected, args.profile()))
+            {
+                warning!("{}", problem);
+            }
+        }
+        Err(e) => warning!("Could not look up the channel: {}", e),
+    }
+    Ok(ExitStatus::Success)
+}
+
+/// Uploads `size` bytes of filler into a cancelled upload session and
+/// reports the throughput per chunk and overall.
+async fn run_bench(args: &Args, size: &str) -> Result<ExitStatus, Box<dyn std::error::Error>> {
+    let size = parse_size(size).map_err(Failure::validation)?;
+    let chunk_size = args
+        .chunk_options()
+        .map_err(Failure::validation)?
+        .chunk_size;
+    let chunks = size.div_ceil(chunk_size).max(1);
+    journal::init(args.state_dir());
+
+    let oauth_config = load_oauth_config(args.oauth_config()).map_err(Failure::auth)?;
+    let mut uploader = YouTubeUploader::with_token_store(&oauth_config, token_store(args))
+        .map_err(Failure::auth)?;
+    info!("Authenticating with YouTube...");
+    uploader.authenticate().await.map_err(Failure::auth)?;
+
+    info!(
+        "Sending {} MiB in {} chunks...",
+        (chunks * chunk_size) >> 20,
+        chunks
+    );
+    let timings = uploader.bench_upload(chunks, chunk_size).await?;
+
+    let mbit_per_s = |bytes: u64, elapsed: std::time::Duration| {
+        bytes as f64 * 8.0 / 1_000_000.0 / elapsed.as_secs_f64().max(f64::EPSILON)
+    };
+    let mut rates: Vec<f64> = timings
+        .iter()
+        .map(|elapsed| mbit_per_s(chunk_size, *elapsed))
+        .collect();
+    rates.sort_by(f64::total_cmp);
+    let total: std::time::Duration = timings.iter().sum();
+    let average = mbit_per_s(chunks * chunk_size, total);
+
+    success!(
+        "\nAverage {:.1} Mbit/s over {:.1}s",
+        average,
+        total.as_secs_f64()
+    );
+    info!(
+        "Per chunk: slowest {:.1}, median {:.1}, fastest {:.1} Mbit/s",
+        rates[0],
+        rates[rates.len() / 2],
+        rates[rates.len() - 1]
+    );
+    info!(
+        "At this rate a 1 GB video takes about {:.0} minutes",
+        8_000.0 / average / 60.0
+    );
+    Ok(ExitStatus::Success)
+}
+
+/// Reads `file` in upload chunks with every I/O backend and reports the
+/// throughput of each. A first untimed pass warms the page cache so the
+/// backends are compared on equal terms.
+async fn run_disk_bench(args: &Args, file: &str) -> Result<ExitStatus, Box<dyn std::error::Error>> {
+    let options = args.chunk_options().map_err(Failure::validation)?;
+    let total = std::fs::metadata(file)
+        .map_err(|e| Failure::validation(format!("Cannot read {}: {}", file, e)))?
+        .len();
+
+    let read_all = |backend: IoBackend| async move {
+        let started = Instant::now();
+        let mut reader = ChunkReader::start(file, 0, total, options.with_backend(backend));
+        while let Some(chunk) = reader.next().await {
+            chunk?;
+        }
+        Ok::<_, std::io::Error>(started.elapsed())
+    };
+    info!(
+        "Reading {} MiB in chunks of {} MiB, {} ahead...",
+        total >> 20,
+        options.chunk_size >> 20,
+        options.read_ahead
+    );
+    read_all(IoBackend::Buffered).await?;
+    for backend in IoBackend::value_variants() {
+        let elapsed = read_all(*backend).await?;
+        success!(
+            "{:>10}: {:.0} MiB/s",
+            backend.to_possible_value().unwrap().get_name(),
+            total as f64 / (1 << 20) as f64 / elapsed.as_secs_f64().max(f64::EPSILON)
+        );
+    }
+    Ok(ExitStatus::Success)
+}
+
+/// Runs every metadata and schedule check without touching the network and
+/// reports all problems instead of stopping at the first.
+fn run_lint(
+    args: &Args,
+    metadata_path: Option<&str>,
+    videos: &str,
+    interval: Option<&str>,
+    start_time: Option<&str>,
+) -> Result<ExitStatus, Box<dyn std::error::Error>> {
+    let video_files: Vec<String> = videos.split(',').map(|s| s.trim().to_string()).collect();
+    let mut problems = Vec::new();
+
+    let metadata = match metadata_path {
+        Some(path) => match load_video_metadata(path) {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                error!("{}: {}", path, e);
+                return Ok(ExitStatus::ValidationError);
+            }
+        },
+        None => {
+            let profile = load_config(args.config())?.profile(args.profile())?;
+            create_default_metadata(&video_files, None, args.title_case(), &profile)
+        }
+    };
+    if metadata.len() != video_files.len() {
+        problems.push((
+            metadata_path.unwrap_or("metadata").to_string(),
+            format!(
+                "{} metadata entries for {} videos",
+                metadata.len(),
+                video_files.len()
+            ),
+        ));
+    }
+
+    let max_size = parse_size(args.max_size())?;
+    let max_duration = parse_duration(args.max_duration())?;
+    for (video_file, video_metadata) in video_files.iter().zip(metadata.iter()) {
+        for issue in video_metadata.lint() {
+            problems.push((video_file.clone(), issue));
+        }
+        match std::fs::metadata(video_file) {
+            Ok(file) => {
+                for issue in
+                    preflight::check_limits(file.len(), None, max_size, max_duration.num_seconds())
+                {
+                    problems.push((video_file.clone(), issue));
+                }
+            }
+            Err(e) => problems.push((video_file.clone(), format!("cannot read file: {}", e))),
+        }
+    }
+
+    let titles: Vec<&str> = metadata.iter().map(VideoMetadata::title).collect();
+    for (first, duplicate) in lint::duplicate_titles(&titles) {
+        if let (Some(first), Some(duplicate)) = (video_files.get(first), video_files.get(duplicate))
+        {
+            problems.push((
+                duplicate.clone(),
+                format!("title is the same as for {}", first),
+            ));
+        }
+    }
+
+    if let Some(interval) = interval {
+        let schedule = parse_intervals(interval).and_then(|intervals| {
+            let start_time = start_time
+                .map(|s| DateTime::parse_from_rfc3339(s).map(|t| t.with_timezone(&Utc)))
+                .transpose()?;
+            let start = schedule_start(start_time, None)?;
+            fill_schedule_gaps(
+                video_files.len(),
+                &intervals,
+                start,
+                &SlotConstraints::default(),
+                args.timezone().is_some(),
+            )
+        });
+        if let Err(e) = schedule {
+            problems.push(("schedule".to_string(), e.to_string()));
+        }
+    }
+
+    for (subject, issue) in &problems {
+        error!("{}: {}", subject, issue);
+    }
+    if problems.is_empty() {
+        success!("No problems found in {} videos", video_files.len());
+        Ok(ExitStatus::Success)
+    } else {
+        error!("{} problem(s) found", problems.len());
+        Ok(ExitStatus::ValidationError)
+    }
+}
+
+fn show_journal(
+    args: &Args,
+    command: &JournalCommand,
+) -> Result<ExitStatus, Box<dyn std::error::Error>> {
+    let entries = journal::read_entries(args.state_dir())?;
+    let print = |entries: &[journal::JournalEntry], raw: bool| -> serde_json::Result<()> {
+        for entry in entries {
+            if raw {
+                println!("{}", serde_json::to_string(entry)?);
+            } else {
+                println!("{}", entry);
+            }
+        }
+        Ok(())
+    };
+
+    match command {
+        JournalCommand::Show { raw } => print(&entries, *raw)?,
+        JournalCommand::Tail { lines, follow, raw } => {
+            print(&entries[entries.len().saturating_sub(*lines)..], *raw)?;
+            if *follow {
+                journal::follow(args.state_dir(), *raw)?;
+            }
+        }
+    }
+    Ok(ExitStatus::Success)
+}
+
+/// Warns about batch titles already used by one of the channel's recent
+/// uploads. YouTube accepts them, but they confuse viewers.
+async fn warn_channel_duplicates(uploader: &YouTubeUploader, batch: &Batch) {
+    let recent = match uploader.recent_upload_titles(CHANNEL_TITLE_HISTORY).await {
+        Ok(recent) => recent,
+        Err(e) => {
+            warning!(
+                "Could not check channel uploads for duplicate titles: {}",
+                e
+            );
+            return;
+        }
+    };
+    verbose!("Checked titles against {} recent uploads", recent.len());
+    let titles: Vec<&str> = batch.metadata.iter().map(VideoMetadata::title).collect();
+    for i in lint::titles_in(&titles, &recent) {
+        warning!(
+            "{} has the same title as an existing upload: \"{}\"",
+            batch.video_files[i],
+            titles[i]
+        );
+    }
+}
+
+/// What --reupload-rejected needs to upload a video again the way the
+/// batch did.
+struct ReuploadSources<'a> {
+    /// File sent and metadata for each video file
+    files: HashMap<String, (String, VideoMetadata)>,
+    profile: &'a str,
+    captions_config: Option<&'a CaptionsConfig>,
+}
+
+/// Follows YouTube's processing of the uploaded videos until each one is
+/// processed or `limit` passed. Videos that are rejected, or still
+/// processing at the limit or their publish time, are alerted about.
+/// Returns how many were.
+///
+/// With --reupload-rejected, videos failing for a transient reason are
+/// deleted and uploaded again from `sources` as long as their slot is still
+/// ahead.
+async fn watch_processing(
+    args: &Args,
+    uploader: &mut YouTubeUploader,
+    telegram: &Option<TelegramBot>,
+    results: &[UploadResult],
+    sources: &ReuploadSources<'_>,
+    limit: chrono::Duration,
+) -> usize {
+    // Each video with the ID it has now and how often it was re-uploaded
+    let mut watched: Vec<(&UploadResult, String, u32)> = results
+        .iter()
+        .filter_map(|result| match &result.outcome {
+            Outcome::Uploaded { video_id } => Some((result, video_id.clone(), 0)),
+            Outcome::Failed { .. } => None,
+        })
         .collect();
+    if watched.is_empty() {
+        return 0;
+    }
+    let limit_text = format_duration(limit.num_seconds().max(0) as u64);
+    info!(
+        "\nFollowing processing of {} video(s) for up to {}...",
+        watched.len(),
+        limit_text
+    );
+    let deadline = Utc::now() + limit;
+    let mut problems = 0;
+    while !watched.is_empty() {
+        let ids: Vec<String> = watched.iter().map(|(_, id, _)| id.clone()).collect();
+        let states = match uploader.authenticate().await {
+            Ok(()) => uploader.processing_states(&ids).await,
+            Err(e) => Err(e),
+        }
+        .unwrap_or_else(|e| {
+            warning!("Could not check processing: {}", e);
+            HashMap::new()
+        });
+
+        let now = Utc::now();
+        let mut still_processing = Vec::new();
+        for (result, video_id, reuploads) in watched {
+            let problem = match states.get(&video_id) {
+                Some(ProcessingState::Processed) => {
+                    verbose!("{} is processed", result.file);
+                    continue;
+                }
+                Some(ProcessingState::Failed { reason, transient }) => {
+                    let retry = *transient && result.publish_at > now;
+                    if retry && reuploads < args.reupload_rejected() {
+                        match reupload(args, uploader, result, &video_id, reason, sources).await {
+                            Ok(new_id) => {
+                                let notice = format!(
+                                    "↻ {} {}, uploaded again as https://youtu.be/{} ({}/{})",
+                                    result.file,
+                                    reason,
+                                    new_id,
+                                    reuploads + 1,
+                                    args.reupload_rejected()
+                                );
+                                warning!("{}", notice);
+                                if let Some(bot) = telegram {
+                                    bot.notify(&notice).await;
+                                }
+                                still_processing.push((result, new_id, reuploads + 1));
+                                continue;
+                            }
+                            Err(e) => format!("{}, uploading it again failed: {}", reason, e),
+                        }
+                    } else if retry && reuploads > 0 {
+                        format!("{} after {} re-upload(s)", reason, reuploads)
+                    } else {
+                        reason.clone()
+                    }
+                }
+                _ if result.publish_at <= now => "still processing at its publish time".to_string(),
+                _ if now >= deadline => format!("still processing after {}", limit_text),
+                _ => {
+                    still_processing.push((result, video_id, reuploads));
+                    continue;
+                }
+            };
+            problems += 1;
+            journal::record(Event::ProcessingProblem {
+                file: result.file.clone(),
+                video_id: video_id.to_string(),
+                problem: problem.clone(),
+            });
+            let alert = format!(
+                "⚠ {} (https://youtu.be/{}) {}, scheduled for {}",
+                result.file,
+                video_id,
+                problem,
+                result.publish_at.format("%Y-%m-%d %H:%M UTC")
+            );
+            error!("{}", alert);
+            if let Some(bot) = telegram {
+                bot.notify(&alert).await;
+            }
+            if !args.email_to().is_empty() {
+                send_email(args, "YouTube processing problem", &alert).await;
+            }
+        }
+        watched = still_processing;
+        if !watched.is_empty() {
+            let wait = (deadline - now)
+                .to_std()
+                .unwrap_or_default()
+                .min(std::time::Duration::from_secs(watchdog::POLL_INTERVAL_SECS));
+            tokio::time::sleep(wait).await;
+        }
+    }
+    problems
+}
+
+/// Deletes the video `video_id` of `result` and uploads the same file with
+/// the same metadata, and so into the same slot, again. The new video gets
+/// the queued publish actions of the old one and the same post-upload steps
+/// as the batch. Returns the ID of the new video.
+async fn reupload(
+    args: &Args,
+    uploader: &YouTubeUploader,
+    result: &UploadResult,
+    video_id: &str,
+    reason: &str,
+    sources: &ReuploadSources<'_>,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let (upload_file, metadata) = sources
+        .files
+        .get(&result.file)
+        .ok_or("the uploaded file and metadata are unknown")?;
+    if !Path::new(upload_file).exists() {
+        return Err(format!(
+            "{} is gone, keep preprocessed files with --keep-artifacts",
+            upload_file
+        )
+        .into());
+    }
+    info!(
+        "Deleting {} (ID: {}) to upload it again",
+        result.file, video_id
+    );
+    uploader.delete_video(video_id).await?;
+    journal::record(Event::UploadStarted {
+        file: result.file.clone(),
+    });
+    let response = uploader
+        .upload_video(upload_file, metadata, &CancellationToken::new())
+        .await?;
+    journal::record(Event::Reuploaded {
+        file: result.file.clone(),
+        old_video_id: video_id.to_string(),
+        video_id: response.id.clone(),
+        reason: reason.to_string(),
+    });
+    if let Err(e) = PendingQueue::replace_video(args.state_dir(), video_id, &response.id) {
+        warning!(
+            "Could not move the publish actions of {}: {}",
+            result.file,
+            e
+        );
+    }
+    // Replaces the history record of the deleted video
+    let video = ScheduledVideo {
+        profile: sources.profile.to_string(),
+        file: result.file.clone(),
+        video_id: response.id.clone(),
+        publish_at: result.publish_at,
+    };
+    let mut artifacts = Artifacts::new(ffmpeg::cache_dir(args.state_dir()));
+    let mut quota = 0;
+    finish_upload(
+        args,
+        uploader,
+        sources.captions_config,
+        &video,
+        metadata,
+        &mut artifacts,
+        &mut quota,
+    )
+    .await;
+    if !args.keep_artifacts() {
+        artifacts.remove();
+    }
+    Ok(response.id)
+}
+
+/// Emails the per-video results to `--email-to`. Problems sending it are
+/// only warned about, the uploads themselves already happened.
+async fn send_summary_email(args: &Args, results: &[UploadResult]) {
+    let failures = results.iter().filter(|r| !r.is_success()).count();
+    let subject = if failures > 0 {
+        format!("YouTube uploads: {} of {} failed", failures, results.len())
+    } else {
+        format!("YouTube uploads: {} uploaded", results.len())
+    };
+    send_email(args, &subject, &render_summary(results)).await;
+}
+
+/// Emails `body` to `--email-to`, warning when it can't be sent.
+async fn send_email(args: &Args, subject: &str, body: &str) {
+    let smtp = match load_config(args.config()) {
+        Ok(Config {
+            smtp: Some(smtp), ..
+        }) => smtp,
+        Ok(_) => {
+            warning!("--email-to given but no smtp section in the config file");
+            return;
+        }
+        Err(e) => {
+            warning!("Could not send email: {}", e);
+            return;
+        }
+    };
+
+    match email::send_email(&smtp, args.email_to(), subject, body).await {
+        Ok(()) => verbose!("Sent '{}' to {}", subject, args.email_to().join(", ")),
+        Err(e) => warning!("Could not send email: {}", e),
+    }
+}
+
+/// Writes the batch note into `dir`, named after the time the batch ended.
+fn export_note(
+    dir: &str,
+    results: &[UploadResult],
+    finished_at: DateTime<Utc>,
+) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let dir = PathBuf::from(expand_tilde(dir));
+    fs::create_dir_all(&dir)?;
+    let name = format!(
+        "Upload batch {} {}.md",
+        timezone::local_date(finished_at).format("%Y-%m-%d"),
+        timezone::local_time(finished_at).format("%H%M")
+    );
+    let path = dir.join(name);
+    fs::write(&path, render_note(results, finished_at))?;
+    Ok(path)
+}
+
+fn save_named_plan(
+    args: &Args,
+    name: &str,
+    occupied: &[DateTime<Utc>],
+) -> Result<ExitStatus, Box<dyn std::error::Error>> {
+    let batch = prepare_batch(args, BatchSource::Args, occupied).map_err(Failure::validation)?;
+
+    info!("Upload Schedule:");
+    info!("================");
+    print_schedule(&batch.video_files, &batch.schedule, args.calendar());
+
+    let plan = Plan {
+        name: name.to_string(),
+        created_at: Utc::now(),
+        entries: batch
+            .video_files
+            .into_iter()
+            .zip(batch.schedule)
+            .zip(batch.metadata)
+            .map(|((file, publish_at), metadata)| PlanEntry {
+                file,
+                publish_at,
+                metadata,
+            })
+            .collect(),
+    };
+    let path = plan::save_plan(args.state_dir(), &plan)?;
+    // The plan fixed its descriptions, later runs continue after them
+    if let Some(pool) = &batch.description_pool {
+        pool.save_cursor(args.state_dir())?;
+    }
+
+    success!("\nSaved plan '{}' to {}", name, path.display());
+    info!("Upload it with: upload --plan {}", name);
+    Ok(ExitStatus::Success)
+}
+
+/// Plans the batch like a regular run but never loads the OAuth config,
+/// so it works on a machine without credentials.
+async fn save_manifest(args: &Args, path: &str) -> Result<ExitStatus, Box<dyn std::error::Error>> {
+    if args.fill_gaps() {
+        return Err(Failure::validation(
+            "--fill-gaps reads the channel schedule and needs sign-in, it can't go into a manifest",
+        )
+        .into());
+    }
+    let key = args
+        .manifest_key()
+        .map(|path| plan::load_manifest_key(path))
+        .transpose()
+        .map_err(Failure::validation)?;
+    history::init_fingerprint_cache(args.state_dir());
+    download_sources(args).await?;
+    let batch = prepare_batch(args, BatchSource::Args, &[]).map_err(Failure::validation)?;
+
+    info!("Upload Schedule:");
+    info!("================");
+    print_schedule(&batch.video_files, &batch.schedule, args.calendar());
+
+    let mut entries = Vec::new();
+    for ((file, publish_at), metadata) in batch
+        .video_files
+        .into_iter()
+        .zip(batch.schedule)
+        .zip(batch.metadata)
+    {
+        verbose!("Hashing {}...", file);
+        let hash_error = |e| Failure::validation(format!("Failed to hash '{}': {}", file, e));
+        let size = fs::metadata(&file).map_err(hash_error)?.len();
+        let sha256 = plan::file_sha256(&file).map_err(hash_error)?;
+        entries.push(ManifestEntry {
+            file,
+            size,
+            sha256,
+            publish_at,
+            metadata,
+        });
+    }
+    let mut manifest = Manifest {
+        created_at: Utc::now(),
+        entries,
+        signature: None,
+    };
+    if let Some(key) = &key {
+        manifest.sign(key);
+    }
+    plan::save_manifest(path, &manifest)?;
+    if let Some(pool) = &batch.description_pool {
+        pool.save_cursor(args.state_dir())?;
+    }
+
+    if key.is_some() {
+        success!("\nWrote manifest to {}, signed", path);
+        info!(
+            "Upload it with: --manifest-key KEY_FILE upload --manifest {}",
+            path
+        );
+    } else {
+        success!("\nWrote manifest to {}, unsigned", path);
+        info!(
+            "Upload it with: --allow-unsigned-manifest upload --manifest {}",
+            path
+        );
+    }
+    Ok(ExitStatus::Success)
+}
+
+/// Every upload of `batch` as it would be sent, for review by other tools.
+fn resolved_plan(batch: &Batch) -> Vec<ResolvedEntry> {
+    batch
+        .video_files
+        .iter()
+        .zip(&batch.schedule)
+        .zip(&batch.metadata)
+        .map(|((file, publish_at), metadata)| ResolvedEntry {
+            file: file.clone(),
+            publish_at: *publish_at,
+            metadata: metadata.to_api_json(),
+            quota_cost: youtube::UPLOAD_QUOTA_COST
+                + if metadata.has_post_upload_placeholders() {
+                    youtube::UPDATE_QUOTA_COST
+                } else {
+                    0
+                },
+            warnings: metadata.lint(),
+        })
+        .collect()
+}
+
+/// Rebuilds a batch from a saved plan exactly as it was reviewed.
+fn load_named_plan(args: &Args, name: &str) -> Result<Batch, Box<dyn std::error::Error>> {
+    let plan: Plan<VideoMetadata> = plan::load_plan(args.state_dir(), name)?;
+    info!(
+        "Loaded plan '{}' created {}",
+        plan.name,
+        plan.created_at.format("%Y-%m-%d %H:%M UTC")
+    );
+
+    let mut batch = Batch {
+        video_files: Vec::new(),
+        metadata: Vec::new(),
+        schedule: Vec::new(),
+        description_pool: None,
+        profile: args.profile().to_string(),
+    };
+    let history = upload_history(args)?;
+    if let Some(history) = &history {
+        let files: Vec<String> = plan.entries.iter().map(|e| e.file.clone()).collect();
+        history.prefetch(&files, args.hash_jobs());
+    }
+    for entry in plan.entries {
+        if let Some(record) = history.as_ref().and_then(|h| h.find(&entry.file)) {
+            info!(
+                "Skipping {}, already uploaded as {}",
+                entry.file, record.video_id
+            );
+            continue;
+        }
+        batch.video_files.push(entry.file);
+        batch.schedule.push(entry.publish_at);
+        batch.metadata.push(entry.metadata);
+    }
+    Ok(batch)
+}
+
+/// The batch of a manifest as it was planned, without the videos uploaded
+/// since. Fails when a video no longer matches its hash, or the signature
+/// doesn't match with --manifest-key.
+fn load_manifest(args: &Args, path: &str) -> Result<Batch, Box<dyn std::error::Error>> {
+    let manifest: Manifest<VideoMetadata> = plan::load_manifest(path)?;
+    let key = args
+        .manifest_key()
+        .map(|path| plan::load_manifest_key(path))
+        .transpose()?;
+    manifest.check_signature(key.as_deref(), args.allow_unsigned_manifest())?;
+    info!(
+        "Loaded manifest '{}' created {}",
+        path,
+        manifest.created_at.format("%Y-%m-%d %H:%M UTC")
+    );
+    info!(
+        "Checking {} video(s) against the manifest...",
+        manifest.entries.len()
+    );
+    plan::verify_manifest(&manifest)?;
+
+    let mut batch = Batch {
+        video_files: Vec::new(),
+        metadata: Vec::new(),
+        schedule: Vec::new(),
+        description_pool: None,
+        profile: args.profile().to_string(),
+    };
+    let history = upload_history(args)?;
+    if let Some(history) = &history {
+        let files: Vec<String> = manifest.entries.iter().map(|e| e.file.clone()).collect();
+        history.prefetch(&files, args.hash_jobs());
+    }
+    for entry in manifest.entries {
+        if let Some(record) = history.as_ref().and_then(|h| h.find(&entry.file)) {
+            info!(
+                "Skipping {}, already uploaded as {}",
+                entry.file, record.video_id
+            );
+            continue;
+        }
+        batch.video_files.push(entry.file);
+        batch.schedule.push(entry.publish_at);
+        batch.metadata.push(entry.metadata);
+    }
+    Ok(batch)
+}
+
+/// The entries of `batch` picked by `selection`, keeping their slots.
+fn select_entries(batch: Batch, selection: &Selection) -> Batch {
+    fn pick<T>(items: Vec<T>, indices: &[usize]) -> Vec<T> {
+        items
+            .into_iter()
+            .enumerate()
+            .filter(|(i, _)| indices.contains(i))
+            .map(|(_, item)| item)
+            .collect()
+    }
+    let indices = selection.indices(batch.video_files.len());
+    Batch {
+        video_files: pick(batch.video_files, &indices),
+        metadata: pick(batch.metadata, &indices),
+        schedule: pick(batch.schedule, &indices),
+        description_pool: batch.description_pool,
+        profile: batch.profile,
+    }
+}
+
+/// Token store for the uploader. An injected refresh token bypasses the
+/// configured store so nothing is read from or written to disk.
+fn token_store(args: &Args) -> Box<dyn TokenStore> {
+    match (args.refresh_token(), args.token_file()) {
+        (Some(refresh_token), _) => Box::new(MemoryTokenStore::with_refresh_token(&refresh_token)),
+        (None, Some(path)) => Box::new(FileTokenStore::new(path)),
+        (None, None) => args.token_store().store(),
+    }
+}
+
+/// History of earlier uploads, or `None` with --force-all.
+fn upload_history(args: &Args) -> Result<Option<UploadHistory>, Box<dyn std::error::Error>> {
+    if args.force_all() {
+        return Ok(None);
+    }
+    Ok(Some(UploadHistory::load(args.state_dir())?))
+}
+
+/// Publish times already taken on the channel when --fill-gaps is set.
+/// Signs in just for this, since the schedule is needed before the upload
+/// confirmation.
+async fn channel_schedule(args: &Args) -> Result<Vec<DateTime<Utc>>, Box<dyn std::error::Error>> {
+    if !args.fill_gaps() {
+        return Ok(Vec::new());
+    }
+
+    let oauth_config = load_oauth_config(args.oauth_config()).map_err(Failure::auth)?;
+    let mut uploader = YouTubeUploader::with_token_store(&oauth_config, token_store(args))
+        .map_err(Failure::auth)?;
+    uploader.add_scope(youtube::READONLY_SCOPE);
+    info!("Authenticating with YouTube to read the channel schedule...");
+    uploader.authenticate().await.map_err(Failure::auth)?;
+
+    let occupied = uploader
+        .scheduled_publish_times(CHANNEL_SCHEDULE_HISTORY)
+        .await?;
+    verbose!("Found {} videos already scheduled", occupied.len());
+    Ok(occupied)
+}
+
+/// Resolves videos, metadata and schedule without touching the network,
+/// placing videos around the `occupied` publish times.
+fn prepare_batch(
+    args: &Args,
+    source: BatchSource,
+    occupied: &[DateTime<Utc>],
+) -> Result<Batch, Box<dyn std::error::Error>> {
+    let cursor = match source {
+        BatchSource::Args => args.start_after(),
+        BatchSource::Watched { .. } => None,
+    };
+    let (all_files, metadata_path, lead_time_policy, profile_name) = match source {
+        BatchSource::Args => {
+            if args.videos().is_empty() {
+                return Err("--videos is required".into());
+            }
+            let sources: Vec<&str> = args.videos().split(',').map(str::trim).collect();
+            if let Some(min_age) = args.min_file_age() {
+                let local_files: Vec<String> = sources
+                    .iter()
+                    .filter(|source| !download::is_remote(source))
+                    .map(|source| source.to_string())
+                    .collect();
+                check_files_stable(&local_files, parse_duration(min_age)?)?;
+            }
+            // URLs stand for the file they download to, fetched while
+            // earlier videos upload
+            let cache_dir = ffmpeg::cache_dir(args.state_dir());
+            let all_files = sources
+                .into_iter()
+                .map(|source| match source {
+                    url if download::is_remote(url) => Ok(download::register(url, &cache_dir)?
+                        .to_string_lossy()
+                        .into_owned()),
+                    path => Ok(path.to_string()),
+                })
+                .collect::<Result<Vec<String>, Box<dyn std::error::Error>>>()?;
+            (
+                all_files,
+                args.metadata(),
+                args.lead_time_policy(),
+                args.profile().to_string(),
+            )
+        }
+        // New files keep arriving, so a slot that became too close while
+        // waiting moves on instead of stopping the watch
+        BatchSource::Watched { files, profile } => (files, None, LeadTimePolicy::Shift, profile),
+    };
+    let config = load_config(args.config())?;
+    let profile = config.profile(&profile_name)?;
+    let template = schedule_template(args)?;
+    // --interval wins over the profile's cadence, imported and template
+    // slots need none
+    let intervals = match (args.interval().is_empty(), &profile.interval) {
+        _ if args.schedule_from().is_some() || template.is_some() => Vec::new(),
+        (false, _) => parse_intervals(args.interval())?,
+        (true, Some(interval)) => parse_intervals(interval)?,
+        (true, None) => {
+            return Err(format!(
+                "--interval is required unless profile '{}' sets an interval",
+                profile_name
+            )
+            .into())
+        }
+    };
 
-    let interval_str = args.interval();
-    let interval = parse_duration(interval_str)?;
+    // Earlier runs walked the list up to --start-after
+    let start = match cursor {
+        Some(cursor) => history::position_after(&all_files, cursor).ok_or_else(|| {
+            format!(
+                "--start-after {}: no video matches this path or fingerprint",
+                cursor
+            )
+        })?,
+        None => 0,
+    };
+    if start > 0 {
+        info!("Starting after {}", all_files[start - 1]);
+    }
 
-    let oauth_config_path = args.oauth_config();
-    let oauth_config = load_oauth_config(oauth_config_path)?;
+    // Skip videos a previous run uploaded, so re-running after a partial
+    // failure only processes the remainder
+    let history = upload_history(args)?;
+    if let Some(history) = &history {
+        history.prefetch(&all_files[start..], args.hash_jobs());
+    }
+    let skipped: Vec<bool> = all_files
+        .iter()
+        .enumerate()
+        .map(|(i, video_file)| {
+            if i < start {
+                return true;
+            }
+            match history.as_ref().and_then(|h| h.find(video_file)) {
+                Some(record) => {
+                    info!(
+                        "Skipping {}, already uploaded as {}",
+                        video_file, record.video_id
+                    );
+                    true
+                }
+                None => false,
+            }
+        })
+        .collect();
+    let video_files: Vec<String> = all_files
+        .into_iter()
+        .zip(&skipped)
+        .filter(|(_, skipped)| !**skipped)
+        .map(|(video_file, _)| video_file)
+        .collect();
 
     let start_time = if let Some(start_str) = args.start_time() {
         Some(DateTime::parse_from_rfc3339(start_str)?.with_timezone(&Utc))
@@ -28,80 +2464,611 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         None
     };
 
-    let timestamp_file = args.timestamp_file();
-    let dry_run = args.dry_run();
+    // Random draws would differ between the dry run and the upload
+    if args.require_approved_plan().is_some()
+        && args.description_pool().is_some()
+        && args.description_order() != PoolOrder::Sequential
+    {
+        return Err(
+            "--require-approved-plan needs --description-order sequential for the description pool"
+                .into(),
+        );
+    }
+    let mut description_pool = args
+        .description_pool()
+        .map(|path| DescriptionPool::load(path, args.description_order(), args.state_dir()))
+        .transpose()?;
 
     // Load or create metadata
-    let mut metadata = if let Some(metadata_path) = args.metadata() {
+    let mut metadata = if let Some(metadata_path) = metadata_path {
+        // Entries match videos by position, so drop those of skipped videos
         load_video_metadata(metadata_path)?
+            .into_iter()
+            .enumerate()
+            .filter(|(i, _)| !skipped.get(*i).copied().unwrap_or(false))
+            .map(|(_, video_metadata)| video_metadata)
+            .collect()
     } else {
-        create_default_metadata(&video_files, args.description_file())
+        let mut metadata = create_default_metadata(
+            &video_files,
+            description_pool.as_mut(),
+            args.title_case(),
+            &profile,
+        );
+        if args.metadata_source() == MetadataSource::Container {
+            for (video_file, video_metadata) in video_files.iter().zip(metadata.iter_mut()) {
+                video_metadata.apply_container_tags(&ffprobe::probe_tags(video_file)?);
+            }
+        }
+        metadata
     };
 
-    // Generate schedule
-    let schedule = generate_schedule(
-        video_files.len(),
-        interval,
-        start_time,
-        timestamp_file.map(|s| s.as_str()),
+    // Audience and license are compliance settings, so the profile's count
+    // for metadata file entries as well, and the command line beats both
+    for video_metadata in metadata.iter_mut() {
+        video_metadata.apply_profile_settings(&profile);
+        video_metadata.override_settings(args.made_for_kids(), args.license(), args.category());
+    }
+
+    if let Some(mode) = args.archive_mode() {
+        for (video_file, video_metadata) in video_files.iter().zip(metadata.iter_mut()) {
+            video_metadata.apply_archive_date(ffprobe::original_date(video_file)?, mode);
+        }
+    }
+
+    if args.chapters() != ChapterSource::Off {
+        let intro_seconds = match args.preprocess_options().prepend {
+            Some(intro) => ffprobe::probe_duration(&intro)?,
+            None => 0.0,
+        };
+        for (video_file, video_metadata) in video_files.iter().zip(metadata.iter_mut()) {
+            let chapters = ffprobe::load_chapters(video_file, args.chapters())?;
+            if !chapters.is_empty() {
+                let chapters = ffprobe::offset_chapters(chapters, intro_seconds);
+                video_metadata.append_description(&ffprobe::format_chapters(&chapters));
+            }
+        }
+    }
+
+    if !profile.links.is_empty() {
+        for (video_file, video_metadata) in video_files.iter().zip(metadata.iter_mut()) {
+            let stem = Path::new(video_file).file_stem().unwrap_or_default();
+            let video_slug = links::slugify(&stem.to_string_lossy());
+            video_metadata.append_description(&links::link_block(
+                &profile.links,
+                profile.utm.as_ref(),
+                &video_slug,
+            )?);
+        }
+    }
+
+    // Episodes without a number continue after the last uploaded one
+    if metadata.iter().any(|m| m.series().is_some()) {
+        let index = SeriesIndex::load(args.state_dir())?;
+        let mut next_numbers: HashMap<String, u32> = HashMap::new();
+        for video_metadata in metadata.iter_mut() {
+            let Some(series) = video_metadata.series().map(str::to_string) else {
+                continue;
+            };
+            let number = video_metadata.episode().unwrap_or_else(|| {
+                let next = next_numbers
+                    .entry(series.clone())
+                    .or_insert_with(|| index.get(&series).next_number());
+                *next += 1;
+                *next - 1
+            });
+            video_metadata.number_episode(number);
+        }
+    }
+
+    // Variants go by position in the whole list, so a re-run after some
+    // uploads gives the remaining videos the same suffixes
+    if !profile.title_suffixes.is_empty() {
+        let positions = skipped
+            .iter()
+            .enumerate()
+            .filter(|(_, skipped)| !**skipped)
+            .map(|(position, _)| position);
+        for (position, video_metadata) in positions.zip(metadata.iter_mut()) {
+            let suffixes = &profile.title_suffixes;
+            video_metadata.apply_title_variant(&suffixes[position % suffixes.len()]);
+        }
+    }
+
+    for video_metadata in metadata.iter_mut() {
+        video_metadata.sanitize();
+    }
+    // Thumbnails YouTube would reject are fixed now rather than failing
+    // after their video uploaded
+    for video_metadata in metadata.iter_mut() {
+        if let Some(image) = video_metadata.thumbnail() {
+            let fitted =
+                thumbnail::prepare(Path::new(image), &ffmpeg::cache_dir(args.state_dir()))?;
+            video_metadata.set_thumbnail_file(&fitted.to_string_lossy());
+        }
+    }
+    let titles: Vec<&str> = metadata.iter().map(VideoMetadata::title).collect();
+    for (first, duplicate) in lint::duplicate_titles(&titles) {
+        if let (Some(first_file), Some(duplicate_file)) =
+            (video_files.get(first), video_files.get(duplicate))
+        {
+            warning!(
+                "{} and {} have the same title \"{}\"",
+                first_file,
+                duplicate_file,
+                titles[duplicate]
+            );
+        }
+    }
+    if let Some(screening) = &config.screening {
+        screen_metadata(&Screener::load(screening)?, &video_files, &metadata)?;
+    }
+
+    check_upload_limits(
+        &video_files,
+        parse_size(args.max_size())?,
+        parse_duration(args.max_duration())?,
     )?;
+    run_preflight(args.preflight(), &video_files, &metadata)?;
+
+    // Generate schedule
+    // Uploads outside the window wait, which the publish times have to allow
+    let upload_start = match args.upload_window()? {
+        Some(window) => window.next_open(Utc::now()),
+        None => Utc::now(),
+    };
+    let earliest = upload_start + parse_duration(args.min_lead_time())?;
+    let (other_profiles, min_spacing) = match args.min_spacing() {
+        Some(spacing) => (
+            SharedSchedule::load(args.state_dir())?.other_profiles(&profile_name),
+            parse_duration(spacing)?,
+        ),
+        None => (Vec::new(), chrono::Duration::zero()),
+    };
+    let constraints = SlotConstraints {
+        occupied: occupied.to_vec(),
+        skip_dates: match args
+            .skip_dates()
+            .or(template.as_ref().and_then(|t| t.skip_dates.as_ref()))
+        {
+            Some(path) => SkipDates::load(path)?,
+            None => SkipDates::default(),
+        },
+        not_before: (lead_time_policy == LeadTimePolicy::Shift).then_some(earliest),
+        other_profiles,
+        min_spacing,
+    };
+    // The upload keeps the slots of the approved dry run
+    let approved_plan = args.require_approved_plan().filter(|_| !args.dry_run());
+    let schedule = match (approved_plan, args.schedule_from(), &template) {
+        (Some(path), _, _) => plan::approved_slots(path, &video_files)?,
+        (None, Some(path), _) => imported_schedule(
+            video_files.len(),
+            slot_file::load(path)?,
+            constraints.not_before.unwrap_or_else(Utc::now),
+            &constraints.occupied,
+        )
+        .map_err(|e| format!("--schedule-from {}: {}", path, e))?,
+        (None, None, Some(template)) => weekly_schedule(
+            video_files.len(),
+            &template.days,
+            &template.times()?,
+            schedule_start(start_time, args.timestamp_file().map(|s| s.as_str()))?,
+            &constraints,
+        )?,
+        (None, None, None) => {
+            let start = schedule_start(start_time, args.timestamp_file().map(|s| s.as_str()))?;
+            let schedule = fill_schedule_gaps(
+                video_files.len(),
+                &intervals,
+                start,
+                &constraints,
+                args.timezone().is_some(),
+            )?;
+            if schedule.first().is_some_and(|first| *first != start) && start < earliest {
+                warning!(
+                    "start time is past or less than {} away, schedule moved to the next free slots",
+                    args.min_lead_time()
+                );
+            }
+            schedule
+        }
+    };
+    check_lead_time(&video_files, &schedule, earliest)?;
+    if let Some(horizon) = args.max_horizon() {
+        let latest = Utc::now()
+            + parse_duration(horizon).map_err(|e| format!("Invalid --max-horizon: {}", e))?;
+        check_horizon(&schedule, latest, horizon)?;
+    }
 
     // Apply schedule to metadata
     for (i, scheduled_time) in schedule.iter().enumerate() {
         if i < metadata.len() {
-            metadata[i].scheduled_start_time = Some(scheduled_time.to_rfc3339());
+            // publishAt always makes videos public, other privacy changes
+            // are queued after the upload
+            metadata[i].scheduled_start_time =
+                (args.publish_as() == PublishPrivacy::Public).then(|| scheduled_time.to_rfc3339());
             metadata[i].privacy_status = "private".to_string(); // Set to private for scheduling
+            metadata[i].resolve_schedule(*scheduled_time);
         }
     }
 
-    //Display schedule
-    println!("Upload Schedule:");
-    println!("================");
-    print_schedule(&video_files, &schedule);
+    Ok(Batch {
+        video_files,
+        metadata,
+        schedule,
+        description_pool,
+        profile: profile_name,
+    })
+}
 
-    if dry_run {
-        println!("\nDry run complete. No videos were uploaded.");
+/// Makes videos with a publish time private, since YouTube ignores
+/// publishAt otherwise, and reports videos that stay private for good.
+fn check_publish_privacy(
+    video_files: &[String],
+    metadata: &mut [VideoMetadata],
+    queued_privacy: bool,
+) -> Vec<String> {
+    let mut problems = Vec::new();
+    for (video_file, metadata) in video_files.iter().zip(metadata) {
+        if let Some(fix) = metadata.fix_publish_privacy() {
+            problems.push(format!("{}: {}", video_file, fix));
+        } else if metadata.scheduled_start_time.is_none()
+            && metadata.privacy_status == "private"
+            && !queued_privacy
+        {
+            problems.push(format!(
+                "{}: private without scheduled_start_time, the video never goes live",
+                video_file
+            ));
+        }
+    }
+    problems
+}
+
+/// The schedule template picked with --schedule-template, if any.
+fn schedule_template(args: &Args) -> Result<Option<ScheduleTemplate>, Box<dyn std::error::Error>> {
+    args.schedule_template()
+        .map(|name| load_config(args.config())?.schedule_template(name))
+        .transpose()
+}
+
+/// Refuses videos that were modified less than `min_age` ago or are still
+/// being written, e.g. by an export that hasn't finished.
+fn check_files_stable(
+    video_files: &[String],
+    min_age: chrono::Duration,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let min_age = min_age.to_std()?;
+    let unstable: Vec<String> = video_files
+        .iter()
+        .filter_map(
+            |video_file| match watch::unstable_reason(video_file, min_age) {
+                Ok(Some(reason)) => Some(format!("{} ({})", video_file, reason)),
+                Ok(None) => None,
+                Err(e) => Some(format!("{} ({})", video_file, e)),
+            },
+        )
+        .collect();
+    if unstable.is_empty() {
         return Ok(());
     }
+    Err(format!(
+        "Not uploading files that may still be written: {}",
+        unstable.join(", ")
+    )
+    .into())
+}
 
-    // Confirm before proceeding
-    println!("\nProceed with upload? (y/N): ");
-    let mut input = String::new();
-    std::io::stdin().read_line(&mut input)?;
-    if !input.trim().to_lowercase().starts_with('y') {
-        println!("Upload cancelled.");
+/// YouTube rejects publish times in the past or only minutes away, so the
+/// whole batch is refused before the first upload would fail.
+fn check_lead_time(
+    video_files: &[String],
+    schedule: &[DateTime<Utc>],
+    earliest: DateTime<Utc>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let too_soon: Vec<String> = video_files
+        .iter()
+        .zip(schedule)
+        .filter(|(_, publish_at)| **publish_at < earliest)
+        .map(|(video_file, publish_at)| {
+            format!(
+                "{} ({})",
+                video_file,
+                publish_at.format("%Y-%m-%d %H:%M UTC")
+            )
+        })
+        .collect();
+    if too_soon.is_empty() {
         return Ok(());
     }
+    Err(format!(
+        "Publish time too soon for {}. Choose a later --start-time or pass --lead-time-policy shift",
+        too_soon.join(", ")
+    )
+    .into())
+}
 
-    // Create uploader and authenticate
-    let mut uploader = YouTubeUploader::new(&oauth_config)?;
+/// Refuses schedules running past `latest`, saying how many videos fit.
+fn check_horizon(
+    schedule: &[DateTime<Utc>],
+    latest: DateTime<Utc>,
+    horizon: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let fitting = schedule.iter().filter(|t| **t <= latest).count();
+    let Some(last) = schedule.last().filter(|_| fitting < schedule.len()) else {
+        return Ok(());
+    };
+    Err(format!(
+        "The schedule runs until {}, past the --max-horizon of {} ({}). {} of {} videos fit, shorten --interval or upload fewer videos with --limit",
+        last.format("%Y-%m-%d %H:%M UTC"),
+        horizon,
+        latest.format("%Y-%m-%d"),
+        fitting,
+        schedule.len()
+    )
+    .into())
+}
 
-    if !dry_run {
-        println!("Authenticating with YouTube...");
-        uploader.authenticate().await?;
+fn print_schedule_diff(changes: &[ScheduleChange]) {
+    if changes.is_empty() {
+        info!("\nSchedule unchanged since the previous run.");
+        return;
     }
-    // Upload videos
-    println!("\nUploading videos...");
-    for (i, (video_file, video_metadata)) in video_files.iter().zip(metadata.iter()).enumerate() {
-		if (i+1) % 8 == 0 {
-		println!("refreshing token");
-        uploader.authenticate().await?;
-		}
-        println!("Uploading {} ({}/{})", video_file, i + 1, video_files.len());
-
-        match uploader.upload_video(video_file, video_metadata).await {
-            Ok(response) => {
-                println!(
-                    "✓ Successfully uploaded: {} (ID: {})",
-                    video_file, response.id
-                );
-            }
-            Err(e) => {
-                eprintln!("✗ Failed to upload {}: {}", video_file, e);
-            }
+    info!("\nChanges since the previous run:");
+    for change in changes {
+        info!("  {}", change);
+    }
+}
+
+/// Refuses the whole batch when any video is over the size or duration
+/// limit, before any bandwidth is spent on it.
+fn check_upload_limits(
+    video_files: &[String],
+    max_size: u64,
+    max_duration: chrono::Duration,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut problems = 0;
+
+    for video_file in video_files.iter().filter(|f| !is_pending_download(f)) {
+        for issue in upload_limit_problems(video_file, max_size, max_duration)? {
+            error!("{}: {}", video_file, issue);
+            problems += 1;
+        }
+    }
+
+    if problems > 0 {
+        return Err(format!("{} video(s) exceed upload limits, aborting", problems).into());
+    }
+    Ok(())
+}
+
+/// Checks every video's format with ffprobe, returning an error in fail
+/// mode when any check flagged a problem.
+fn run_preflight(
+    mode: PreflightMode,
+    video_files: &[String],
+    metadata: &[VideoMetadata],
+) -> Result<(), Box<dyn std::error::Error>> {
+    if mode == PreflightMode::Off {
+        return Ok(());
+    }
+    if !ffprobe::ffprobe_available() {
+        if mode == PreflightMode::Fail {
+            return Err("--preflight fail requires ffprobe in PATH".into());
+        }
+        warning!("ffprobe not found, skipping preflight checks");
+        return Ok(());
+    }
+
+    let mut problems = 0;
+    for (video_file, video_metadata) in video_files.iter().zip(metadata) {
+        if is_pending_download(video_file) {
+            continue;
+        }
+        for issue in preflight_problems(video_file, video_metadata.is_short()) {
+            warning!("{}: {}", video_file, issue);
+            problems += 1;
         }
     }
 
-    println!("\nUpload process completed!");
+    if mode == PreflightMode::Fail && problems > 0 {
+        return Err(format!("Preflight found {} problem(s), aborting", problems).into());
+    }
     Ok(())
 }
+
+/// Size and duration limits `video_file` exceeds.
+fn upload_limit_problems(
+    video_file: &str,
+    max_size: u64,
+    max_duration: chrono::Duration,
+) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let size = std::fs::metadata(video_file)
+        .map_err(|e| format!("Cannot read '{}': {}", video_file, e))?
+        .len();
+    let duration = if ffprobe::ffprobe_available() {
+        ffprobe::probe_duration(video_file).ok()
+    } else {
+        None
+    };
+    Ok(preflight::check_limits(
+        size,
+        duration,
+        max_size,
+        max_duration.num_seconds(),
+    ))
+}
+
+/// Format problems ffprobe finds in `video_file`. Audio files have no
+/// video stream to check.
+fn preflight_problems(video_file: &str, is_short: bool) -> Vec<String> {
+    if ffmpeg::is_audio_file(video_file) {
+        return Vec::new();
+    }
+    match ffprobe::probe_video_info(video_file) {
+        Ok(info) => preflight::check_video_format(&info, is_short),
+        Err(e) => {
+            warning!("skipping preflight for {}: {}", video_file, e);
+            Vec::new()
+        }
+    }
+}
+
+/// Whether `video_file` stands for a URL that is not downloaded yet. The
+/// pipeline checks it against the limits once it is.
+fn is_pending_download(video_file: &str) -> bool {
+    let path = Path::new(video_file);
+    !path.exists() && download::source_of(path).is_some()
+}
+
+/// The checks planning leaves to the pipeline for videos given as URLs.
+#[derive(Debug, Clone, Copy)]
+struct DownloadChecks {
+    max_size: u64,
+    max_duration: chrono::Duration,
+    preflight: PreflightMode,
+}
+
+impl DownloadChecks {
+    fn from_args(args: &Args) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(Self {
+            max_size: parse_size(args.max_size())?,
+            max_duration: parse_duration(args.max_duration())?,
+            preflight: args.preflight(),
+        })
+    }
+
+    /// Fails the downloaded `video_file` the way planning fails a local
+    /// file: over the limits, or with format problems in fail mode.
+    fn run(&self, video_file: &str, is_short: bool) -> Result<(), String> {
+        let problems = upload_limit_problems(video_file, self.max_size, self.max_duration)
+            .map_err(|e| e.to_string())?;
+        if !problems.is_empty() {
+            return Err(problems.join(", "));
+        }
+        // Planning already refused fail mode without ffprobe
+        if self.preflight == PreflightMode::Off || !ffprobe::ffprobe_available() {
+            return Ok(());
+        }
+        let problems = preflight_problems(video_file, is_short);
+        for issue in &problems {
+            warning!("{}: {}", video_file, issue);
+        }
+        if self.preflight == PreflightMode::Fail && !problems.is_empty() {
+            return Err(format!("preflight found {} problem(s)", problems.len()));
+        }
+        Ok(())
+    }
+}
+
+/// How many of each pipeline stage run at a time.
+#[derive(Debug, Clone, Copy)]
+struct StageJobs {
+    download: usize,
+    preprocess: usize,
+}
+
+/// A video through the pipeline: the path to upload, and the place it
+/// holds in the pipeline until it has been uploaded.
+type Prepared = (Result<String, String>, Option<OwnedSemaphorePermit>);
+
+/// Starts downloading and preprocessing the videos in batch order, so the
+/// next videos download and encode while the current one uploads. Each
+/// stage runs its own number of jobs, and at most one video per job waits
+/// ahead of the uploads, so the batch never sits on disk all at once.
+/// Downloads go through `checks` before they are preprocessed.
+fn spawn_pipeline(
+    video_files: &[String],
+    metadata: &[VideoMetadata],
+    options: PreprocessOptions,
+    cache_dir: PathBuf,
+    jobs: StageJobs,
+    checks: DownloadChecks,
+) -> Vec<oneshot::Receiver<Prepared>> {
+    let options = Arc::new(options);
+    let downloads = Arc::new(Semaphore::new(jobs.download));
+    let encodes = Arc::new(Semaphore::new(jobs.preprocess));
+    // One more for the video uploading
+    let lookahead = Arc::new(Semaphore::new(jobs.download + jobs.preprocess + 1));
+    let client = reqwest::Client::new();
+
+    let (senders, receivers): (Vec<_>, Vec<_>) =
+        video_files.iter().map(|_| oneshot::channel()).unzip();
+    let video_files = video_files.to_vec();
+    let shorts: Vec<bool> = metadata.iter().map(VideoMetadata::is_short).collect();
+    tokio::spawn(async move {
+        for ((video_file, is_short), sender) in video_files.into_iter().zip(shorts).zip(senders) {
+            let source = download::source_of(Path::new(&video_file));
+            if source.is_none() && !options.applies_to(&video_file) {
+                let _ = sender.send((Ok(video_file), None));
+                continue;
+            }
+            // Waits for an earlier video to upload. Videos of a batch that
+            // stopped early are skipped.
+            let Ok(place) = Arc::clone(&lookahead).acquire_owned().await else {
+                return;
+            };
+            if sender.is_closed() {
+                continue;
+            }
+            let stage = PipelineStage {
+                options: Arc::clone(&options),
+                cache_dir: cache_dir.clone(),
+                downloads: Arc::clone(&downloads),
+                encodes: Arc::clone(&encodes),
+                client: client.clone(),
+                checks,
+                is_short,
+            };
+            tokio::spawn(async move {
+                let result = stage.prepare(video_file, source).await;
+                let _ = sender.send((result, Some(place)));
+            });
+        }
+    });
+    receivers
+}
+
+/// What a video needs on its way through the pipeline.
+struct PipelineStage {
+    options: Arc<PreprocessOptions>,
+    cache_dir: PathBuf,
+    downloads: Arc<Semaphore>,
+    encodes: Arc<Semaphore>,
+    client: reqwest::Client,
+    checks: DownloadChecks,
+    is_short: bool,
+}
+
+impl PipelineStage {
+    /// Downloads `video_file` from `source` when it is a URL and checks
+    /// it, then runs the preprocessing steps that apply.
+    async fn prepare(self, video_file: String, source: Option<String>) -> Result<String, String> {
+        if let Some(source) = source {
+            let _permit = self.downloads.acquire().await.map_err(|e| e.to_string())?;
+            download::download(&self.client, &source, &self.cache_dir)
+                .await
+                .map_err(|e| e.to_string())?;
+            let (checks, is_short, file) = (self.checks, self.is_short, video_file.clone());
+            tokio::task::spawn_blocking(move || checks.run(&file, is_short))
+                .await
+                .map_err(|e| e.to_string())??;
+        }
+        if !self.options.applies_to(&video_file) {
+            return Ok(video_file);
+        }
+        let _permit = self
+            .encodes
+            .acquire_owned()
+            .await
+            .map_err(|e| e.to_string())?;
+        tokio::task::spawn_blocking(move || {
+            ffmpeg::preprocess(&video_file, &self.options, &self.cache_dir)
+                .map(|path| path.to_string_lossy().to_string())
+                .map_err(|e| e.to_string())
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+}
@@ -0,0 +1,203 @@
+use clap::ValueEnum;
+use serde::Serialize;
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+
+use crate::redact;
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorChoice {
+    /// Color when writing to a terminal and NO_COLOR is unset
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Human-readable messages
+    Text,
+    /// One JSON event per line on stdout, messages move to stderr
+    Json,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    /// Errors only
+    Quiet = 0,
+    Normal = 1,
+    Verbose = 2,
+    Debug = 3,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Style {
+    Error,
+    Warning,
+    Success,
+    Dim,
+}
+
+static VERBOSITY: AtomicU8 = AtomicU8::new(Verbosity::Normal as u8);
+static COLOR_STDOUT: AtomicBool = AtomicBool::new(false);
+static COLOR_STDERR: AtomicBool = AtomicBool::new(false);
+static JSON: AtomicBool = AtomicBool::new(false);
+
+/// Configures all output for the rest of the process. Call once at startup.
+pub fn init(verbosity: Verbosity, color: ColorChoice, format: OutputFormat) {
+    VERBOSITY.store(verbosity as u8, Ordering::Relaxed);
+    JSON.store(format == OutputFormat::Json, Ordering::Relaxed);
+
+    let (stdout, stderr) = match color {
+        ColorChoice::Always => (true, true),
+        ColorChoice::Never => (false, false),
+        ColorChoice::Auto => {
+            let no_color = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+            (
+                !no_color && std::io::stdout().is_terminal(),
+                !no_color && std::io::stderr().is_terminal(),
+            )
+        }
+    };
+    COLOR_STDOUT.store(stdout, Ordering::Relaxed);
+    COLOR_STDERR.store(stderr, Ordering::Relaxed);
+}
+
+pub fn enabled(level: Verbosity) -> bool {
+    VERBOSITY.load(Ordering::Relaxed) >= level as u8
+}
+
+/// Whether stdout is reserved for JSON events.
+pub fn json() -> bool {
+    JSON.load(Ordering::Relaxed)
+}
+
+/// Writes `event` as a line of JSON on stdout in `--output json`, otherwise
+/// does nothing.
+pub fn emit(event: &impl Serialize) {
+    if !json() {
+        return;
+    }
+    match serde_json::to_string(event) {
+        Ok(line) => println!("{}", redact::redact(&line)),
+        Err(e) => crate::debug!("Could not serialize event: {}", e),
+    }
+}
+
+/// Prints a message meant for humans: on stdout, or on stderr when stdout
+/// carries JSON events.
+pub fn print(message: &str) {
+    let message = redact::redact(message);
+    if json() {
+        eprintln!("{}", message);
+    } else {
+        println!("{}", message);
+    }
+}
+
+/// Wraps `text` in ANSI color codes when color is enabled for the stream.
+pub fn paint(text: &str, style: Style, stderr: bool) -> String {
+    let colored = if stderr {
+        COLOR_STDERR.load(Ordering::Relaxed)
+    } else {
+        COLOR_STDOUT.load(Ordering::Relaxed)
+    };
+    if !colored {
+        return text.to_string();
+    }
+
+    let code = match style {
+        Style::Error => "31",
+        Style::Warning => "33",
+        Style::Success => "32",
+        Style::Dim => "2",
+    };
+    format!("\x1b[{}m{}\x1b[0m", code, text)
+}
+
+/// Regular progress output on stdout, hidden by `--quiet`.
+#[macro_export]
+macro_rules! info {
+    ($($arg:tt)*) => {
+        if $crate::output::enabled($crate::output::Verbosity::Normal) {
+            $crate::output::print(&format!($($arg)*));
+        }
+    };
+}
+
+/// Like `info!`, highlighted as a successful outcome.
+#[macro_export]
+macro_rules! success {
+    ($($arg:tt)*) => {
+        if $crate::output::enabled($crate::output::Verbosity::Normal) {
+            $crate::output::print(&$crate::output::paint(
+                &format!($($arg)*),
+                $crate::output::Style::Success,
+                $crate::output::json(),
+            ));
+        }
+    };
+}
+
+/// Non-fatal problems on stderr, prefixed with `WARNING:`.
+#[macro_export]
+macro_rules! warning {
+    ($($arg:tt)*) => {
+        if $crate::output::enabled($crate::output::Verbosity::Normal) {
+            eprintln!(
+                "{}",
+                $crate::output::paint(
+                    &$crate::redact::redact(&format!("WARNING: {}", format!($($arg)*))),
+                    $crate::output::Style::Warning,
+                    true
+                )
+            );
+        }
+    };
+}
+
+/// Errors on stderr, shown at every verbosity.
+#[macro_export]
+macro_rules! error {
+    ($($arg:tt)*) => {
+        eprintln!(
+            "{}",
+            $crate::output::paint(
+                &$crate::redact::redact(&format!($($arg)*)),
+                $crate::output::Style::Error,
+                true
+            )
+        );
+    };
+}
+
+/// Extra detail shown with `--verbose`.
+#[macro_export]
+macro_rules! verbose {
+    ($($arg:tt)*) => {
+        if $crate::output::enabled($crate::output::Verbosity::Verbose) {
+            $crate::output::print(&$crate::output::paint(
+                &format!($($arg)*),
+                $crate::output::Style::Dim,
+                $crate::output::json(),
+            ));
+        }
+    };
+}
+
+/// Diagnostics on stderr shown with `--verbose --verbose`.
+#[macro_export]
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        if $crate::output::enabled($crate::output::Verbosity::Debug) {
+            eprintln!(
+                "{}",
+                $crate::output::paint(
+                    &$crate::redact::redact(&format!("debug: {}", format!($($arg)*))),
+                    $crate::output::Style::Dim,
+                    true
+                )
+            );
+        }
+    };
+}
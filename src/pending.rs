@@ -0,0 +1,161 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::expand_tilde;
+
+const PENDING_FILE: &str = "pending.json";
+
+/// Something to do to an uploaded video that YouTube cannot schedule by
+/// itself.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Action {
+    /// Change the privacy status with videos.update
+    SetPrivacy {
+        privacy: String,
+    },
+    /// Post a top-level comment. The API has no way to pin it
+    PostComment {
+        text: String,
+    },
+    AddToPlaylist {
+        playlist_id: String,
+    },
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Action::SetPrivacy { privacy } => write!(f, "make {}", privacy),
+            Action::PostComment { .. } => write!(f, "post comment"),
+            Action::AddToPlaylist { playlist_id } => write!(f, "add to playlist {}", playlist_id),
+        }
+    }
+}
+
+/// An action run by `run-pending` or watch mode once `at` has passed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PendingAction {
+    pub video_id: String,
+    pub file: String,
+    pub at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub action: Action,
+}
+
+/// Queue file written before actions other than privacy changes existed.
+#[derive(Debug, Deserialize)]
+struct LegacyPrivacy {
+    video_id: String,
+    file: String,
+    at: DateTime<Utc>,
+    privacy: String,
+}
+
+/// Actions waiting in the state dir for their time.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PendingQueue {
+    #[serde(default)]
+    actions: Vec<PendingAction>,
+    #[serde(default, skip_serializing)]
+    privacy: Vec<LegacyPrivacy>,
+}
+
+fn pending_path(state_dir: &str) -> PathBuf {
+    Path::new(&expand_tilde(state_dir)).join(PENDING_FILE)
+}
+
+impl PendingQueue {
+    /// Loads the queue, treating a missing file as empty.
+    pub fn load(state_dir: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let path = pending_path(state_dir);
+        let mut queue: Self = match fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content)
+                .map_err(|e| format!("Failed to parse '{}': {}", path.display(), e))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Self::default(),
+            Err(e) => return Err(format!("Failed to read '{}': {}", path.display(), e).into()),
+        };
+        let legacy = std::mem::take(&mut queue.privacy);
+        queue
+            .actions
+            .extend(legacy.into_iter().map(|change| PendingAction {
+                video_id: change.video_id,
+                file: change.file,
+                at: change.at,
+                action: Action::SetPrivacy {
+                    privacy: change.privacy,
+                },
+            }));
+        Ok(queue)
+    }
+
+    pub fn save(&self, state_dir: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let path = pending_path(state_dir);
+        fs::create_dir_all(path.parent().unwrap_or(Path::new(".")))?;
+        fs::write(&path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Queues `actions` and writes the queue back. An action already queued
+    /// for the same video is only moved to the new time.
+    pub fn add(
+        state_dir: &str,
+        actions: Vec<PendingAction>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut queue = Self::load(state_dir)?;
+        for action in actions {
+            queue.actions.retain(|queued| {
+                queued.video_id != action.video_id || queued.action != action.action
+            });
+            queue.actions.push(action);
+        }
+        queue.save(state_dir)
+    }
+
+    /// Points the actions queued for `old_id` at `new_id`, for a video that
+    /// was deleted and uploaded again.
+    pub fn replace_video(
+        state_dir: &str,
+        old_id: &str,
+        new_id: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut queue = Self::load(state_dir)?;
+        if !queue.actions.iter().any(|queued| queued.video_id == old_id) {
+            return Ok(());
+        }
+        for queued in &mut queue.actions {
+            if queued.video_id == old_id {
+                queued.video_id = new_id.to_string();
+            }
+        }
+        queue.save(state_dir)
+    }
+
+    /// Actions whose time has come, oldest first.
+    pub fn due(&self, now: DateTime<Utc>) -> Vec<PendingAction> {
+        let mut due: Vec<_> = self
+            .actions
+            .iter()
+            .filter(|pending| pending.at <= now)
+            .cloned()
+            .collect();
+        due.sort_by_key(|pending| pending.at);
+        due
+    }
+
+    pub fn remove(&mut self, done: &PendingAction) {
+        self.actions.retain(|pending| pending != done);
+    }
+
+    pub fn len(&self) -> usize {
+        self.actions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.actions.is_empty()
+    }
+}
@@ -0,0 +1,494 @@
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::expand_tilde;
+
+const LAST_SCHEDULE_FILE: &str = "last_schedule.json";
+const PLANS_DIR: &str = "plans";
+
+/// A fully resolved batch saved for later execution. `M` is the metadata
+/// type sent to YouTube.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Plan<M> {
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub entries: Vec<PlanEntry<M>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PlanEntry<M> {
+    pub file: String,
+    pub publish_at: DateTime<Utc>,
+    pub metadata: M,
+}
+
+/// A batch planned without signing in, for `upload --manifest` on a
+/// machine that holds the credentials. Files are pinned by their SHA-256.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Manifest<M> {
+    pub created_at: DateTime<Utc>,
+    pub entries: Vec<ManifestEntry<M>>,
+    /// HMAC-SHA256 of the rest with the key of `--manifest-key`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+}
+
+/// Shorter keys are too easy to guess
+const MIN_MANIFEST_KEY_LEN: usize = 16;
+
+impl<M: Serialize> Manifest<M> {
+    fn signed_content(&self) -> Vec<u8> {
+        serde_json::to_vec(&(&self.created_at, &self.entries)).unwrap_or_default()
+    }
+
+    pub fn sign(&mut self, key: &[u8]) {
+        self.signature = Some(hmac_sha256(key, &self.signed_content()));
+    }
+
+    /// Fails unless the manifest is signed with `key` and unchanged since.
+    /// Unsigned manifests pass only with `allow_unsigned`, otherwise anyone
+    /// editing the manifest could drop the signature along with it.
+    pub fn check_signature(
+        &self,
+        key: Option<&[u8]>,
+        allow_unsigned: bool,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        match (key, &self.signature) {
+            (_, None) if allow_unsigned => Ok(()),
+            (_, None) => Err(
+                "Refusing the manifest, it is not signed. Sign it with --manifest-key, \
+                 or pass --allow-unsigned-manifest"
+                    .into(),
+            ),
+            (None, Some(_)) => {
+                Err("The manifest is signed, pass --manifest-key to check it".into())
+            }
+            (Some(key), Some(signature)) => {
+                let mut mac = Hmac::<Sha256>::new_from_slice(key)
+                    .map_err(|e| format!("Invalid manifest key: {}", e))?;
+                mac.update(&self.signed_content());
+                let matches = decode_hex(signature)
+                    .map(|signature| mac.verify_slice(&signature).is_ok())
+                    .unwrap_or(false);
+                if !matches {
+                    return Err(
+                        "Refusing the manifest, it was changed after signing or signed with another key"
+                            .into(),
+                    );
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ManifestEntry<M> {
+    pub file: String,
+    pub size: u64,
+    pub sha256: String,
+    pub publish_at: DateTime<Utc>,
+    pub metadata: M,
+}
+
+/// One upload of a dry run exactly as it would be sent.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ResolvedEntry {
+    pub file: String,
+    pub publish_at: DateTime<Utc>,
+    /// The video resource sent to videos.insert
+    pub metadata: serde_json::Value,
+    /// YouTube Data API units the upload costs
+    pub quota_cost: u64,
+    /// Lint rules the metadata violates
+    pub warnings: Vec<String>,
+}
+
+/// A dry-run plan written for review by `--require-approved-plan`. Runs
+/// only go ahead while their plan still hashes to `hash`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ApprovedPlan {
+    pub hash: String,
+    pub created_at: DateTime<Utc>,
+    pub entries: Vec<ResolvedEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScheduleEntry {
+    pub file: String,
+    pub publish_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScheduleChange {
+    Moved {
+        file: String,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    },
+    Added {
+        file: String,
+        at: DateTime<Utc>,
+    },
+    Removed {
+        file: String,
+        at: DateTime<Utc>,
+    },
+}
+
+impl std::fmt::Display for ScheduleChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let format = |time: &DateTime<Utc>| time.format("%Y-%m-%d %H:%M UTC").to_string();
+        match self {
+            ScheduleChange::Moved { file, from, to } => {
+                write!(f, "~ {}: {} -> {}", file, format(from), format(to))
+            }
+            ScheduleChange::Added { file, at } => write!(f, "+ {}: {}", file, format(at)),
+            ScheduleChange::Removed { file, at } => write!(f, "- {}: {}", file, format(at)),
+        }
+    }
+}
+
+pub fn schedule_entries(video_files: &[String], schedule: &[DateTime<Utc>]) -> Vec<ScheduleEntry> {
+    video_files
+        .iter()
+        .zip(schedule)
+        .map(|(file, publish_at)| ScheduleEntry {
+            file: file.clone(),
+            publish_at: *publish_at,
+        })
+        .collect()
+}
+
+fn last_schedule_path(state_dir: &str) -> PathBuf {
+    Path::new(&expand_tilde(state_dir)).join(LAST_SCHEDULE_FILE)
+}
+
+/// The schedule computed by the previous run, dry-run or not.
+pub fn load_last_schedule(state_dir: &str) -> Option<Vec<ScheduleEntry>> {
+    let content = fs::read_to_string(last_schedule_path(state_dir)).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+pub fn save_last_schedule(
+    state_dir: &str,
+    entries: &[ScheduleEntry],
+) -> Result<(), Box<dyn std::error::Error>> {
+    fs::create_dir_all(expand_tilde(state_dir))?;
+    fs::write(
+        last_schedule_path(state_dir),
+        serde_json::to_string_pretty(entries)?,
+    )?;
+    Ok(())
+}
+
+/// Compares two schedules by file, in the order of the new schedule
+/// followed by files that were dropped.
+pub fn diff_schedules(old: &[ScheduleEntry], new: &[ScheduleEntry]) -> Vec<ScheduleChange> {
+    let mut changes = Vec::new();
+
+    for entry in new {
+        match old.iter().find(|o| o.file == entry.file) {
+            Some(previous) if previous.publish_at != entry.publish_at => {
+                changes.push(ScheduleChange::Moved {
+                    file: entry.file.clone(),
+                    from: previous.publish_at,
+                    to: entry.publish_at,
+                })
+            }
+            Some(_) => {}
+            None => changes.push(ScheduleChange::Added {
+                file: entry.file.clone(),
+                at: entry.publish_at,
+            }),
+        }
+    }
+    for previous in old {
+        if !new.iter().any(|n| n.file == previous.file) {
+            changes.push(ScheduleChange::Removed {
+                file: previous.file.clone(),
+                at: previous.publish_at,
+            });
+        }
+    }
+
+    changes
+}
+
+fn plan_path(state_dir: &str, name: &str) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+        return Err(format!("Invalid plan name: '{}'", name).into());
+    }
+    Ok(Path::new(&expand_tilde(state_dir))
+        .join(PLANS_DIR)
+        .join(format!("{}.json", name)))
+}
+
+/// Part of a plan picked with `--only`, `--skip` and `--limit`, by 1-based
+/// position in the printed schedule.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Selection {
+    pub only: Option<Vec<(usize, usize)>>,
+    pub skip: Vec<(usize, usize)>,
+    pub limit: Option<usize>,
+}
+
+impl Selection {
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+
+    /// 0-based indices of the selected entries of a plan with `len` entries,
+    /// in plan order.
+    pub fn indices(&self, len: usize) -> Vec<usize> {
+        let contains = |ranges: &[(usize, usize)], i: usize| {
+            ranges.iter().any(|(a, b)| (*a..=*b).contains(&i))
+        };
+        (0..len)
+            .filter(|i| self.only.as_ref().is_none_or(|only| contains(only, i + 1)))
+            .filter(|i| !contains(&self.skip, i + 1))
+            .take(self.limit.unwrap_or(usize::MAX))
+            .collect()
+    }
+}
+
+/// Parses positions like `3,5,7` or `1-2,10-12`.
+pub fn parse_positions(positions: &str) -> Result<Vec<(usize, usize)>, Box<dyn std::error::Error>> {
+    positions
+        .split(',')
+        .map(|part| {
+            let part = part.trim();
+            let (first, last) = part.split_once('-').unwrap_or((part, part));
+            let parse = |n: &str| {
+                n.trim()
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|n| *n > 0)
+                    .ok_or_else(|| format!("Invalid position '{}', positions start at 1", part))
+            };
+            let (first, last) = (parse(first)?, parse(last)?);
+            if first > last {
+                return Err(format!("Invalid range '{}'", part).into());
+            }
+            Ok((first, last))
+        })
+        .collect()
+}
+
+/// SHA-256 over the entries as JSON, so any change to a file, slot or
+/// metadata field changes the hash.
+pub fn plan_hash(entries: &[ResolvedEntry]) -> String {
+    let json = serde_json::to_vec(entries).unwrap_or_default();
+    Sha256::digest(json)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Writes `entries` and their hash to `path` for approval and returns the
+/// hash.
+pub fn write_plan_for_approval(
+    path: &str,
+    entries: Vec<ResolvedEntry>,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let plan = ApprovedPlan {
+        hash: plan_hash(&entries),
+        created_at: Utc::now(),
+        entries,
+    };
+    fs::write(expand_tilde(path), serde_json::to_string_pretty(&plan)?)
+        .map_err(|e| format!("Failed to write plan '{}': {}", path, e))?;
+    Ok(plan.hash)
+}
+
+fn load_approved_plan(path: &str) -> Result<ApprovedPlan, Box<dyn std::error::Error>> {
+    let content = fs::read_to_string(expand_tilde(path)).map_err(|e| {
+        format!(
+            "Failed to read approved plan '{}': {}. Write it with --dry-run first",
+            path, e
+        )
+    })?;
+    let approved = serde_json::from_str(&content)
+        .map_err(|e| format!("Failed to parse approved plan '{}': {}", path, e))?;
+    Ok(approved)
+}
+
+/// The publish slots approved in `path` for `files`, so the upload uses
+/// the slots of the dry run instead of computing them again from the
+/// current time.
+pub fn approved_slots(
+    path: &str,
+    files: &[String],
+) -> Result<Vec<DateTime<Utc>>, Box<dyn std::error::Error>> {
+    let approved = load_approved_plan(path)?;
+    files
+        .iter()
+        .map(|file| {
+            approved
+                .entries
+                .iter()
+                .find(|entry| &entry.file == file)
+                .map(|entry| entry.publish_at)
+                .ok_or_else(|| format!("{} is not in the approved plan '{}'", file, path).into())
+        })
+        .collect()
+}
+
+/// Fails unless `entries` hash to the plan approved in `path`.
+pub fn check_approved_plan(
+    path: &str,
+    entries: &[ResolvedEntry],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let approved = load_approved_plan(path)?;
+    let hash = plan_hash(entries);
+    if hash != approved.hash {
+        return Err(format!(
+            "The plan changed since it was approved in '{}' (approved {}, now {}). \
+             Review it with --dry-run again",
+            path,
+            &approved.hash[..approved.hash.len().min(12)],
+            &hash[..12]
+        )
+        .into());
+    }
+    Ok(())
+}
+
+pub fn save_plan<M: Serialize>(
+    state_dir: &str,
+    plan: &Plan<M>,
+) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let path = plan_path(state_dir, &plan.name)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&path, serde_json::to_string_pretty(plan)?)?;
+    Ok(path)
+}
+
+pub fn load_plan<M: DeserializeOwned>(
+    state_dir: &str,
+    name: &str,
+) -> Result<Plan<M>, Box<dyn std::error::Error>> {
+    let path = plan_path(state_dir, name)?;
+    let content = fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read plan '{}': {}", path.display(), e))?;
+    let plan = serde_json::from_str(&content)
+        .map_err(|e| format!("Failed to parse plan '{}': {}", path.display(), e))?;
+    Ok(plan)
+}
+
+/// SHA-256 of the whole file. Unlike the sampled history fingerprint this
+/// notices any change, which a manifest executed verbatim needs.
+pub fn file_sha256(path: &str) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+/// Fails listing every video of `manifest` that is missing or whose
+/// contents changed since it was planned.
+pub fn verify_manifest<M>(manifest: &Manifest<M>) -> Result<(), Box<dyn std::error::Error>> {
+    let mut problems = Vec::new();
+    for entry in &manifest.entries {
+        let size = match fs::metadata(&entry.file) {
+            Ok(metadata) => metadata.len(),
+            Err(e) => {
+                problems.push(format!("{}: {}", entry.file, e));
+                continue;
+            }
+        };
+        // A different size can't hash the same, skip reading the file
+        let matches = size == entry.size
+            && match file_sha256(&entry.file) {
+                Ok(hash) => hash.eq_ignore_ascii_case(&entry.sha256),
+                Err(e) => {
+                    problems.push(format!("{}: {}", entry.file, e));
+                    continue;
+                }
+            };
+        if !matches {
+            problems.push(format!(
+                "{}: changed since the manifest was written",
+                entry.file
+            ));
+        }
+    }
+    if !problems.is_empty() {
+        return Err(format!(
+            "Refusing to upload the manifest:\n  {}",
+            problems.join("\n  ")
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// Reads the shared manifest key from `path`, ignoring surrounding
+/// whitespace.
+pub fn load_manifest_key(path: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let content = fs::read_to_string(expand_tilde(path))
+        .map_err(|e| format!("Failed to read manifest key '{}': {}", path, e))?;
+    let key = content.trim();
+    if key.len() < MIN_MANIFEST_KEY_LEN {
+        return Err(format!(
+            "Manifest key '{}' is shorter than {} characters",
+            path, MIN_MANIFEST_KEY_LEN
+        )
+        .into());
+    }
+    crate::redact::register(key);
+    Ok(key.as_bytes().to_vec())
+}
+
+/// HMAC-SHA256 (RFC 2104) of `message`, hex encoded.
+pub fn hmac_sha256(key: &[u8], message: &[u8]) -> String {
+    // Accepts keys of any length, longer ones are hashed first
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any length");
+    mac.update(message);
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Bytes of the hex string `text`, `None` unless it is valid hex.
+fn decode_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+pub fn save_manifest<M: Serialize>(
+    path: &str,
+    manifest: &Manifest<M>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    fs::write(expand_tilde(path), serde_json::to_string_pretty(manifest)?)
+        .map_err(|e| format!("Failed to write manifest '{}': {}", path, e))?;
+    Ok(())
+}
+
+pub fn load_manifest<M: DeserializeOwned>(
+    path: &str,
+) -> Result<Manifest<M>, Box<dyn std::error::Error>> {
+    let content = fs::read_to_string(expand_tilde(path))
+        .map_err(|e| format!("Failed to read manifest '{}': {}", path, e))?;
+    let manifest = serde_json::from_str(&content)
+        .map_err(|e| format!("Failed to parse manifest '{}': {}", path, e))?;
+    Ok(manifest)
+}
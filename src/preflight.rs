@@ -0,0 +1,119 @@
+use clap::ValueEnum;
+use std::io;
+use std::path::Path;
+
+use crate::ffprobe::VideoInfo;
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PreflightMode {
+    Off,
+    /// Print problems and continue
+    Warn,
+    /// Abort before uploading when any problem is found
+    Fail,
+}
+
+/// Flags resolutions and aspect ratios that are unusual for how the video
+/// will be published.
+pub fn check_video_format(info: &VideoInfo, is_short: bool) -> Vec<String> {
+    let mut issues = Vec::new();
+    let VideoInfo { width, height } = *info;
+    let vertical = height > width;
+
+    if vertical && !is_short {
+        issues.push(format!(
+            "vertical video ({}x{}) is not marked as a Short, add #Shorts to the title or description",
+            width, height
+        ));
+    }
+    if !vertical && is_short {
+        issues.push(format!(
+            "marked as a Short but not vertical ({}x{})",
+            width, height
+        ));
+    }
+    if width.min(height) < 720 {
+        issues.push(format!("resolution {}x{} is below 720p", width, height));
+    }
+    // 16:9 for regular uploads, 9:16 for Shorts, with some tolerance for
+    // encoders that crop a few pixels
+    let ratio = width.max(height) as f64 / width.min(height) as f64;
+    if !vertical && (ratio - 16.0 / 9.0).abs() > 0.02 {
+        issues.push(format!(
+            "aspect ratio {:.2}:1 is not 16:9, YouTube will add black bars",
+            ratio
+        ));
+    }
+
+    issues
+}
+
+/// Checks a video against the size and duration limits. YouTube's own
+/// limits are 256 GB and 12 hours, whichever is reached first.
+pub fn check_limits(
+    size_bytes: u64,
+    duration_seconds: Option<f64>,
+    max_size: u64,
+    max_duration_seconds: i64,
+) -> Vec<String> {
+    let mut issues = Vec::new();
+
+    if size_bytes > max_size {
+        issues.push(format!(
+            "file size {:.1} GB exceeds the limit of {:.1} GB",
+            size_bytes as f64 / 1e9,
+            max_size as f64 / 1e9
+        ));
+    }
+    if let Some(duration) = duration_seconds {
+        if duration > max_duration_seconds as f64 {
+            issues.push(format!(
+                "duration {:.1}h exceeds the limit of {:.1}h",
+                duration / 3600.0,
+                max_duration_seconds as f64 / 3600.0
+            ));
+        }
+    }
+
+    issues
+}
+
+/// Free bytes on the file system holding `dir`, or of its nearest existing
+/// parent when it is not created yet. `None` where it cannot be queried.
+#[cfg(unix)]
+pub fn available_space(dir: &Path) -> io::Result<Option<u64>> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let existing = dir
+        .ancestors()
+        .find(|d| d.exists())
+        .unwrap_or(Path::new("."));
+    let path = CString::new(existing.as_os_str().as_bytes())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: a NUL-terminated path and a statvfs to fill, checked for failure
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(Some(stat.f_bavail as u64 * stat.f_frsize as u64))
+}
+
+#[cfg(not(unix))]
+pub fn available_space(_dir: &Path) -> io::Result<Option<u64>> {
+    Ok(None)
+}
+
+/// Checks that `available` bytes leave room for `needed` plus a tenth for
+/// estimates running short.
+pub fn check_free_space(dir: &Path, needed: u64, available: u64) -> Option<String> {
+    let required = needed + needed / 10;
+    (required > available).then(|| {
+        format!(
+            "{} needs about {:.1} GB free but only {:.1} GB are available",
+            dir.display(),
+            required as f64 / 1e9,
+            available as f64 / 1e9
+        )
+    })
+}
@@ -0,0 +1,137 @@
+use std::sync::Mutex;
+
+pub const REDACTED: &str = "[redacted]";
+
+/// Headers that carry credentials
+pub const SECRET_HEADERS: &[&str] = &["authorization", "cookie", "set-cookie", "x-goog-api-key"];
+/// Query, form and JSON fields that carry credentials
+pub const SECRET_PARAMS: &[&str] = &[
+    "access_token",
+    "refresh_token",
+    "id_token",
+    "token",
+    "code",
+    "key",
+    "client_secret",
+    // Anyone holding a resumable session URL can upload into it
+    "upload_id",
+    // Signed URLs, e.g. of thumbnails and downloads
+    "signature",
+    "x-goog-signature",
+];
+/// Prefixes of Google access tokens, refresh tokens and client secrets,
+/// and of bearer credentials
+const SECRET_PREFIXES: &[&str] = &["ya29.", "1//", "gocspx-", "bearer "];
+
+/// Shorter values are too likely to appear in ordinary text
+const MIN_SECRET_LEN: usize = 8;
+
+/// Credentials of this process, replaced wherever they show up.
+static SECRETS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Redacts `secret` from all output for the rest of the process.
+pub fn register(secret: &str) {
+    let secret = secret.trim();
+    if secret.len() < MIN_SECRET_LEN {
+        return;
+    }
+    let mut secrets = SECRETS.lock().unwrap();
+    if !secrets.iter().any(|known| known == secret) {
+        secrets.push(secret.to_string());
+    }
+}
+
+/// `text` with credentials replaced: registered secrets, Google tokens
+/// and client secrets, bearer credentials, and the values of credential
+/// parameters, JSON fields and headers. Applied to everything printed,
+/// journaled or sent as a notification.
+pub fn redact(text: &str) -> String {
+    let mut text = text.to_string();
+    for secret in SECRETS.lock().unwrap().iter() {
+        if text.contains(secret.as_str()) {
+            text = text.replace(secret.as_str(), REDACTED);
+        }
+    }
+    for prefix in SECRET_PREFIXES {
+        text = redact_after(&text, prefix, |rest| Some((0, value_end(rest))));
+    }
+    for name in SECRET_PARAMS.iter().chain(SECRET_HEADERS) {
+        let header = SECRET_HEADERS.contains(name);
+        text = redact_after(&text, name, |rest| field_value(rest, header));
+    }
+    text
+}
+
+/// Replaces what `value` finds after every occurrence of `marker`, which
+/// is matched ignoring ASCII case and only at the start of a word.
+/// `value` gets the text after the marker and returns the byte range of
+/// the secret in it.
+fn redact_after(
+    text: &str,
+    marker: &str,
+    value: impl Fn(&str) -> Option<(usize, usize)>,
+) -> String {
+    // ASCII lowercasing keeps byte offsets
+    let lowered = text.to_ascii_lowercase();
+    let mut redacted = String::with_capacity(text.len());
+    let mut copied = 0;
+    for (at, _) in lowered.match_indices(marker) {
+        let after = at + marker.len();
+        let at_word_start = !text[..at]
+            .chars()
+            .next_back()
+            .is_some_and(|c| c.is_alphanumeric() || c == '_' || c == '-');
+        if at < copied || !at_word_start {
+            continue;
+        }
+        let rest = &text[after..];
+        let Some((start, end)) = value(rest) else {
+            continue;
+        };
+        if start == end || rest[start..].starts_with(REDACTED) {
+            continue;
+        }
+        redacted.push_str(&text[copied..after + start]);
+        redacted.push_str(REDACTED);
+        copied = after + end;
+    }
+    redacted.push_str(&text[copied..]);
+    redacted
+}
+
+/// Value of a field whose name was just matched: `=value` in queries and
+/// forms, `"name": "value"` in JSON, also escaped inside JSON strings,
+/// and `name: value` up to the end of the line for headers.
+fn field_value(rest: &str, header: bool) -> Option<(usize, usize)> {
+    if let Some(value) = rest.strip_prefix('=') {
+        return Some((1, 1 + value_end(value)));
+    }
+    let quoted = rest
+        .strip_prefix("\\\"")
+        .or_else(|| rest.strip_prefix('"'))
+        .and_then(|after| after.trim_start().strip_prefix(':'))
+        .map(str::trim_start)
+        .and_then(|after| {
+            after
+                .strip_prefix("\\\"")
+                .or_else(|| after.strip_prefix('"'))
+        });
+    if let Some(value) = quoted {
+        let start = rest.len() - value.len();
+        let end = value.find(['"', '\\']).unwrap_or(value.len());
+        return Some((start, start + end));
+    }
+    if header {
+        let value = rest.strip_prefix(':')?.trim_start();
+        let start = rest.len() - value.len();
+        let end = value.find(['\n', '\r']).unwrap_or(value.len());
+        return Some((start, start + value[..end].trim_end().len()));
+    }
+    None
+}
+
+/// Length of the unquoted value at the start of `text`.
+fn value_end(text: &str) -> usize {
+    text.find(|c: char| c.is_whitespace() || "&\"',;)]}<>\\".contains(c))
+        .unwrap_or(text.len())
+}
@@ -0,0 +1,386 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::output;
+use crate::timezone;
+use crate::youtube::UploadProgress;
+
+/// What happened to one video of a batch.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    Uploaded {
+        video_id: String,
+    },
+    Failed {
+        error: String,
+        /// Uploading the file again would fail the same way, e.g. an
+        /// invalid title or a duplicate, as opposed to network errors
+        permanent: bool,
+    },
+}
+
+/// How adding one caption track to an uploaded video went.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaptionResult {
+    pub language: String,
+    /// Why the track is missing, `None` once it was added
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct UploadResult {
+    pub file: String,
+    pub title: String,
+    pub publish_at: DateTime<Utc>,
+    pub outcome: Outcome,
+    /// Caption tracks of the uploaded video, in upload order
+    pub captions: Vec<CaptionResult>,
+}
+
+impl UploadResult {
+    pub fn is_success(&self) -> bool {
+        matches!(self.outcome, Outcome::Uploaded { .. })
+    }
+}
+
+/// Plain-text summary of a batch: counts, a per-video table and the full
+/// error of every failure, failed caption tracks included.
+pub fn render_summary(results: &[UploadResult]) -> String {
+    let succeeded = results.iter().filter(|r| r.is_success()).count();
+    let mut summary = format!(
+        "{} of {} videos uploaded, {} failed.\n\n",
+        succeeded,
+        results.len(),
+        results.len() - succeeded
+    );
+
+    let file_width = results
+        .iter()
+        .map(|r| r.file.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max(4);
+    summary.push_str(&format!(
+        "{:<file_width$}  {:<20}  RESULT\n",
+        "FILE", "PUBLISH AT (UTC)"
+    ));
+    for result in results {
+        let mut status = match &result.outcome {
+            Outcome::Uploaded { video_id } => format!("ok https://youtu.be/{}", video_id),
+            Outcome::Failed { .. } => "FAILED".to_string(),
+        };
+        if !result.captions.is_empty() {
+            let tracks: Vec<_> = result
+                .captions
+                .iter()
+                .map(|track| match track.error {
+                    Some(_) => format!("{} FAILED", track.language),
+                    None => format!("{} ok", track.language),
+                })
+                .collect();
+            status.push_str(&format!(", captions {}", tracks.join(", ")));
+        }
+        summary.push_str(&format!(
+            "{:<file_width$}  {:<20}  {}\n",
+            result.file,
+            result.publish_at.format("%Y-%m-%d %H:%M").to_string(),
+            status
+        ));
+    }
+
+    let failures: Vec<_> = results
+        .iter()
+        .filter_map(|r| match &r.outcome {
+            Outcome::Failed { error, .. } => Some((r, error)),
+            Outcome::Uploaded { .. } => None,
+        })
+        .collect();
+    if !failures.is_empty() {
+        summary.push_str("\nFailures:\n");
+        for (result, error) in failures {
+            summary.push_str(&format!("\n{}:\n{}\n", result.file, error));
+        }
+    }
+
+    let caption_failures: Vec<_> = results
+        .iter()
+        .flat_map(|r| r.captions.iter().map(move |track| (r, track)))
+        .filter_map(|(r, track)| Some((r, &track.language, track.error.as_ref()?)))
+        .collect();
+    if !caption_failures.is_empty() {
+        summary.push_str("\nCaption failures:\n");
+        for (result, language, error) in caption_failures {
+            summary.push_str(&format!("\n{} ({}):\n{}\n", result.file, language, error));
+        }
+    }
+
+    summary
+}
+
+/// Markdown note of a batch for planning vaults like Obsidian or Notion:
+/// a task per uploaded video with its publish time and an embed, and the
+/// failures. Times are in the configured timezone.
+pub fn render_note(results: &[UploadResult], finished_at: DateTime<Utc>) -> String {
+    let local = |time: DateTime<Utc>| {
+        format!(
+            "{} {}",
+            timezone::local_date(time).format("%Y-%m-%d"),
+            timezone::local_time(time).format("%H:%M")
+        )
+    };
+    let succeeded = results.iter().filter(|r| r.is_success()).count();
+    let mut note = format!(
+        "# Upload batch {}\n\n{} of {} videos uploaded, {} failed.\n",
+        local(finished_at),
+        succeeded,
+        results.len(),
+        results.len() - succeeded
+    );
+
+    let mut uploaded: Vec<_> = results
+        .iter()
+        .filter_map(|r| match &r.outcome {
+            Outcome::Uploaded { video_id } => Some((r, video_id)),
+            Outcome::Failed { .. } => None,
+        })
+        .collect();
+    uploaded.sort_by_key(|(r, _)| r.publish_at);
+    if !uploaded.is_empty() {
+        note.push_str(&format!("\n## Scheduled ({})\n\n", timezone::label()));
+        for (result, video_id) in uploaded {
+            note.push_str(&format!(
+                "- [ ] {} **{}** (`{}`)\n    ![{}](https://www.youtube.com/watch?v={})\n",
+                local(result.publish_at),
+                result.title,
+                result.file,
+                result.title,
+                video_id
+            ));
+        }
+    }
+
+    let failures: Vec<_> = results
+        .iter()
+        .filter_map(|r| match &r.outcome {
+            Outcome::Failed { error, .. } => Some((r, error)),
+            Outcome::Uploaded { .. } => None,
+        })
+        .collect();
+    if !failures.is_empty() {
+        note.push_str("\n## Failed\n\n");
+        for (result, error) in failures {
+            let reason = error.lines().next().unwrap_or_default();
+            note.push_str(&format!("- [ ] `{}`: {}\n", result.file, reason));
+        }
+    }
+    note
+}
+
+/// Totals of a batch for capacity planning, printed at its end and
+/// journaled as `batch_finished`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BatchStats {
+    pub succeeded: usize,
+    pub failed: usize,
+    /// Bytes sent, failed attempts included
+    pub bytes: u64,
+    /// Wall-clock time of the batch, waits included
+    pub duration_secs: u64,
+    /// Time spent sending
+    pub upload_secs: f64,
+    /// `bytes` over `upload_secs`, in bytes per second
+    pub average_rate: u64,
+    /// Average rate of the fastest upload, in bytes per second
+    pub peak_rate: u64,
+    /// Chunks sent again and videos uploaded again
+    pub retries: u32,
+    /// Estimated quota units spent
+    pub quota: u64,
+}
+
+impl BatchStats {
+    /// Adds the transfer of one upload attempt, from its last progress.
+    pub fn add_transfer(&mut self, progress: &UploadProgress, seconds: f64) {
+        self.bytes += progress.bytes_sent;
+        self.upload_secs += seconds;
+        self.peak_rate = self.peak_rate.max(progress.rate);
+        self.retries += progress.chunk_retries;
+        if self.upload_secs > 0.0 {
+            self.average_rate = (self.bytes as f64 / self.upload_secs) as u64;
+        }
+    }
+
+    pub fn render(&self) -> String {
+        let megabits = |rate: u64| rate as f64 * 8.0 / 1_000_000.0;
+        format!(
+            "{} uploaded, {} failed\n\
+             {:.1} MB sent in {}, {} in total\n\
+             Throughput: average {:.1} Mbit/s, peak {:.1} Mbit/s\n\
+             {} retries, about {} quota units",
+            self.succeeded,
+            self.failed,
+            self.bytes as f64 / 1_000_000.0,
+            format_duration(self.upload_secs as u64),
+            format_duration(self.duration_secs),
+            megabits(self.average_rate),
+            megabits(self.peak_rate),
+            self.retries,
+            self.quota
+        )
+    }
+}
+
+/// `bytes` in GB from one GB on, in MB below.
+pub fn format_size(bytes: u64) -> String {
+    if bytes >= 1_000_000_000 {
+        format!("{:.1} GB", bytes as f64 / 1e9)
+    } else {
+        format!("{:.1} MB", bytes as f64 / 1e6)
+    }
+}
+
+/// `1h 02m 03s`, leaving out leading zero units.
+pub fn format_duration(seconds: u64) -> String {
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{}h {:02}m {:02}s", hours, minutes, seconds)
+    } else if minutes > 0 {
+        format!("{}m {:02}s", minutes, seconds)
+    } else {
+        format!("{}s", seconds)
+    }
+}
+
+/// Heartbeat of a long upload, e.g. `Still uploading ep1.mkv: 42% of
+/// 3.0 GB, 12.3 Mbit/s, about 35m 10s left`.
+pub fn heartbeat_line(file: &str, progress: &UploadProgress) -> String {
+    let percent = match progress.total {
+        0 => 100,
+        total => progress.bytes_sent * 100 / total,
+    };
+    let mut line = format!(
+        "Still uploading {}: {}% of {:.1} GB, {:.1} Mbit/s",
+        file,
+        percent,
+        progress.total as f64 / 1_000_000_000.0,
+        progress.rate as f64 * 8.0 / 1_000_000.0
+    );
+    if let Some(eta) = progress.eta {
+        line.push_str(&format!(", about {} left", format_duration(eta)));
+    }
+    line
+}
+
+/// Emits `upload_progress` events for one upload with `--output json`, at
+/// most one per `interval` except for the final one, and prints a
+/// heartbeat line every `heartbeat` in any output mode, so logs of long
+/// uploads show they are alive.
+pub struct ProgressReporter {
+    file: String,
+    interval: Duration,
+    last: Mutex<Option<Instant>>,
+    heartbeat: Option<Duration>,
+    last_heartbeat: Mutex<Instant>,
+    started: Instant,
+    latest: Mutex<Option<UploadProgress>>,
+}
+
+impl ProgressReporter {
+    pub fn new(file: &str, interval: Duration, heartbeat: Option<Duration>) -> Self {
+        Self {
+            file: file.to_string(),
+            interval,
+            last: Mutex::new(None),
+            heartbeat,
+            last_heartbeat: Mutex::new(Instant::now()),
+            started: Instant::now(),
+            latest: Mutex::new(None),
+        }
+    }
+
+    /// The last progress reported and the time since the reporter was
+    /// created, `None` when no chunk was sent.
+    pub fn transfer(&self) -> Option<(UploadProgress, f64)> {
+        let latest = self.latest.lock().unwrap().clone()?;
+        Some((latest, self.started.elapsed().as_secs_f64()))
+    }
+
+    pub fn report(&self, progress: &UploadProgress) {
+        *self.latest.lock().unwrap() = Some(progress.clone());
+        let done = progress.bytes_sent >= progress.total;
+        if let Some(heartbeat) = self.heartbeat.filter(|_| !done) {
+            let mut last_heartbeat = self.last_heartbeat.lock().unwrap();
+            if last_heartbeat.elapsed() >= heartbeat {
+                *last_heartbeat = Instant::now();
+                crate::info!("{}", heartbeat_line(&self.file, progress));
+            }
+        }
+        let mut last = self.last.lock().unwrap();
+        if !done && last.is_some_and(|at| at.elapsed() < self.interval) {
+            return;
+        }
+        *last = Some(Instant::now());
+        output::emit(&json!({
+            "at": Utc::now(),
+            "event": "upload_progress",
+            "file": self.file,
+            "bytes_sent": progress.bytes_sent,
+            "total": progress.total,
+            "rate": progress.rate,
+            "eta": progress.eta,
+            "chunk_retries": progress.chunk_retries,
+        }));
+    }
+}
+
+/// Live state of a running batch, shared with remote status commands.
+#[derive(Debug, Default)]
+pub struct BatchProgress {
+    pub current: Option<String>,
+    /// One slot per video in batch order, filled once it was attempted
+    pub results: Vec<Option<UploadResult>>,
+}
+
+impl BatchProgress {
+    pub fn new(total: usize) -> Self {
+        Self {
+            current: None,
+            results: vec![None; total],
+        }
+    }
+
+    /// Indices of videos whose latest attempt failed.
+    pub fn failed_indices(&self) -> Vec<usize> {
+        self.results
+            .iter()
+            .enumerate()
+            .filter(|(_, r)| r.as_ref().is_some_and(|r| !r.is_success()))
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// Results of every attempted video in batch order.
+    pub fn finished(&self) -> Vec<UploadResult> {
+        self.results.iter().flatten().cloned().collect()
+    }
+
+    pub fn status(&self, paused: bool) -> String {
+        let done = self.results.iter().flatten().count();
+        let mut status = format!(
+            "{} of {} videos done, {} failed",
+            done,
+            self.results.len(),
+            self.failed_indices().len()
+        );
+        if let Some(current) = &self.current {
+            status.push_str(&format!("\nUploading {}", current));
+        }
+        if paused {
+            status.push_str("\nPaused, send /resume to continue");
+        }
+        status
+    }
+}
@@ -0,0 +1,376 @@
+use chrono::{DateTime, Utc};
+use tokio::sync::mpsc;
+
+use crate::config::Profile;
+use crate::control::CancellationToken;
+use crate::report::{Outcome, UploadResult};
+use crate::sanitize::CaseStyle;
+use crate::youtube::{
+    create_default_metadata, UploadError, UploadProgress, UploadResponse, VideoMetadata,
+    YouTubeUploader,
+};
+
+/// Uploads between token refreshes, matching the binary
+const REFRESH_EVERY: usize = 8;
+
+/// A single video with its metadata and optional publish time.
+pub struct UploadJob {
+    file: String,
+    metadata: VideoMetadata,
+    cancel: CancellationToken,
+}
+
+impl UploadJob {
+    pub fn builder() -> UploadJobBuilder {
+        UploadJobBuilder::default()
+    }
+
+    pub fn file(&self) -> &str {
+        &self.file
+    }
+
+    pub fn metadata(&self) -> &VideoMetadata {
+        &self.metadata
+    }
+
+    /// Token that aborts just this upload when cancelled.
+    pub fn cancellation_token(&self) -> &CancellationToken {
+        &self.cancel
+    }
+}
+
+#[derive(Default)]
+pub struct UploadJobBuilder {
+    file: Option<String>,
+    metadata: Option<VideoMetadata>,
+    publish_at: Option<DateTime<Utc>>,
+    cancel: Option<CancellationToken>,
+}
+
+impl UploadJobBuilder {
+    pub fn file(mut self, file: impl Into<String>) -> Self {
+        self.file = Some(file.into());
+        self
+    }
+
+    /// Metadata to upload with. Without it the title is the file name and
+    /// the rest comes from the default profile.
+    pub fn metadata(mut self, metadata: VideoMetadata) -> Self {
+        self.metadata = Some(metadata);
+        self
+    }
+
+    /// Keeps the video private until `publish_at`.
+    pub fn publish_at(mut self, publish_at: DateTime<Utc>) -> Self {
+        self.publish_at = Some(publish_at);
+        self
+    }
+
+    /// Aborts the upload when `cancel` fires. A fresh token is used
+    /// otherwise, see `UploadJob::cancellation_token`.
+    pub fn cancellation_token(mut self, cancel: CancellationToken) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+
+    pub fn build(self) -> Result<UploadJob, Box<dyn std::error::Error>> {
+        let file = self.file.ok_or("UploadJob needs a file")?;
+        let mut metadata = match self.metadata {
+            Some(metadata) => metadata,
+            None => create_default_metadata(
+                std::slice::from_ref(&file),
+                None,
+                CaseStyle::None,
+                &Profile::default(),
+            )
+            .remove(0),
+        };
+        if let Some(publish_at) = self.publish_at {
+            metadata.scheduled_start_time = Some(publish_at.to_rfc3339());
+            metadata.privacy_status = "private".to_string();
+            metadata.resolve_schedule(publish_at);
+        }
+        metadata.sanitize();
+        Ok(UploadJob {
+            file,
+            metadata,
+            cancel: self.cancel.unwrap_or_default(),
+        })
+    }
+}
+
+/// What happened while a `BatchRunner` worked through its jobs. `index` is
+/// the job's position in the batch.
+#[derive(Debug, Clone)]
+pub enum BatchEvent {
+    Started {
+        index: usize,
+        file: String,
+    },
+    /// Sent after every chunk of an upload
+    Progress {
+        index: usize,
+        file: String,
+        progress: UploadProgress,
+    },
+    Retrying {
+        index: usize,
+        file: String,
+        attempt: u32,
+        error: String,
+    },
+    Uploaded {
+        index: usize,
+        file: String,
+        video_id: String,
+    },
+    Failed {
+        index: usize,
+        file: String,
+        error: String,
+    },
+    Cancelled {
+        index: usize,
+        file: String,
+    },
+    /// Always the last event
+    Finished {
+        results: Vec<UploadResult>,
+    },
+}
+
+/// Callbacks a `BatchRunner` invokes as it goes, for GUIs and bots that
+/// render progress. Every method does nothing unless overridden.
+pub trait UploadObserver: Send + Sync {
+    fn on_started(&self, _index: usize, _file: &str) {}
+    /// `done` of `total` jobs are finished, successfully or not
+    fn on_progress(&self, _done: usize, _total: usize) {}
+    /// Bytes sent of the current upload, after every chunk
+    fn on_upload_progress(&self, _index: usize, _file: &str, _progress: &UploadProgress) {}
+    /// Upload `attempt` (starting at 2) follows a failed one
+    fn on_retry(&self, _index: usize, _file: &str, _attempt: u32, _error: &str) {}
+    fn on_completed(&self, _index: usize, _file: &str, _video_id: &str) {}
+    fn on_failed(&self, _index: usize, _file: &str, _error: &str) {}
+    fn on_cancelled(&self, _index: usize, _file: &str) {}
+}
+
+/// Uploads jobs one after another with an authenticated uploader, for
+/// applications embedding this crate instead of running the binary. Like
+/// the binary it stops early once the quota is exhausted.
+pub struct BatchRunner {
+    uploader: YouTubeUploader,
+    jobs: Vec<UploadJob>,
+    observers: Vec<Box<dyn UploadObserver>>,
+    retries: u32,
+    cancel: CancellationToken,
+}
+
+impl BatchRunner {
+    pub fn new(uploader: YouTubeUploader) -> Self {
+        Self {
+            uploader,
+            jobs: Vec::new(),
+            observers: Vec::new(),
+            retries: 0,
+            cancel: CancellationToken::new(),
+        }
+    }
+
+    pub fn job(mut self, job: UploadJob) -> Self {
+        self.jobs.push(job);
+        self
+    }
+
+    pub fn jobs(mut self, jobs: impl IntoIterator<Item = UploadJob>) -> Self {
+        self.jobs.extend(jobs);
+        self
+    }
+
+    pub fn observer(mut self, observer: impl UploadObserver + 'static) -> Self {
+        self.observers.push(Box::new(observer));
+        self
+    }
+
+    /// Uploads that fail for reasons other than the quota are attempted up
+    /// to `retries` more times.
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Cancelling `cancel` aborts the current upload and skips the rest of
+    /// the batch; the results cover the jobs started until then.
+    pub fn cancellation_token(mut self, cancel: CancellationToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    /// Starts uploading in the background and returns the stream of events.
+    /// Dropping the receiver doesn't stop the batch.
+    pub fn run(self) -> mpsc::UnboundedReceiver<BatchEvent> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        tokio::spawn(self.upload_all(sender));
+        receiver
+    }
+
+    fn notify(&self, callback: impl Fn(&dyn UploadObserver)) {
+        for observer in &self.observers {
+            callback(observer.as_ref());
+        }
+    }
+
+    async fn upload_all(mut self, events: mpsc::UnboundedSender<BatchEvent>) {
+        let total = self.jobs.len();
+        let mut results = Vec::new();
+        let mut attempts = 0;
+        for index in 0..total {
+            if self.cancel.is_cancelled() {
+                break;
+            }
+            let file = self.jobs[index].file.clone();
+            let _ = events.send(BatchEvent::Started {
+                index,
+                file: file.clone(),
+            });
+            self.notify(|o| o.on_started(index, &file));
+
+            let mut attempt = 1;
+            let result = loop {
+                attempts += 1;
+                if attempts % REFRESH_EVERY == 0 {
+                    if let Err(e) = self
+                        .uploader
+                        .authenticate()
+                        .await
+                        .map_err(|e| e.to_string())
+                    {
+                        crate::warning!("Could not refresh the access token: {}", e);
+                    }
+                }
+
+                let job = &self.jobs[index];
+                let on_progress = |progress: &UploadProgress| {
+                    let _ = events.send(BatchEvent::Progress {
+                        index,
+                        file: file.clone(),
+                        progress: progress.clone(),
+                    });
+                    self.notify(|o| o.on_upload_progress(index, &file, progress));
+                };
+                let upload = self.uploader.upload_video_with_progress(
+                    &job.file,
+                    &job.metadata,
+                    &job.cancel,
+                    &on_progress,
+                );
+                let result = tokio::select! {
+                    result = upload => Attempt::from(result),
+                    _ = self.cancel.cancelled() => Attempt::Cancelled,
+                };
+                match result {
+                    Attempt::Failed(error, _) if attempt <= self.retries => {
+                        attempt += 1;
+                        let _ = events.send(BatchEvent::Retrying {
+                            index,
+                            file: file.clone(),
+                            attempt,
+                            error: error.clone(),
+                        });
+                        self.notify(|o| o.on_retry(index, &file, attempt, &error));
+                    }
+                    result => break result,
+                }
+            };
+
+            let outcome = match &result {
+                Attempt::Uploaded(video_id) => {
+                    let _ = events.send(BatchEvent::Uploaded {
+                        index,
+                        file: file.clone(),
+                        video_id: video_id.clone(),
+                    });
+                    self.notify(|o| o.on_completed(index, &file, video_id));
+                    Outcome::Uploaded {
+                        video_id: video_id.clone(),
+                    }
+                }
+                Attempt::Cancelled => {
+                    let _ = events.send(BatchEvent::Cancelled {
+                        index,
+                        file: file.clone(),
+                    });
+                    self.notify(|o| o.on_cancelled(index, &file));
+                    Outcome::Failed {
+                        error: UploadError::Cancelled.to_string(),
+                        permanent: false,
+                    }
+                }
+                Attempt::Failed(error, permanent) => {
+                    let _ = events.send(BatchEvent::Failed {
+                        index,
+                        file: file.clone(),
+                        error: error.clone(),
+                    });
+                    self.notify(|o| o.on_failed(index, &file, error));
+                    Outcome::Failed {
+                        error: error.clone(),
+                        permanent: *permanent,
+                    }
+                }
+                Attempt::QuotaExhausted(error) => {
+                    let _ = events.send(BatchEvent::Failed {
+                        index,
+                        file: file.clone(),
+                        error: error.clone(),
+                    });
+                    self.notify(|o| o.on_failed(index, &file, error));
+                    Outcome::Failed {
+                        error: error.clone(),
+                        permanent: false,
+                    }
+                }
+            };
+            let metadata = &self.jobs[index].metadata;
+            results.push(UploadResult {
+                file,
+                title: metadata.title().to_string(),
+                publish_at: metadata
+                    .scheduled_start_time
+                    .as_deref()
+                    .and_then(|at| DateTime::parse_from_rfc3339(at).ok())
+                    .map_or_else(Utc::now, |at| at.with_timezone(&Utc)),
+                outcome,
+                captions: Vec::new(),
+            });
+            self.notify(|o| o.on_progress(index + 1, total));
+            if matches!(result, Attempt::QuotaExhausted(_)) {
+                break;
+            }
+        }
+        let _ = events.send(BatchEvent::Finished { results });
+    }
+}
+
+/// How a single upload attempt ended.
+enum Attempt {
+    Uploaded(String),
+    /// The error and whether it is permanent
+    Failed(String, bool),
+    QuotaExhausted(String),
+    Cancelled,
+}
+
+impl From<Result<UploadResponse, Box<dyn std::error::Error>>> for Attempt {
+    fn from(result: Result<UploadResponse, Box<dyn std::error::Error>>) -> Self {
+        match result {
+            Ok(response) => Attempt::Uploaded(response.id),
+            Err(e) => match e.downcast_ref::<UploadError>() {
+                Some(UploadError::Cancelled) => Attempt::Cancelled,
+                Some(error) if error.is_quota_exhausted() => Attempt::QuotaExhausted(e.to_string()),
+                error => {
+                    Attempt::Failed(e.to_string(), error.is_some_and(UploadError::is_permanent))
+                }
+            },
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use clap::ValueEnum;
+
+pub const MAX_TITLE_CHARS: usize = 100;
+pub const MAX_DESCRIPTION_BYTES: usize = 5000;
+
+const ILLEGAL_CHARS: [char; 2] = ['<', '>'];
+const ELLIPSIS: char = '…';
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CaseStyle {
+    None,
+    Title,
+    Sentence,
+    Lower,
+    Upper,
+}
+
+/// Strips characters YouTube rejects, collapses whitespace and truncates
+/// to the title limit. Truncation is reported on stderr since it changes
+/// what viewers see.
+pub fn sanitize_title(title: &str) -> String {
+    let cleaned: String = title
+        .chars()
+        .filter(|c| !ILLEGAL_CHARS.contains(c))
+        .collect();
+    let normalized = cleaned.split_whitespace().collect::<Vec<_>>().join(" ");
+
+    if normalized.chars().count() > MAX_TITLE_CHARS {
+        let truncated: String = normalized.chars().take(MAX_TITLE_CHARS - 1).collect();
+        let truncated = format!("{}{}", truncated.trim_end(), ELLIPSIS);
+        crate::warning!(
+            "title exceeds {} characters and was truncated to \"{}\"",
+            MAX_TITLE_CHARS,
+            truncated
+        );
+        truncated
+    } else {
+        normalized
+    }
+}
+
+/// Same as `sanitize_title` but keeps line breaks, only trimming trailing
+/// whitespace on each line. The limit is in bytes, as YouTube counts it.
+pub fn sanitize_description(description: &str) -> String {
+    let cleaned: String = description
+        .chars()
+        .filter(|c| !ILLEGAL_CHARS.contains(c))
+        .collect();
+    let normalized = cleaned
+        .lines()
+        .map(str::trim_end)
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_string();
+
+    if normalized.len() > MAX_DESCRIPTION_BYTES {
+        let mut end = MAX_DESCRIPTION_BYTES - ELLIPSIS.len_utf8();
+        while !normalized.is_char_boundary(end) {
+            end -= 1;
+        }
+        crate::warning!(
+            "description exceeds {} bytes and was truncated",
+            MAX_DESCRIPTION_BYTES
+        );
+        format!("{}{}", normalized[..end].trim_end(), ELLIPSIS)
+    } else {
+        normalized
+    }
+}
+
+pub fn apply_case_style(title: &str, style: CaseStyle) -> String {
+    match style {
+        CaseStyle::None => title.to_string(),
+        CaseStyle::Lower => title.to_lowercase(),
+        CaseStyle::Upper => title.to_uppercase(),
+        CaseStyle::Title => title
+            .split(' ')
+            .map(capitalize)
+            .collect::<Vec<_>>()
+            .join(" "),
+        CaseStyle::Sentence => capitalize(&title.to_lowercase()),
+    }
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
@@ -0,0 +1,131 @@
+use serde::Deserialize;
+use std::fs;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use crate::expand_tilde;
+
+/// Checks of titles and descriptions from the `screening` section of the
+/// config file, e.g. for internal codenames that must not go public.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ScreeningConfig {
+    /// Words and phrases matched as whole words, ignoring case
+    pub words: Vec<String>,
+    /// File of more words, one per line. Blank lines and lines starting
+    /// with `#` are ignored
+    pub wordlist: Option<String>,
+    /// Program and arguments run without a shell for every video, with the
+    /// title and description on stdin. A non-zero exit flags the video and
+    /// its output says why
+    pub command: Vec<String>,
+    pub action: ScreeningAction,
+}
+
+/// What happens to flagged videos.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScreeningAction {
+    /// Warn and upload anyway
+    #[default]
+    Warn,
+    /// Stop before anything is uploaded
+    Block,
+}
+
+/// Screening settings with the wordlist read.
+#[derive(Debug, Clone)]
+pub struct Screener {
+    words: Vec<String>,
+    command: Vec<String>,
+    pub action: ScreeningAction,
+}
+
+impl Screener {
+    pub fn load(config: &ScreeningConfig) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut words: Vec<String> = config.words.iter().map(|w| w.to_lowercase()).collect();
+        if let Some(path) = &config.wordlist {
+            let expanded_path = expand_tilde(path);
+            let content = fs::read_to_string(&expanded_path)
+                .map_err(|e| format!("Failed to read wordlist '{}': {}", expanded_path, e))?;
+            words.extend(
+                content
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                    .map(str::to_lowercase),
+            );
+        }
+        words.retain(|w| !w.trim().is_empty());
+        Ok(Self {
+            words,
+            command: config.command.clone(),
+            action: config.action,
+        })
+    }
+
+    /// Why the title or description was flagged, empty when they pass.
+    pub fn screen(
+        &self,
+        title: &str,
+        description: &str,
+    ) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let mut findings = Vec::new();
+        for (field, text) in [("title", title), ("description", description)] {
+            for word in find_words(text, &self.words) {
+                findings.push(format!("{} contains \"{}\"", field, word));
+            }
+        }
+        if let Some(finding) = self.run_command(title, description)? {
+            findings.push(finding);
+        }
+        Ok(findings)
+    }
+
+    fn run_command(
+        &self,
+        title: &str,
+        description: &str,
+    ) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        let Some(program) = self.command.first() else {
+            return Ok(None);
+        };
+        let mut child = Command::new(expand_tilde(program))
+            .args(&self.command[1..])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Failed to run {}: {}", program, e))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            // A command that exits without reading closes the pipe early
+            let _ = write!(stdin, "{}\n\n{}", title, description);
+        }
+        let output = child.wait_with_output()?;
+        if output.status.success() {
+            return Ok(None);
+        }
+        let reason = [output.stdout, output.stderr]
+            .iter()
+            .map(|out| String::from_utf8_lossy(out).trim().to_string())
+            .find(|out| !out.is_empty())
+            .unwrap_or_else(|| format!("{} flagged it ({})", program, output.status));
+        Ok(Some(reason))
+    }
+}
+
+/// The `words` found in `text` as whole words, ignoring case.
+pub fn find_words<'a>(text: &str, words: &'a [String]) -> Vec<&'a str> {
+    let text = text.to_lowercase();
+    let is_word_char = |c: Option<char>| c.is_some_and(char::is_alphanumeric);
+    words
+        .iter()
+        .filter(|word| {
+            text.match_indices(word.as_str()).any(|(at, found)| {
+                !is_word_char(text[..at].chars().next_back())
+                    && !is_word_char(text[at + found.len()..].chars().next())
+            })
+        })
+        .map(String::as_str)
+        .collect()
+}
@@ -0,0 +1,119 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::expand_tilde;
+
+const SERIES_FILE: &str = "series.json";
+/// Longest playlist description YouTube accepts
+const MAX_DESCRIPTION_CHARS: usize = 5000;
+
+/// An uploaded episode of a series.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Episode {
+    pub number: u32,
+    pub title: String,
+    pub video_id: String,
+}
+
+/// Playlist and episodes of one series.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Series {
+    /// Found or created with the first upload
+    #[serde(default)]
+    pub playlist_id: Option<String>,
+    #[serde(default)]
+    pub episodes: Vec<Episode>,
+}
+
+impl Series {
+    /// Number of the episode after the last uploaded one.
+    pub fn next_number(&self) -> u32 {
+        self.episodes.iter().map(|e| e.number).max().unwrap_or(0) + 1
+    }
+
+    /// Adds an episode, replacing an earlier upload with the same number.
+    pub fn record(&mut self, episode: Episode) {
+        self.episodes.retain(|e| e.number != episode.number);
+        self.episodes.push(episode);
+        self.episodes.sort_by_key(|e| e.number);
+    }
+
+    /// Playlist description listing the episodes. The oldest are left out
+    /// once the list outgrows YouTube's limit.
+    pub fn playlist_description(&self) -> String {
+        let lines: Vec<String> = self
+            .episodes
+            .iter()
+            .map(|e| format!("{}. {} https://youtu.be/{}", e.number, e.title, e.video_id))
+            .collect();
+        let mut kept = 0;
+        let mut length = "Episodes:".chars().count();
+        for line in lines.iter().rev() {
+            length += line.chars().count() + 1;
+            if length > MAX_DESCRIPTION_CHARS {
+                break;
+            }
+            kept += 1;
+        }
+        let mut description = "Episodes:".to_string();
+        for line in &lines[lines.len() - kept..] {
+            description.push('\n');
+            description.push_str(line);
+        }
+        description
+    }
+}
+
+/// Title of episode `number` of `series`, e.g. `Let's Play #12: Boss fight`.
+/// Titles already carrying the prefix are left alone.
+pub fn episode_title(series: &str, number: u32, title: &str) -> String {
+    let prefix = format!("{} #{}: ", series, number);
+    if title.starts_with(&prefix) {
+        title.to_string()
+    } else {
+        format!("{}{}", prefix, title)
+    }
+}
+
+/// Every series uploaded to, kept in the state dir.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SeriesIndex {
+    #[serde(default)]
+    series: BTreeMap<String, Series>,
+}
+
+fn series_path(state_dir: &str) -> PathBuf {
+    Path::new(&expand_tilde(state_dir)).join(SERIES_FILE)
+}
+
+impl SeriesIndex {
+    /// Loads the index, treating a missing file as empty.
+    pub fn load(state_dir: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let path = series_path(state_dir);
+        match fs::read_to_string(&path) {
+            Ok(content) => Ok(serde_json::from_str(&content)
+                .map_err(|e| format!("Failed to parse '{}': {}", path.display(), e))?),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(format!("Failed to read '{}': {}", path.display(), e).into()),
+        }
+    }
+
+    pub fn save(&self, state_dir: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let path = series_path(state_dir);
+        fs::create_dir_all(path.parent().unwrap_or(Path::new(".")))?;
+        fs::write(&path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// The series called `name`, empty if nothing was uploaded to it yet.
+    pub fn get(&self, name: &str) -> Series {
+        self.series.get(name).cloned().unwrap_or_default()
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> &mut Series {
+        self.series.entry(name.to_string()).or_default()
+    }
+}
@@ -0,0 +1,71 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::expand_tilde;
+
+const SHARED_SCHEDULE_FILE: &str = "shared_schedule.json";
+
+/// Publish time of a video uploaded with `--min-spacing`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScheduledVideo {
+    pub profile: String,
+    pub file: String,
+    pub video_id: String,
+    pub publish_at: DateTime<Utc>,
+}
+
+/// Upcoming publish times of every profile using the state dir, so
+/// `--min-spacing` can keep them apart across channels.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SharedSchedule {
+    #[serde(default)]
+    videos: Vec<ScheduledVideo>,
+}
+
+fn shared_schedule_path(state_dir: &str) -> PathBuf {
+    Path::new(&expand_tilde(state_dir)).join(SHARED_SCHEDULE_FILE)
+}
+
+impl SharedSchedule {
+    /// Loads the schedule, treating a missing file as empty.
+    pub fn load(state_dir: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let path = shared_schedule_path(state_dir);
+        match fs::read_to_string(&path) {
+            Ok(content) => Ok(serde_json::from_str(&content)
+                .map_err(|e| format!("Failed to parse '{}': {}", path.display(), e))?),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(format!("Failed to read '{}': {}", path.display(), e).into()),
+        }
+    }
+
+    /// Adds a video, replacing an earlier entry of the profile for the same
+    /// video, and drops videos published before `now`.
+    pub fn record(
+        state_dir: &str,
+        video: ScheduledVideo,
+        now: DateTime<Utc>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut schedule = Self::load(state_dir)?;
+        schedule.videos.retain(|v| {
+            (v.profile != video.profile || v.video_id != video.video_id) && v.publish_at >= now
+        });
+        schedule.videos.push(video);
+
+        let path = shared_schedule_path(state_dir);
+        fs::create_dir_all(path.parent().unwrap_or(Path::new(".")))?;
+        fs::write(&path, serde_json::to_string_pretty(&schedule)?)?;
+        Ok(())
+    }
+
+    /// Publish times of every profile but `profile`.
+    pub fn other_profiles(&self, profile: &str) -> Vec<DateTime<Utc>> {
+        self.videos
+            .iter()
+            .filter(|v| v.profile != profile)
+            .map(|v| v.publish_at)
+            .collect()
+    }
+}
@@ -0,0 +1,459 @@
+use rand::Rng;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::youtube::{CAPTION_QUOTA_COST, UPLOAD_QUOTA_COST};
+
+/// Quota units YouTube charges for list calls
+const LIST_COST: u64 = 1;
+/// Quota units YouTube charges for updates and inserts other than uploads
+const WRITE_COST: u64 = 50;
+/// ID of the channel every simulated token belongs to
+pub const SIMULATED_CHANNEL: &str = "UCsimulated";
+
+static BASE_URL: OnceLock<String> = OnceLock::new();
+
+/// Behaviour of the simulated API from the `simulator` section of the
+/// config file.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SimulatorConfig {
+    /// Delay before every response
+    pub latency_ms: u64,
+    /// Share of requests answered with a server error, from 0.0 to 1.0
+    pub failure_rate: f64,
+    /// Daily quota in units; uploads cost 1600, captions 400, other writes
+    /// 50 and list calls 1
+    pub quota: u64,
+}
+
+impl Default for SimulatorConfig {
+    fn default() -> Self {
+        Self {
+            latency_ms: 200,
+            failure_rate: 0.0,
+            quota: 10_000,
+        }
+    }
+}
+
+struct Request {
+    method: String,
+    path: String,
+    query: String,
+    content_range: Option<String>,
+    body: Vec<u8>,
+}
+
+impl Request {
+    /// Whether this chunk reaches the announced total, e.g.
+    /// `bytes 0-99/100` or `bytes */0`.
+    fn completes_upload(&self) -> bool {
+        let Some(range) = &self.content_range else {
+            return false;
+        };
+        let Some((range, total)) = range.trim_start_matches("bytes ").split_once('/') else {
+            return false;
+        };
+        let Ok(total) = total.parse::<u64>() else {
+            return false;
+        };
+        match range.split_once('-') {
+            Some((_, last)) => last.parse::<u64>().is_ok_and(|last| last + 1 == total),
+            // A status query of a session that got bytes still pending
+            None => range == "*" && total == 0,
+        }
+    }
+}
+
+struct Response {
+    status: u16,
+    location: Option<String>,
+    body: Value,
+}
+
+struct SimulatedVideo {
+    id: String,
+    title: String,
+    publish_at: Option<String>,
+    privacy: String,
+}
+
+#[derive(Default)]
+struct State {
+    quota_used: u64,
+    next_id: u64,
+    videos: Vec<SimulatedVideo>,
+    /// Metadata of open resumable sessions by session number
+    sessions: HashMap<u64, Value>,
+    /// Playlists as playlists.list returns them
+    playlists: Vec<Value>,
+}
+
+impl State {
+    /// Stores a video like videos.insert and answers with its resource.
+    fn insert_video(&mut self, metadata: &Value) -> Response {
+        self.next_id += 1;
+        let video = SimulatedVideo {
+            id: format!("sim{:08}", self.next_id),
+            title: metadata["snippet"]["title"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string(),
+            publish_at: metadata["status"]["publishAt"].as_str().map(str::to_string),
+            privacy: metadata["status"]["privacyStatus"]
+                .as_str()
+                .unwrap_or("private")
+                .to_string(),
+        };
+        let response = ok(json!({
+            "id": video.id,
+            "snippet": { "title": video.title },
+            "status": {
+                "uploadStatus": "uploaded",
+                "privacyStatus": video.privacy,
+                "publishAt": video.publish_at
+            }
+        }));
+        self.videos.push(video);
+        response
+    }
+}
+
+/// Starts the fake API on a local port for the rest of the process.
+/// Requests go there instead of Google once `base_url` returns it.
+pub async fn start(config: SimulatorConfig) -> io::Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let _ = BASE_URL.set(format!("http://{}", listener.local_addr()?));
+    let state = Arc::new(Mutex::new(State::default()));
+
+    tokio::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    tokio::spawn(serve(stream, config.clone(), state.clone()));
+                }
+                Err(e) => crate::debug!("simulator accept failed: {}", e),
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Address of the running simulator, if `start` was called.
+pub fn base_url() -> Option<&'static str> {
+    BASE_URL.get().map(String::as_str)
+}
+
+async fn serve(stream: TcpStream, config: SimulatorConfig, state: Arc<Mutex<State>>) {
+    let mut stream = BufReader::new(stream);
+    while let Ok(Some(request)) = read_request(&mut stream).await {
+        tokio::time::sleep(Duration::from_millis(config.latency_ms)).await;
+        let response = respond(&request, &config, &state);
+        crate::verbose!(
+            "[simulator] {} {} -> {}",
+            request.method,
+            request.path,
+            response.status
+        );
+        if write_response(stream.get_mut(), &response).await.is_err() {
+            break;
+        }
+    }
+}
+
+async fn read_request(stream: &mut BufReader<TcpStream>) -> io::Result<Option<Request>> {
+    let mut line = String::new();
+    if stream.read_line(&mut line).await? == 0 {
+        return Ok(None);
+    }
+    let mut parts = line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let target = parts.next().unwrap_or_default();
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let (path, query) = (path.to_string(), query.to_string());
+
+    let mut content_length = 0;
+    let mut content_range = None;
+    let mut chunked = false;
+    loop {
+        line.clear();
+        if stream.read_line(&mut line).await? == 0 {
+            return Ok(None);
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            match name.to_ascii_lowercase().as_str() {
+                "content-length" => content_length = value.trim().parse().unwrap_or(0),
+                "transfer-encoding" => chunked = value.contains("chunked"),
+                "content-range" => content_range = Some(value.trim().to_string()),
+                _ => {}
+            }
+        }
+    }
+
+    let mut body = Vec::new();
+    if chunked {
+        loop {
+            line.clear();
+            stream.read_line(&mut line).await?;
+            let size = usize::from_str_radix(line.trim(), 16)
+                .map_err(|_| io::Error::other("invalid chunk size"))?;
+            let mut chunk = vec![0; size + 2];
+            stream.read_exact(&mut chunk).await?;
+            if size == 0 {
+                break;
+            }
+            body.extend_from_slice(&chunk[..size]);
+        }
+    } else {
+        body.resize(content_length, 0);
+        stream.read_exact(&mut body).await?;
+    }
+
+    Ok(Some(Request {
+        method,
+        path,
+        query,
+        content_range,
+        body,
+    }))
+}
+
+async fn write_response(stream: &mut TcpStream, response: &Response) -> io::Result<()> {
+    let body = response.body.to_string();
+    let mut head = format!(
+        "HTTP/1.1 {} Simulated\r\nContent-Type: application/json\r\nContent-Length: {}\r\n",
+        response.status,
+        body.len()
+    );
+    if let Some(location) = &response.location {
+        head.push_str(&format!("Location: {}\r\n", location));
+    }
+    head.push_str("\r\n");
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(body.as_bytes()).await?;
+    stream.flush().await
+}
+
+/// An error body shaped like Google's, so the reason maps to the same
+/// `UploadError` a real response would.
+fn api_error(status: u16, reason: &str, message: &str) -> Response {
+    Response {
+        status,
+        location: None,
+        body: json!({
+            "error": {
+                "code": status,
+                "message": message,
+                "errors": [{ "reason": reason, "message": message }]
+            }
+        }),
+    }
+}
+
+fn ok(body: Value) -> Response {
+    Response {
+        status: 200,
+        location: None,
+        body,
+    }
+}
+
+/// Reads the JSON metadata part of a multipart upload.
+fn upload_metadata(body: &[u8]) -> Value {
+    let head = String::from_utf8_lossy(&body[..body.len().min(64 * 1024)]);
+    head.find("\r\n\r\n")
+        .map(|start| &head[start + 4..])
+        .and_then(|part| part.split("\r\n--").next())
+        .and_then(|json| serde_json::from_str(json).ok())
+        .unwrap_or_default()
+}
+
+fn session_number(path: &str) -> u64 {
+    path.rsplit('/')
+        .next()
+        .and_then(|id| id.parse().ok())
+        .unwrap_or_default()
+}
+
+fn respond(request: &Request, config: &SimulatorConfig, state: &Mutex<State>) -> Response {
+    if rand::thread_rng().gen_bool(config.failure_rate.clamp(0.0, 1.0)) {
+        return api_error(500, "backendError", "Simulated server error");
+    }
+
+    let is_upload = request.method == "POST" && request.path == "/upload/youtube/v3/videos";
+    let cost = if is_upload {
+        UPLOAD_QUOTA_COST
+    } else if request.path == "/upload/youtube/v3/captions" {
+        CAPTION_QUOTA_COST
+    } else if request.method != "GET" && request.path.starts_with("/youtube/") {
+        WRITE_COST
+    } else if request.path.starts_with("/youtube/") {
+        LIST_COST
+    } else {
+        0
+    };
+    let mut state = state.lock().unwrap();
+    if state.quota_used + cost > config.quota {
+        return api_error(
+            403,
+            "quotaExceeded",
+            "The request cannot be completed because you have exceeded your quota.",
+        );
+    }
+    state.quota_used += cost;
+
+    match (request.method.as_str(), request.path.as_str()) {
+        ("POST", "/upload/youtube/v3/videos") if request.query.contains("uploadType=resumable") => {
+            state.next_id += 1;
+            let session = state.next_id;
+            let metadata = serde_json::from_slice(&request.body).unwrap_or_default();
+            state.sessions.insert(session, metadata);
+            Response {
+                status: 200,
+                location: Some(format!(
+                    "{}/upload/session/{}",
+                    base_url().unwrap_or_default(),
+                    session
+                )),
+                body: json!({}),
+            }
+        }
+        ("POST", "/upload/youtube/v3/videos") => {
+            state.insert_video(&upload_metadata(&request.body))
+        }
+        // The video is created with the last chunk, sessions of unknown size
+        // never complete
+        ("PUT", path) if path.starts_with("/upload/session/") => {
+            let session = session_number(path);
+            if !state.sessions.contains_key(&session) {
+                api_error(404, "notFound", "Unknown upload session")
+            } else if request.completes_upload() {
+                let metadata = state.sessions.remove(&session).unwrap_or_default();
+                state.insert_video(&metadata)
+            } else {
+                Response {
+                    status: 308,
+                    location: None,
+                    body: json!({}),
+                }
+            }
+        }
+        ("DELETE", path) if path.starts_with("/upload/session/") => {
+            state.sessions.remove(&session_number(path));
+            Response {
+                status: 499,
+                location: None,
+                body: json!({}),
+            }
+        }
+        ("GET", "/youtube/v3/channels") => ok(json!({
+            "items": [{
+                "id": SIMULATED_CHANNEL,
+                "snippet": { "title": "Simulated channel" },
+                "contentDetails": { "relatedPlaylists": { "uploads": "UUsimulated" } }
+            }]
+        })),
+        ("GET", "/youtube/v3/playlistItems") => ok(json!({
+            "items": state.videos.iter().rev().map(|video| json!({
+                "snippet": { "title": video.title, "resourceId": { "videoId": video.id } }
+            })).collect::<Vec<_>>()
+        })),
+        ("GET", "/youtube/v3/videos") => ok(json!({
+            "items": state.videos.iter()
+                .filter(|video| request.query.contains(&video.id))
+                .map(|video| json!({
+                    "id": video.id,
+                    "status": {
+                        "uploadStatus": "processed",
+                        "publishAt": video.publish_at,
+                        "privacyStatus": video.privacy
+                    },
+                    "processingDetails": { "processingStatus": "succeeded" }
+                }))
+                .collect::<Vec<_>>()
+        })),
+        ("PUT", "/youtube/v3/videos") => {
+            let update: Value = serde_json::from_slice(&request.body).unwrap_or_default();
+            let id = update["id"].as_str().unwrap_or_default();
+            match state.videos.iter_mut().find(|video| video.id == id) {
+                Some(video) if request.query.contains("part=snippet") => {
+                    if let Some(title) = update["snippet"]["title"].as_str() {
+                        video.title = title.to_string();
+                    }
+                    ok(json!({ "id": video.id, "snippet": update["snippet"] }))
+                }
+                Some(video) => {
+                    video.privacy = update["status"]["privacyStatus"]
+                        .as_str()
+                        .unwrap_or("private")
+                        .to_string();
+                    // Without a publishAt in the update the schedule is gone
+                    video.publish_at = update["status"]["publishAt"].as_str().map(str::to_string);
+                    ok(json!({
+                        "id": video.id,
+                        "status": { "privacyStatus": video.privacy, "publishAt": video.publish_at }
+                    }))
+                }
+                None => api_error(404, "videoNotFound", "Video not found"),
+            }
+        }
+        ("DELETE", "/youtube/v3/videos") => {
+            match state
+                .videos
+                .iter()
+                .position(|video| request.query.contains(&video.id))
+            {
+                Some(i) => {
+                    state.videos.remove(i);
+                    ok(json!({}))
+                }
+                None => api_error(404, "videoNotFound", "Video not found"),
+            }
+        }
+        ("POST", "/upload/youtube/v3/thumbnails/set") => ok(json!({
+            "items": [{ "default": { "url": "https://i.ytimg.com/simulated.jpg" } }]
+        })),
+        ("GET", "/youtube/v3/playlists") => ok(json!({ "items": state.playlists })),
+        ("POST", "/youtube/v3/playlists") => {
+            let body: Value = serde_json::from_slice(&request.body).unwrap_or_default();
+            state.next_id += 1;
+            let playlist =
+                json!({ "id": format!("PLsim{:08}", state.next_id), "snippet": body["snippet"] });
+            state.playlists.push(playlist.clone());
+            ok(playlist)
+        }
+        ("PUT", "/youtube/v3/playlists") => {
+            let body: Value = serde_json::from_slice(&request.body).unwrap_or_default();
+            match state.playlists.iter_mut().find(|p| p["id"] == body["id"]) {
+                Some(playlist) => {
+                    playlist["snippet"] = body["snippet"].clone();
+                    ok(playlist.clone())
+                }
+                None => api_error(404, "playlistNotFound", "Playlist not found"),
+            }
+        }
+        ("POST", "/upload/youtube/v3/captions") => {
+            let caption = upload_metadata(&request.body);
+            state.next_id += 1;
+            ok(json!({ "id": format!("sim{:08}", state.next_id), "snippet": caption["snippet"] }))
+        }
+        ("POST", "/youtube/v3/commentThreads" | "/youtube/v3/playlistItems") => {
+            let body: Value = serde_json::from_slice(&request.body).unwrap_or_default();
+            state.next_id += 1;
+            ok(json!({ "id": format!("sim{:08}", state.next_id), "snippet": body["snippet"] }))
+        }
+        (_, path) if path.starts_with("/calendar/v3/") => ok(json!({})),
+        _ => api_error(404, "notFound", "Not simulated"),
+    }
+}
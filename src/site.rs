@@ -0,0 +1,80 @@
+use chrono::{DateTime, SecondsFormat, Utc};
+use clap::ValueEnum;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::expand_tilde;
+use crate::links::slugify;
+use crate::timezone;
+use crate::youtube::VideoMetadata;
+
+/// Static site generator the posts are written for.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SiteFlavor {
+    /// `<slug>.md`, dated by the front matter
+    Hugo,
+    /// `YYYY-MM-DD-<slug>.md`, as Jekyll expects in `_posts`
+    Jekyll,
+}
+
+/// File name of the post for `file`, named after its stem so a re-upload
+/// replaces the post.
+pub fn post_file_name(flavor: SiteFlavor, file: &str, publish_at: DateTime<Utc>) -> String {
+    let stem = Path::new(file).file_stem().unwrap_or_default();
+    let slug = slugify(&stem.to_string_lossy());
+    match flavor {
+        SiteFlavor::Hugo => format!("{}.md", slug),
+        SiteFlavor::Jekyll => format!(
+            "{}-{}.md",
+            timezone::local_date(publish_at).format("%Y-%m-%d"),
+            slug
+        ),
+    }
+}
+
+/// Markdown post with YAML front matter, the description as its body.
+/// Strings are written as JSON, which YAML reads as quoted strings.
+pub fn render_post(metadata: &VideoMetadata, video_id: &str, publish_at: DateTime<Utc>) -> String {
+    let quote = |text: &str| serde_json::Value::from(text).to_string();
+    let mut post = String::from("---\n");
+    post.push_str(&format!("title: {}\n", quote(metadata.title())));
+    post.push_str(&format!(
+        "date: {}\n",
+        publish_at.to_rfc3339_opts(SecondsFormat::Secs, false)
+    ));
+    post.push_str(&format!("video_id: {}\n", quote(video_id)));
+    post.push_str(&format!(
+        "video_url: {}\n",
+        quote(&format!("https://youtu.be/{}", video_id))
+    ));
+    post.push_str(&format!(
+        "tags: {}\n",
+        serde_json::Value::from(metadata.tags())
+    ));
+    if let (Some(series), Some(episode)) = (metadata.series(), metadata.episode()) {
+        post.push_str(&format!("series: {}\n", quote(series)));
+        post.push_str(&format!("episode: {}\n", episode));
+    }
+    post.push_str("---\n\n");
+    if !metadata.description().is_empty() {
+        post.push_str(metadata.description());
+        post.push('\n');
+    }
+    post
+}
+
+/// Writes the post of an uploaded video into `dir`.
+pub fn write_post(
+    dir: &str,
+    flavor: SiteFlavor,
+    file: &str,
+    metadata: &VideoMetadata,
+    video_id: &str,
+    publish_at: DateTime<Utc>,
+) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let dir = PathBuf::from(expand_tilde(dir));
+    fs::create_dir_all(&dir)?;
+    let path = dir.join(post_file_name(flavor, file, publish_at));
+    fs::write(&path, render_post(metadata, video_id, publish_at))?;
+    Ok(path)
+}
@@ -0,0 +1,52 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use std::fs;
+
+use crate::expand_tilde;
+use crate::timezone;
+
+/// Days on which nothing may be published, e.g. holidays or vacations.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SkipDates {
+    /// Inclusive date ranges, single days have equal ends
+    ranges: Vec<(NaiveDate, NaiveDate)>,
+}
+
+impl SkipDates {
+    /// Reads one `YYYY-MM-DD` date or `YYYY-MM-DD..YYYY-MM-DD` range per
+    /// line. Blank lines and lines starting with `#` are ignored.
+    pub fn load(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let expanded_path = expand_tilde(path);
+        let content = fs::read_to_string(&expanded_path)
+            .map_err(|e| format!("Failed to read skip dates from '{}': {}", expanded_path, e))?;
+        Self::parse(&content).map_err(|e| format!("{}: {}", expanded_path, e).into())
+    }
+
+    pub fn parse(content: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut ranges = Vec::new();
+        for (number, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (from, to) = line.split_once("..").unwrap_or((line, line));
+            let parse = |date: &str| {
+                NaiveDate::parse_from_str(date.trim(), "%Y-%m-%d")
+                    .map_err(|e| format!("line {}: invalid date '{}': {}", number + 1, date, e))
+            };
+            let (from, to) = (parse(from)?, parse(to)?);
+            if to < from {
+                return Err(format!("line {}: range ends before it starts", number + 1).into());
+            }
+            ranges.push((from, to));
+        }
+        Ok(Self { ranges })
+    }
+
+    /// Whether `time` falls on a skipped day in the configured timezone.
+    pub fn contains(&self, time: DateTime<Utc>) -> bool {
+        let date = timezone::local_date(time);
+        self.ranges
+            .iter()
+            .any(|(from, to)| *from <= date && date <= *to)
+    }
+}
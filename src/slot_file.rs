@@ -0,0 +1,122 @@
+use chrono::{DateTime, NaiveDateTime, Utc};
+use std::fs;
+
+use crate::expand_tilde;
+use crate::timezone;
+
+/// Formats of local times in CSV files, tried after RFC 3339
+const LOCAL_FORMATS: [&str; 3] = ["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M"];
+
+/// Reads publish slots from an iCalendar file, taking the start of every
+/// event, or from a CSV file with a date and time in the first column.
+pub fn load(path: &str) -> Result<Vec<DateTime<Utc>>, Box<dyn std::error::Error>> {
+    let expanded_path = expand_tilde(path);
+    let content = fs::read_to_string(&expanded_path)
+        .map_err(|e| format!("Failed to read slots from '{}': {}", expanded_path, e))?;
+    let slots = if content.trim_start().starts_with("BEGIN:VCALENDAR") {
+        parse_ics(&content)
+    } else {
+        parse_csv(&content)
+    };
+    slots.map_err(|e| format!("{}: {}", expanded_path, e).into())
+}
+
+/// `DTSTART` of every `VEVENT`. Times without a zone and times in the
+/// zone of `--timezone` are local, other zones are refused since only the
+/// configured one can be resolved.
+pub fn parse_ics(content: &str) -> Result<Vec<DateTime<Utc>>, Box<dyn std::error::Error>> {
+    // Long lines continue on lines starting with a space or tab
+    let mut lines: Vec<String> = Vec::new();
+    for line in content.lines() {
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(rest), Some(last)) => last.push_str(rest),
+            _ => lines.push(line.to_string()),
+        }
+    }
+
+    let mut slots = Vec::new();
+    let mut in_event = false;
+    for line in &lines {
+        if line == "BEGIN:VEVENT" {
+            in_event = true;
+        } else if line == "END:VEVENT" {
+            in_event = false;
+        }
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        let mut params = name.split(';');
+        if !in_event || params.next() != Some("DTSTART") {
+            continue;
+        }
+        let mut zone = None;
+        for param in params {
+            match param.split_once('=') {
+                Some(("VALUE", "DATE")) => {
+                    return Err(format!(
+                        "all-day event on {} has no publish time, give it a start time",
+                        value
+                    )
+                    .into())
+                }
+                Some(("TZID", tzid)) => zone = Some(tzid),
+                _ => {}
+            }
+        }
+        if let Some(zone) = zone {
+            if timezone::name() != Some(zone) {
+                return Err(format!(
+                    "event at {} is in {}, pass --timezone {} to use it",
+                    value, zone, zone
+                )
+                .into());
+            }
+        }
+        let slot = match value.strip_suffix('Z') {
+            Some(utc) => NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S")
+                .ok()
+                .map(|time| time.and_utc()),
+            None => NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S")
+                .ok()
+                .and_then(timezone::from_local),
+        };
+        slots.push(slot.ok_or_else(|| format!("invalid DTSTART '{}'", value))?);
+    }
+    Ok(slots)
+}
+
+/// First column of every line: an RFC 3339 time, or a local one like
+/// `2024-06-01 18:00`. A header line, blank lines and lines starting with
+/// `#` are skipped.
+pub fn parse_csv(content: &str) -> Result<Vec<DateTime<Utc>>, Box<dyn std::error::Error>> {
+    let mut slots = Vec::new();
+    let mut header = true;
+    for (number, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let field = line.split(',').next().unwrap_or_default().trim();
+        let field = field.trim_matches('"');
+        let slot = DateTime::parse_from_rfc3339(field)
+            .map(|time| time.with_timezone(&Utc))
+            .ok()
+            .or_else(|| {
+                LOCAL_FORMATS
+                    .iter()
+                    .find_map(|format| NaiveDateTime::parse_from_str(field, format).ok())
+                    .and_then(timezone::from_local)
+            });
+        match slot {
+            Some(slot) => slots.push(slot),
+            None if header => {}
+            None => {
+                return Err(
+                    format!("line {}: invalid date and time '{}'", number + 1, field).into(),
+                )
+            }
+        }
+        header = false;
+    }
+    Ok(slots)
+}
@@ -0,0 +1,122 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Whether the cached file at `path` exists. Marks it as used, so `clean`
+/// running next to this process leaves it alone.
+pub fn reuse(path: &Path) -> bool {
+    match fs::File::options().append(true).open(path) {
+        Ok(file) => {
+            let _ = file.set_modified(SystemTime::now());
+            true
+        }
+        Err(_) => path.exists(),
+    }
+}
+
+/// Name to write `output` under until it is complete. Unique per process,
+/// so parallel runs preparing the same file don't write into each other.
+pub fn partial_path(output: &Path) -> PathBuf {
+    let extension = output.extension().unwrap_or_default().to_string_lossy();
+    output.with_extension(format!("partial-{}.{}", std::process::id(), extension))
+}
+
+/// Files written to the cache dir for one video. Removed once the video is
+/// uploaded, files of failed steps stay for the retry and to look into.
+#[derive(Debug)]
+pub struct Artifacts {
+    cache_dir: PathBuf,
+    files: Vec<PathBuf>,
+}
+
+impl Artifacts {
+    pub fn new(cache_dir: PathBuf) -> Self {
+        Self {
+            cache_dir,
+            files: Vec::new(),
+        }
+    }
+
+    /// Adds `path` when it is in the cache dir, source files are never
+    /// removed.
+    pub fn add(&mut self, path: &Path) {
+        if path.starts_with(&self.cache_dir) && !self.files.iter().any(|f| f == path) {
+            self.files.push(path.to_path_buf());
+        }
+    }
+
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    pub fn remove(self) {
+        for file in &self.files {
+            match fs::remove_file(file) {
+                Ok(()) => crate::verbose!("Removed {}", file.display()),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => crate::warning!("Could not remove {}: {}", file.display(), e),
+            }
+        }
+    }
+}
+
+/// What `clean` removed.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct CleanSummary {
+    pub files: usize,
+    pub bytes: u64,
+}
+
+/// Removes the files in `cache_dir` and its folders last used before
+/// `cutoff`, along with folders left empty, or with `dry_run` only counts
+/// them.
+pub fn clean(
+    cache_dir: &Path,
+    cutoff: SystemTime,
+    dry_run: bool,
+) -> Result<CleanSummary, Box<dyn std::error::Error>> {
+    let mut summary = CleanSummary::default();
+    clean_dir(cache_dir, cutoff, dry_run, &mut summary)?;
+    Ok(summary)
+}
+
+fn clean_dir(
+    dir: &Path,
+    cutoff: SystemTime,
+    dry_run: bool,
+    summary: &mut CleanSummary,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => {
+            return Err(format!("Failed to read directory '{}': {}", dir.display(), e).into())
+        }
+    };
+    for entry in entries {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if metadata.is_dir() {
+            clean_dir(&entry.path(), cutoff, dry_run, summary)?;
+            if !dry_run {
+                // Fails while files are left, which is fine
+                let _ = fs::remove_dir(entry.path());
+            }
+            continue;
+        }
+        if !metadata.is_file() || metadata.modified()? >= cutoff {
+            continue;
+        }
+        if dry_run {
+            crate::verbose!("Would remove {}", entry.path().display());
+        } else {
+            fs::remove_file(entry.path())
+                .map_err(|e| format!("Failed to remove '{}': {}", entry.path().display(), e))?;
+            crate::verbose!("Removed {}", entry.path().display());
+        }
+        summary.files += 1;
+        summary.bytes += metadata.len();
+    }
+    Ok(())
+}
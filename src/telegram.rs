@@ -0,0 +1,233 @@
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::control::PauseControl;
+use crate::redact;
+use crate::report::BatchProgress;
+
+const API_URL: &str = "https://api.telegram.org";
+const POLL_TIMEOUT_SECS: u64 = 30;
+
+/// Bot settings from the `telegram` section of the config file.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TelegramConfig {
+    /// Falls back to the YT_UPLOAD_TELEGRAM_TOKEN environment variable
+    pub bot_token: Option<String>,
+    /// Chat that receives upload notifications
+    pub chat_id: i64,
+    /// User IDs allowed to send commands, commands are ignored when empty
+    #[serde(default)]
+    pub allowed_users: Vec<i64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BotCommand {
+    Status,
+    Pause,
+    Resume,
+    RetryFailed,
+}
+
+const HELP: &str = "Commands: /status, /pause, /resume, /retry (failed uploads)";
+
+/// Parses `/status`, `/status@my_bot` or a bare `status`.
+pub fn parse_command(text: &str) -> Option<BotCommand> {
+    let word = text.split_whitespace().next()?;
+    let word = word.strip_prefix('/').unwrap_or(word);
+    let name = word.split('@').next().unwrap_or(word).to_lowercase();
+    match name.as_str() {
+        "status" => Some(BotCommand::Status),
+        "pause" => Some(BotCommand::Pause),
+        "resume" => Some(BotCommand::Resume),
+        "retry" | "retry_failed" => Some(BotCommand::RetryFailed),
+        _ => None,
+    }
+}
+
+#[derive(Deserialize)]
+struct ApiResponse<T> {
+    ok: bool,
+    description: Option<String>,
+    result: Option<T>,
+}
+
+#[derive(Deserialize)]
+struct Update {
+    update_id: i64,
+    message: Option<Message>,
+}
+
+#[derive(Deserialize)]
+struct Message {
+    from: Option<User>,
+    chat: Chat,
+    text: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct User {
+    id: i64,
+}
+
+#[derive(Deserialize)]
+struct Chat {
+    id: i64,
+}
+
+#[derive(Clone)]
+pub struct TelegramBot {
+    client: reqwest::Client,
+    token: String,
+    chat_id: i64,
+    allowed_users: Vec<i64>,
+}
+
+impl TelegramBot {
+    pub fn new(config: &TelegramConfig) -> Result<Self, Box<dyn std::error::Error>> {
+        let token = config
+            .bot_token
+            .clone()
+            .or_else(|| std::env::var("YT_UPLOAD_TELEGRAM_TOKEN").ok())
+            .ok_or("No Telegram bot token in config or YT_UPLOAD_TELEGRAM_TOKEN")?;
+        // Request errors show the URL, which holds the token
+        redact::register(&token);
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(POLL_TIMEOUT_SECS + 10))
+            .build()?;
+        Ok(Self {
+            client,
+            token,
+            chat_id: config.chat_id,
+            allowed_users: config.allowed_users.clone(),
+        })
+    }
+
+    async fn call<T: serde::de::DeserializeOwned>(
+        &self,
+        method: &str,
+        body: serde_json::Value,
+    ) -> Result<T, Box<dyn std::error::Error>> {
+        let response: ApiResponse<T> = self
+            .client
+            .post(format!("{}/bot{}/{}", API_URL, self.token, method))
+            .json(&body)
+            .send()
+            .await?
+            .json()
+            .await?;
+        match response.result {
+            Some(result) if response.ok => Ok(result),
+            _ => Err(format!(
+                "Telegram {} failed: {}",
+                method,
+                response.description.unwrap_or_default()
+            )
+            .into()),
+        }
+    }
+
+    pub async fn send_message(
+        &self,
+        chat_id: i64,
+        text: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.call::<serde_json::Value>("sendMessage", json!({ "chat_id": chat_id, "text": text }))
+            .await?;
+        Ok(())
+    }
+
+    /// Sends `text` to the notification chat, warning instead of failing.
+    pub async fn notify(&self, text: &str) {
+        if let Err(e) = self.send_message(self.chat_id, &redact::redact(text)).await {
+            crate::warning!("Could not send Telegram notification: {}", e);
+        }
+    }
+
+    async fn get_updates(
+        &self,
+        offset: i64,
+        timeout: u64,
+    ) -> Result<Vec<Update>, Box<dyn std::error::Error>> {
+        self.call(
+            "getUpdates",
+            json!({ "offset": offset, "timeout": timeout, "allowed_updates": ["message"] }),
+        )
+        .await
+    }
+
+    /// Answers commands from allow-listed users for the rest of the process.
+    /// Messages sent before the batch started are skipped.
+    pub fn listen(&self, control: PauseControl, progress: Arc<Mutex<BatchProgress>>) {
+        if self.allowed_users.is_empty() {
+            crate::verbose!("No allowed_users configured, Telegram commands are disabled");
+            return;
+        }
+
+        let bot = self.clone();
+        tokio::spawn(async move {
+            let mut offset = bot
+                .get_updates(-1, 0)
+                .await
+                .ok()
+                .and_then(|updates| Some(updates.last()?.update_id + 1))
+                .unwrap_or(0);
+            loop {
+                // Box<dyn Error> isn't Send, so keep only the message across the sleep
+                let polled = bot
+                    .get_updates(offset, POLL_TIMEOUT_SECS)
+                    .await
+                    .map_err(|e| e.to_string());
+                let updates = match polled {
+                    Ok(updates) => updates,
+                    Err(e) => {
+                        crate::debug!("Telegram polling failed: {}", e);
+                        tokio::time::sleep(Duration::from_secs(5)).await;
+                        continue;
+                    }
+                };
+
+                for update in updates {
+                    offset = update.update_id + 1;
+                    let Some(message) = update.message else {
+                        continue;
+                    };
+                    if !message
+                        .from
+                        .is_some_and(|from| bot.allowed_users.contains(&from.id))
+                    {
+                        crate::debug!("Ignoring Telegram message from unknown user");
+                        continue;
+                    }
+
+                    let reply = match message.text.as_deref().and_then(parse_command) {
+                        Some(BotCommand::Status) => {
+                            progress.lock().unwrap().status(control.is_paused())
+                        }
+                        Some(BotCommand::Pause) => {
+                            crate::info!("Pause requested via Telegram");
+                            control.pause();
+                            "Pausing after the current video".to_string()
+                        }
+                        Some(BotCommand::Resume) => {
+                            crate::info!("Resume requested via Telegram");
+                            control.resume();
+                            "Resuming".to_string()
+                        }
+                        Some(BotCommand::RetryFailed) => {
+                            let failed = progress.lock().unwrap().failed_indices().len();
+                            control.request_retry();
+                            format!("Queued {} failed videos for another attempt", failed)
+                        }
+                        None => HELP.to_string(),
+                    };
+                    let sent = bot.send_message(message.chat.id, &reply).await;
+                    if let Err(e) = sent.map_err(|e| e.to_string()) {
+                        crate::debug!("Telegram reply failed: {}", e);
+                    }
+                }
+            }
+        });
+    }
+}
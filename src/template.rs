@@ -0,0 +1,104 @@
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::sanitize::{apply_case_style, CaseStyle};
+use crate::timezone;
+
+/// Formats of dates and times the `format` filter reads, tried after RFC 3339
+const DATE_FORMATS: [&str; 2] = ["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M"];
+
+/// Replaces `{name}` placeholders with their values. Unknown placeholders
+/// are left untouched so typos stay visible in the output.
+///
+/// Values pass through filters separated by `|`, e.g. `{stem|titlecase}`,
+/// `{index|pad:3}`, `{episode|add:100}` or `{publish_date|format:%d %b}`.
+/// A placeholder with an unknown filter, or one its value doesn't suit, is
+/// left untouched as well.
+pub fn render_template(template: &str, values: &HashMap<&str, String>) -> String {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        rendered.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        match after.find('}') {
+            Some(end) => {
+                let placeholder = &after[..end];
+                match render_placeholder(placeholder, values) {
+                    Some(value) => rendered.push_str(&value),
+                    None => {
+                        rendered.push('{');
+                        rendered.push_str(placeholder);
+                        rendered.push('}');
+                    }
+                }
+                rest = &after[end + 1..];
+            }
+            None => {
+                rendered.push_str(&rest[start..]);
+                rest = "";
+            }
+        }
+    }
+    rendered.push_str(rest);
+
+    rendered
+}
+
+fn render_placeholder(placeholder: &str, values: &HashMap<&str, String>) -> Option<String> {
+    let mut filters = placeholder.split('|');
+    let name = filters.next()?.trim();
+    filters.try_fold(values.get(name)?.clone(), |value, filter| {
+        let (filter, arg) = match filter.split_once(':') {
+            Some((filter, arg)) => (filter.trim(), Some(arg)),
+            None => (filter.trim(), None),
+        };
+        apply_filter(&value, filter, arg)
+    })
+}
+
+/// `None` for unknown filters and values or arguments that don't fit.
+fn apply_filter(value: &str, filter: &str, arg: Option<&str>) -> Option<String> {
+    let number = |text: &str| text.trim().parse::<i64>().ok();
+    match (filter, arg) {
+        ("upper", None) => Some(value.to_uppercase()),
+        ("lower", None) => Some(value.to_lowercase()),
+        ("titlecase", None) => Some(apply_case_style(value, CaseStyle::Title)),
+        ("trim", None) => Some(value.trim().to_string()),
+        ("pad", Some(width)) => {
+            let width = width.trim().parse::<usize>().ok()?;
+            Some(format!("{:0>width$}", value, width = width))
+        }
+        ("add", Some(arg)) => Some(number(value)?.checked_add(number(arg)?)?.to_string()),
+        ("sub", Some(arg)) => Some(number(value)?.checked_sub(number(arg)?)?.to_string()),
+        ("format", Some(format)) => format_date(value, format),
+        _ => None,
+    }
+}
+
+/// `value` as an RFC 3339 time, shown in the configured timezone, or as a
+/// local date with or without a time, formatted with strftime `format`.
+fn format_date(value: &str, format: &str) -> Option<String> {
+    let value = value.trim();
+    let time = DateTime::parse_from_rfc3339(value)
+        .ok()
+        .map(|time| {
+            let time = time.with_timezone(&Utc);
+            timezone::local_date(time).and_time(timezone::local_time(time))
+        })
+        .or_else(|| {
+            DATE_FORMATS
+                .iter()
+                .find_map(|f| NaiveDateTime::parse_from_str(value, f).ok())
+        })
+        .or_else(|| {
+            NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .ok()
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+        })?;
+    // Invalid specifiers make formatting fail rather than panic here
+    let mut formatted = String::new();
+    write!(formatted, "{}", time.format(format)).ok()?;
+    Some(formatted)
+}
@@ -0,0 +1,138 @@
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::ffmpeg;
+use crate::spool;
+
+/// Largest thumbnail YouTube accepts
+pub const MAX_THUMBNAIL_BYTES: u64 = 2 * 1024 * 1024;
+/// Size YouTube displays thumbnails at, larger ones are scaled down to it
+const MAX_WIDTH: u32 = 1280;
+const MAX_HEIGHT: u32 = 720;
+const MIN_WIDTH: u32 = 640;
+
+/// What the header of an image file says about it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageInfo {
+    /// `jpeg`, `png` or `gif`, the formats YouTube takes, otherwise `None`
+    pub format: Option<&'static str>,
+    pub width: u32,
+    pub height: u32,
+    pub bytes: u64,
+}
+
+impl ImageInfo {
+    /// Reasons YouTube would reject the image.
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.format.is_none() {
+            problems.push("not a JPEG, PNG or GIF".to_string());
+        }
+        if self.bytes > MAX_THUMBNAIL_BYTES {
+            problems.push(format!("{} bytes, more than 2 MB", self.bytes));
+        }
+        problems
+    }
+}
+
+/// Reads format and dimensions from the header of `path`.
+pub fn inspect(path: &Path) -> Result<ImageInfo, Box<dyn std::error::Error>> {
+    let data = fs::read(path)
+        .map_err(|e| format!("Failed to read thumbnail '{}': {}", path.display(), e))?;
+    let (format, (width, height)) = if data.starts_with(b"\x89PNG\r\n\x1a\n") && data.len() >= 24 {
+        let be =
+            |at: usize| u32::from_be_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]]);
+        (Some("png"), (be(16), be(20)))
+    } else if data.starts_with(b"GIF8") && data.len() >= 10 {
+        let le = |at: usize| u16::from_le_bytes([data[at], data[at + 1]]) as u32;
+        (Some("gif"), (le(6), le(8)))
+    } else if data.starts_with(&[0xff, 0xd8]) {
+        (Some("jpeg"), jpeg_dimensions(&data).unwrap_or_default())
+    } else {
+        (None, (0, 0))
+    };
+    Ok(ImageInfo {
+        format,
+        width,
+        height,
+        bytes: data.len() as u64,
+    })
+}
+
+/// Dimensions from the first start-of-frame segment of a JPEG.
+fn jpeg_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    let mut at = 2;
+    while at + 9 < data.len() {
+        if data[at] != 0xff {
+            return None;
+        }
+        let marker = data[at + 1];
+        let length = u16::from_be_bytes([data[at + 2], data[at + 3]]) as usize;
+        // SOF0 to SOF15, except DHT, JPG and DAC which share the range
+        if (0xc0..=0xcf).contains(&marker) && ![0xc4, 0xc8, 0xcc].contains(&marker) {
+            let height = u16::from_be_bytes([data[at + 5], data[at + 6]]) as u32;
+            let width = u16::from_be_bytes([data[at + 7], data[at + 8]]) as u32;
+            return Some((width, height));
+        }
+        at += 2 + length;
+    }
+    None
+}
+
+/// A thumbnail YouTube accepts made from `image`: the image itself when it
+/// already qualifies, otherwise a JPEG re-encoded with ffmpeg, scaled down
+/// to 1280x720 and compressed until it fits in 2 MB.
+pub fn prepare(image: &Path, cache_dir: &Path) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let info = inspect(image)?;
+    let problems = info.problems();
+    if info.width > 0 && info.width < MIN_WIDTH {
+        crate::warning!(
+            "thumbnail {} is {} pixels wide, YouTube recommends at least {}",
+            image.display(),
+            info.width,
+            MIN_WIDTH
+        );
+    }
+    if problems.is_empty() {
+        return Ok(image.to_path_buf());
+    }
+    if !ffmpeg::ffmpeg_available() {
+        return Err(format!(
+            "Thumbnail '{}' is {}, fixing it requires ffmpeg in PATH",
+            image.display(),
+            problems.join(" and ")
+        )
+        .into());
+    }
+
+    let image_path = image.to_string_lossy();
+    let key = ffmpeg::cache_key(&[&image_path], "thumbnail-fit")?;
+    let stem = image.file_stem().unwrap_or_default().to_string_lossy();
+    let output = cache_dir.join(format!("{}-{}.jpg", stem, key));
+    if spool::reuse(&output) {
+        return Ok(output);
+    }
+    fs::create_dir_all(cache_dir)?;
+    let scale = format!(
+        "scale='min({},iw)':'min({},ih)':force_original_aspect_ratio=decrease",
+        MAX_WIDTH, MAX_HEIGHT
+    );
+    for quality in [2, 5, 10, 20, 31] {
+        let quality = quality.to_string();
+        let mut args: Vec<OsString> = ["-i", &image_path, "-vf", &scale, "-q:v", &quality]
+            .map(OsString::from)
+            .to_vec();
+        args.push(output.clone().into());
+        ffmpeg::run_ffmpeg(&args)?;
+        if fs::metadata(&output)?.len() <= MAX_THUMBNAIL_BYTES {
+            crate::verbose!(
+                "Re-encoded thumbnail {} to fit YouTube's limits",
+                image.display()
+            );
+            return Ok(output);
+        }
+    }
+    let _ = fs::remove_file(&output);
+    Err(format!("Thumbnail '{}' does not fit in 2 MB", image.display()).into())
+}
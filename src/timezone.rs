@@ -0,0 +1,110 @@
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Timelike, Utc};
+use chrono_tz::Tz;
+use std::sync::OnceLock;
+
+/// Local hours no one schedules a publish for on purpose
+pub const NIGHT_HOURS: std::ops::Range<u32> = 0..6;
+
+/// Named timezone of this process, set by `init`.
+static ZONE: OnceLock<Tz> = OnceLock::new();
+
+/// Uses the IANA timezone `name` (e.g. `Europe/Berlin`) for local times
+/// for the rest of the process. Call once at startup.
+pub fn init(name: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let zone: Tz = name.parse().map_err(|_| {
+        format!(
+            "Unknown timezone '{}', expected a name like Europe/Berlin",
+            name
+        )
+    })?;
+    let _ = ZONE.set(zone);
+    Ok(())
+}
+
+/// The timezone given to `init`, if any.
+pub fn name() -> Option<&'static str> {
+    ZONE.get().map(|zone| zone.name())
+}
+
+/// Calendar date of `time` in the configured timezone, or in UTC without one.
+pub fn local_date(time: DateTime<Utc>) -> NaiveDate {
+    match ZONE.get() {
+        Some(zone) => time.with_timezone(zone).date_naive(),
+        None => time.date_naive(),
+    }
+}
+
+/// Time of day of `time` in the configured timezone, or in UTC without one.
+pub fn local_time(time: DateTime<Utc>) -> NaiveTime {
+    match ZONE.get() {
+        Some(zone) => time.with_timezone(zone).time(),
+        None => time.time(),
+    }
+}
+
+/// The UTC time of `local` in the configured timezone, or of `local` as UTC
+/// without one. `None` for wall-clock times a DST change skips.
+pub fn from_local(local: NaiveDateTime) -> Option<DateTime<Utc>> {
+    match ZONE.get() {
+        Some(zone) => zone
+            .from_local_datetime(&local)
+            .earliest()
+            .map(|time| time.with_timezone(&Utc)),
+        None => Some(local.and_utc()),
+    }
+}
+
+/// Label of local times: the configured timezone, or UTC without one.
+pub fn label() -> &'static str {
+    name().unwrap_or("UTC")
+}
+
+/// `time` as seen from `now` in local time, e.g. `in 3 days, Friday 18:00
+/// Europe/Berlin`.
+pub fn describe_relative(time: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    let until = time - now;
+    let days = (local_date(time) - local_date(now)).num_days();
+    let when = if until < Duration::zero() {
+        "already past".to_string()
+    } else if until < Duration::hours(1) {
+        format!("in {} min", until.num_minutes())
+    } else if days == 0 {
+        "today".to_string()
+    } else if days == 1 {
+        "tomorrow".to_string()
+    } else {
+        format!("in {} days", days)
+    };
+    format!(
+        "{}, {} {} {}",
+        when,
+        local_date(time).format("%A"),
+        local_time(time).format("%H:%M"),
+        label()
+    )
+}
+
+/// Whether `time` falls in the local night, where a schedule only ends
+/// up through an interval or timezone mistake.
+pub fn is_night(time: DateTime<Utc>) -> bool {
+    NIGHT_HOURS.contains(&local_time(time).hour())
+}
+
+/// `start` moved by `offset` on the local wall clock, so whole days keep
+/// the same local time across DST changes. Wall-clock times skipped by a
+/// DST change move forward by the size of the gap. UTC, the zone without
+/// `init`, has no DST changes.
+pub fn add_wall_clock(start: DateTime<Utc>, offset: Duration) -> DateTime<Utc> {
+    let Some(zone) = ZONE.get() else {
+        return start + offset;
+    };
+    let wall_clock: NaiveDateTime = start.with_timezone(zone).naive_local() + offset;
+    zone.from_local_datetime(&wall_clock)
+        .earliest()
+        .or_else(|| {
+            zone.from_local_datetime(&(wall_clock + Duration::hours(1)))
+                .earliest()
+        })
+        .map(|local| local.with_timezone(&Utc))
+        .unwrap_or(start + offset)
+}
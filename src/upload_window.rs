@@ -0,0 +1,55 @@
+use chrono::{DateTime, Duration, NaiveTime, Timelike, Utc};
+
+use crate::timezone;
+
+/// Hours of the day uploads may start in, e.g. `01:00-07:00` for cheap
+/// night-time bandwidth. Times are in --timezone, UTC by default, and a
+/// window may wrap past midnight like `22:00-06:00`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UploadWindow {
+    start: NaiveTime,
+    end: NaiveTime,
+}
+
+impl UploadWindow {
+    pub fn parse(window: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let invalid = || format!("Invalid upload window '{}', expected HH:MM-HH:MM", window);
+        let (start, end) = window.split_once('-').ok_or_else(invalid)?;
+        let start = NaiveTime::parse_from_str(start.trim(), "%H:%M").map_err(|_| invalid())?;
+        let end = NaiveTime::parse_from_str(end.trim(), "%H:%M").map_err(|_| invalid())?;
+        if start == end {
+            return Err(format!("Upload window '{}' is empty", window).into());
+        }
+        Ok(Self { start, end })
+    }
+
+    pub fn contains(&self, time: DateTime<Utc>) -> bool {
+        let time = timezone::local_time(time);
+        if self.start < self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+
+    /// `time` when it is inside the window, otherwise the next time the
+    /// window opens.
+    pub fn next_open(&self, time: DateTime<Utc>) -> DateTime<Utc> {
+        if self.contains(time) {
+            return time;
+        }
+        let now = timezone::local_time(time)
+            .with_nanosecond(0)
+            .unwrap_or_default();
+        let mut wait = self.start - now;
+        if wait < Duration::zero() {
+            wait += Duration::days(1);
+        }
+        let time = time.with_nanosecond(0).unwrap_or(time);
+        if timezone::name().is_some() {
+            timezone::add_wall_clock(time, wait)
+        } else {
+            time + wait
+        }
+    }
+}
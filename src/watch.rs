@@ -0,0 +1,132 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::ffmpeg::is_audio_file;
+
+/// Subfolder of a watched folder holding the files each worker claimed
+pub const CLAIMED_DIR: &str = "claimed";
+
+const VIDEO_EXTENSIONS: [&str; 9] = [
+    "mp4", "mov", "mkv", "webm", "avi", "m4v", "mpg", "mpeg", "wmv",
+];
+
+/// Whether `path` looks like something worth uploading.
+pub fn is_media_file(path: &Path) -> bool {
+    let is_video = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .is_some_and(|ext| VIDEO_EXTENSIONS.contains(&ext.as_str()));
+    let hidden = path
+        .file_name()
+        .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+    !hidden && (is_video || is_audio_file(&path.to_string_lossy()))
+}
+
+/// Media files directly inside `dir`, sorted by name.
+pub fn scan_dir(dir: &str) -> io::Result<Vec<String>> {
+    let mut files: Vec<String> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && is_media_file(path))
+        .map(|path| path.to_string_lossy().to_string())
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+/// Returns why `path` isn't safe to upload yet: it was modified less than
+/// `min_age` ago, or some process still has it open for writing (e.g. an
+/// editor still exporting it).
+pub fn unstable_reason(path: &str, min_age: Duration) -> io::Result<Option<String>> {
+    let modified = fs::metadata(path)?.modified()?;
+    let age = SystemTime::now()
+        .duration_since(modified)
+        .unwrap_or(Duration::ZERO);
+    if age < min_age {
+        return Ok(Some(format!("modified {}s ago", age.as_secs())));
+    }
+    if open_for_writing(path) {
+        return Ok(Some("still open for writing".to_string()));
+    }
+    Ok(None)
+}
+
+/// Looks through every process's open files for `path` opened with write
+/// access. Processes we may not inspect are skipped.
+#[cfg(target_os = "linux")]
+pub fn open_for_writing(path: &str) -> bool {
+    const O_ACCMODE: u32 = 0o3;
+
+    let Ok(target) = fs::canonicalize(path) else {
+        return false;
+    };
+    let Ok(processes) = fs::read_dir("/proc") else {
+        return false;
+    };
+    processes.filter_map(|p| p.ok()).any(|process| {
+        let Ok(fds) = fs::read_dir(process.path().join("fd")) else {
+            return false;
+        };
+        fds.filter_map(|fd| fd.ok()).any(|fd| {
+            if fs::read_link(fd.path()).ok().as_ref() != Some(&target) {
+                return false;
+            }
+            let fdinfo = process.path().join("fdinfo").join(fd.file_name());
+            fs::read_to_string(fdinfo)
+                .ok()
+                .and_then(|info| {
+                    let flags = info.lines().find_map(|l| l.strip_prefix("flags:"))?;
+                    u32::from_str_radix(flags.trim(), 8).ok()
+                })
+                .is_some_and(|flags| flags & O_ACCMODE != 0)
+        })
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn open_for_writing(_path: &str) -> bool {
+    false
+}
+
+/// Name of this machine, used to keep the claims of several workers apart.
+pub fn hostname() -> String {
+    ["/proc/sys/kernel/hostname", "/etc/hostname"]
+        .iter()
+        .find_map(|path| {
+            fs::read_to_string(path)
+                .ok()
+                .map(|name| name.trim().to_string())
+                .filter(|name| !name.is_empty())
+        })
+        .or_else(|| std::env::var("HOSTNAME").ok())
+        .unwrap_or_else(|| "localhost".to_string())
+}
+
+/// Folder that files claimed by `worker` are moved into.
+pub fn claim_dir(folder: &str, worker: &str) -> PathBuf {
+    Path::new(folder).join(CLAIMED_DIR).join(worker)
+}
+
+/// Moves `file` into `claim_dir` so no other worker picks it up. Renaming
+/// within one file system is atomic, also on NFS, so when two workers race
+/// exactly one succeeds; the other gets `None`.
+pub fn claim(file: &str, claim_dir: &Path) -> io::Result<Option<String>> {
+    fs::create_dir_all(claim_dir)?;
+    let name = Path::new(file)
+        .file_name()
+        .ok_or_else(|| io::Error::other(format!("'{}' has no file name", file)))?;
+    let claimed = claim_dir.join(name);
+    if claimed.exists() {
+        return Err(io::Error::other(format!(
+            "'{}' was already claimed earlier",
+            claimed.display()
+        )));
+    }
+    match fs::rename(file, &claimed) {
+        Ok(()) => Ok(Some(claimed.to_string_lossy().to_string())),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
@@ -0,0 +1,57 @@
+use serde_json::Value;
+
+/// Time between two status checks of videos still processing
+pub const POLL_INTERVAL_SECS: u64 = 60;
+
+/// Failure reasons caused by the transfer or YouTube itself rather than
+/// the file, which uploading again can fix
+const TRANSIENT_REASONS: &[&str] = &[
+    "uploadAborted",
+    "uploadFailed",
+    "streamingFailed",
+    "transcodeFailed",
+];
+
+/// How far YouTube got with an uploaded video.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProcessingState {
+    Processing,
+    Processed,
+    /// Rejected, failed, terminated or deleted, with YouTube's reason
+    Failed {
+        reason: String,
+        transient: bool,
+    },
+}
+
+impl ProcessingState {
+    fn failed(problem: &str, reason: &Value) -> Self {
+        let reason = reason.as_str();
+        ProcessingState::Failed {
+            reason: format!("{}: {}", problem, reason.unwrap_or("no reason given")),
+            transient: reason.is_some_and(|reason| TRANSIENT_REASONS.contains(&reason)),
+        }
+    }
+}
+
+/// The state of `video`, a videos.list item with the `status` and
+/// `processingDetails` parts.
+pub fn processing_state(video: &Value) -> ProcessingState {
+    let status = &video["status"];
+    match status["uploadStatus"].as_str() {
+        Some("rejected") => return ProcessingState::failed("rejected", &status["rejectionReason"]),
+        Some("failed") => return ProcessingState::failed("failed", &status["failureReason"]),
+        Some("deleted") => return ProcessingState::failed("deleted", &Value::Null),
+        Some("processed") => return ProcessingState::Processed,
+        _ => {}
+    }
+    let details = &video["processingDetails"];
+    match details["processingStatus"].as_str() {
+        Some("succeeded") => ProcessingState::Processed,
+        Some("failed") => {
+            ProcessingState::failed("processing failed", &details["processingFailureReason"])
+        }
+        Some("terminated") => ProcessingState::failed("processing terminated", &Value::Null),
+        _ => ProcessingState::Processing,
+    }
+}
@@ -1,27 +1,46 @@
 use chrono::{DateTime, Duration, Utc};
-use oauth2::basic::BasicClient;
-use oauth2::reqwest::async_http_client;
-use oauth2::{
-    AuthUrl, AuthorizationCode, ClientId, ClientSecret, CsrfToken, PkceCodeChallenge, RedirectUrl,
-    Scope, TokenResponse, TokenUrl,
-};
-use rand::seq::SliceRandom;
+use clap::ValueEnum;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
-use std::fs::{self, File};
-use std::io::{self, BufRead, BufReader};
+use std::collections::HashMap;
+use std::fs;
 use std::path::Path;
-use youtube_scheduler::expand_tilde;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct OAuthConfig {
-    client_id: String,
-    client_secret: String,
-    redirect_uri: String,
+pub use crate::auth::{load_oauth_config, OAuthConfig};
+use crate::auth::{Authenticator, FileTokenStore, TokenStore, Tokens};
+use crate::chunks::{ChunkOptions, ChunkReader};
+use crate::config::Profile;
+use crate::control::CancellationToken;
+use crate::debug;
+use crate::description_pool::DescriptionPool;
+use crate::endpoints;
+use crate::fault::{self, FaultKind};
+use crate::ffmpeg::ThumbnailOffset;
+use crate::ffprobe::{ArchiveMode, ContainerTags};
+use crate::http_trace;
+use crate::journal::{self, Event};
+use crate::lint;
+use crate::sanitize::{apply_case_style, sanitize_description, sanitize_title, CaseStyle};
+use crate::series;
+use crate::simulator;
+use crate::template::render_template;
+use crate::timezone;
+use crate::watchdog::{processing_state, ProcessingState};
+
+/// License of a video, in the API's spelling.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum License {
+    /// Standard YouTube license
+    Youtube,
+    /// Creative Commons Attribution
+    CreativeCommon,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VideoMetadata {
     title: String,
     description: String,
@@ -29,13 +48,323 @@ pub struct VideoMetadata {
     category_id: String,
     pub privacy_status: String,
     pub scheduled_start_time: Option<String>,
+    #[serde(default)]
+    language: Option<String>,
+    #[serde(default)]
+    recording_date: Option<String>,
+    /// Where the auto-thumbnail is taken, e.g. `00:12:34` or `25%`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    thumbnail_at: Option<String>,
+    /// Image file set as the thumbnail, instead of an auto-thumbnail
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    thumbnail: Option<String>,
+    /// Series the video belongs to, see `series`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    series: Option<String>,
+    /// Episode number within the series, the next free one when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    episode: Option<u32>,
+    /// Title suffix from the profile's A/B rotation
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    title_variant: Option<String>,
+    /// Audience declared to YouTube, left to the channel default when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    made_for_kids: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    license: Option<License>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct StoredTokens {
-    access_token: String,
-    refresh_token: Option<String>,
-    expires_at: Option<DateTime<Utc>>,
+impl VideoMetadata {
+    /// Metadata with the given title and description, everything else from
+    /// `profile`.
+    pub fn new(title: &str, description: &str, profile: &Profile) -> Self {
+        Self {
+            title: title.to_string(),
+            description: description.to_string(),
+            tags: profile.tags.clone(),
+            category_id: profile.category_id.clone(),
+            privacy_status: profile.privacy_status.clone(),
+            scheduled_start_time: None,
+            language: profile.language.clone(),
+            recording_date: None,
+            thumbnail_at: None,
+            thumbnail: None,
+            series: profile.series.clone(),
+            episode: None,
+            title_variant: None,
+            made_for_kids: profile.made_for_kids,
+            license: profile.license,
+        }
+    }
+
+    pub fn language(&self) -> Option<&str> {
+        self.language.as_deref()
+    }
+
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    pub fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    pub fn thumbnail_at(&self) -> Option<&str> {
+        self.thumbnail_at.as_deref()
+    }
+
+    pub fn series(&self) -> Option<&str> {
+        self.series.as_deref()
+    }
+
+    pub fn episode(&self) -> Option<u32> {
+        self.episode
+    }
+
+    /// Makes this episode `number` of its series, prefixing the title.
+    pub fn number_episode(&mut self, number: u32) {
+        if let Some(series) = &self.series {
+            self.title = series::episode_title(series, number, &self.title);
+            self.episode = Some(number);
+        }
+    }
+
+    pub fn title_variant(&self) -> Option<&str> {
+        self.title_variant.as_deref()
+    }
+
+    /// Appends the A/B title suffix `suffix` and remembers it for the
+    /// upload history.
+    pub fn apply_title_variant(&mut self, suffix: &str) {
+        let suffix = suffix.trim();
+        if !suffix.is_empty() {
+            self.title = format!("{} {}", self.title, suffix);
+        }
+        self.title_variant = Some(suffix.to_string());
+    }
+
+    pub fn thumbnail(&self) -> Option<&str> {
+        self.thumbnail.as_deref()
+    }
+
+    /// Replaces the thumbnail file, e.g. with a version fixed to fit
+    /// YouTube's limits.
+    pub fn set_thumbnail_file(&mut self, path: &str) {
+        self.thumbnail = Some(path.to_string());
+    }
+
+    /// Whether the description has placeholders to fill in after the
+    /// upload.
+    pub fn has_post_upload_placeholders(&self) -> bool {
+        POST_UPLOAD_PLACEHOLDERS
+            .iter()
+            .any(|name| self.description.contains(&format!("{{{}}}", name)))
+    }
+
+    /// Fills in `{publish_date}` and `{publish_time}`, in the configured
+    /// timezone, and `{episode}` in the title and description once the
+    /// schedule is known.
+    pub fn resolve_schedule(&mut self, publish_at: DateTime<Utc>) {
+        let mut values = HashMap::from([
+            (
+                "publish_date",
+                timezone::local_date(publish_at)
+                    .format("%Y-%m-%d")
+                    .to_string(),
+            ),
+            (
+                "publish_time",
+                timezone::local_time(publish_at).format("%H:%M").to_string(),
+            ),
+        ]);
+        if let Some(episode) = self.episode {
+            values.insert("episode", episode.to_string());
+        }
+        self.title = sanitize_title(&render_template(&self.title, &values));
+        self.description = sanitize_description(&render_template(&self.description, &values));
+    }
+
+    /// Fills in the placeholders known once the video is uploaded.
+    /// `{playlist_url}` stays without a playlist.
+    pub fn resolve_post_upload(&mut self, video_id: &str, playlist_id: Option<&str>) {
+        let mut values = HashMap::from([
+            ("video_id", video_id.to_string()),
+            ("video_url", format!("https://youtu.be/{}", video_id)),
+        ]);
+        if let Some(playlist_id) = playlist_id {
+            values.insert(
+                "playlist_url",
+                format!("https://www.youtube.com/playlist?list={}", playlist_id),
+            );
+        }
+        self.description = sanitize_description(&render_template(&self.description, &values));
+    }
+
+    /// The video resource sent to videos.insert.
+    pub fn to_api_json(&self) -> serde_json::Value {
+        json!({
+            "snippet": {
+                "title": self.title,
+                "description": self.description,
+                "tags": self.tags,
+                "categoryId": self.category_id,
+                "defaultLanguage": self.language,
+                "defaultAudioLanguage": self.language
+            },
+            "status": {
+                "privacyStatus": self.privacy_status,
+                "publishAt": self.scheduled_start_time,
+                "selfDeclaredMadeForKids": self.made_for_kids,
+                "license": self.license
+            },
+            "recordingDetails": {
+                "recordingDate": self.recording_date
+            }
+        })
+    }
+
+    /// Cleans title and description so YouTube accepts them as-is.
+    pub fn sanitize(&mut self) {
+        self.title = sanitize_title(&self.title);
+        self.description = sanitize_description(&self.description);
+    }
+
+    /// Every lint rule violated by this video's metadata.
+    pub fn lint(&self) -> Vec<String> {
+        let mut issues = lint::check_title(&self.title);
+        issues.extend(lint::check_description(&self.description));
+        issues.extend(lint::check_tags(&self.tags));
+        issues.extend(lint::check_category(&self.category_id));
+        issues.extend(lint::check_privacy(&self.privacy_status));
+        issues.extend(lint::check_publish_at(
+            &self.privacy_status,
+            self.scheduled_start_time.as_deref(),
+        ));
+        if let Some(language) = &self.language {
+            issues.extend(lint::check_language(language));
+        }
+        if let Some(recording_date) = &self.recording_date {
+            issues.extend(lint::check_recording_date(recording_date));
+        }
+        if let Some(Err(e)) = self.thumbnail_at.as_deref().map(ThumbnailOffset::parse) {
+            issues.push(e.to_string());
+        }
+        issues
+    }
+
+    /// Makes a scheduled video private, the only privacy YouTube schedules.
+    /// Returns what was changed.
+    pub fn fix_publish_privacy(&mut self) -> Option<String> {
+        if self.scheduled_start_time.is_none() || self.privacy_status == "private" {
+            return None;
+        }
+        let fix = format!(
+            "privacy_status '{}' changed to private, YouTube only publishes private videos at scheduled_start_time",
+            self.privacy_status
+        );
+        self.privacy_status = "private".to_string();
+        Some(fix)
+    }
+
+    /// Overrides generated defaults with tags found in the container.
+    pub fn apply_container_tags(&mut self, tags: &ContainerTags) {
+        if let Some(title) = &tags.title {
+            self.title = title.clone();
+        }
+        if let Some(description) = &tags.description {
+            self.description = description.clone();
+        }
+        if let Some(date) = &tags.date {
+            self.recording_date = Some(date.to_rfc3339());
+        }
+    }
+
+    pub fn made_for_kids(&self) -> Option<bool> {
+        self.made_for_kids
+    }
+
+    pub fn license(&self) -> Option<License> {
+        self.license
+    }
+
+    /// Takes audience and license from `profile` where the metadata file
+    /// left them out.
+    pub fn apply_profile_settings(&mut self, profile: &Profile) {
+        self.made_for_kids = self.made_for_kids.or(profile.made_for_kids);
+        self.license = self.license.or(profile.license);
+    }
+
+    /// Replaces audience, license and category with the ones given on the
+    /// command line.
+    pub fn override_settings(
+        &mut self,
+        made_for_kids: Option<bool>,
+        license: Option<License>,
+        category_id: Option<&str>,
+    ) {
+        self.made_for_kids = made_for_kids.or(self.made_for_kids);
+        self.license = license.or(self.license);
+        if let Some(category_id) = category_id {
+            self.category_id = category_id.to_string();
+        }
+    }
+
+    /// Dates a re-uploaded archive video: sets the recording date to
+    /// `original` unless one is set already, and with `ArchiveMode::Note`
+    /// starts the description with when it was recorded.
+    pub fn apply_archive_date(&mut self, original: DateTime<Utc>, mode: ArchiveMode) {
+        let recorded = self
+            .recording_date
+            .as_deref()
+            .and_then(|date| DateTime::parse_from_rfc3339(date).ok())
+            .map_or(original, |date| date.with_timezone(&Utc));
+        self.recording_date = Some(recorded.to_rfc3339());
+        if mode == ArchiveMode::Note {
+            let note = format!("Originally recorded on {}", recorded.format("%B %-d, %Y"));
+            self.description = if self.description.is_empty() {
+                note
+            } else {
+                format!("{}\n\n{}", note, self.description)
+            };
+        }
+    }
+
+    /// YouTube publishes vertical videos as Shorts when tagged #Shorts
+    pub fn is_short(&self) -> bool {
+        let tag = "#shorts";
+        self.title.to_lowercase().contains(tag) || self.description.to_lowercase().contains(tag)
+    }
+
+    pub fn append_description(&mut self, text: &str) {
+        if !self.description.is_empty() {
+            self.description.push_str("\n\n");
+        }
+        self.description.push_str(text);
+    }
+}
+
+/// How far an upload got, reported after every chunk.
+#[derive(Debug, Clone, Serialize)]
+pub struct UploadProgress {
+    pub bytes_sent: u64,
+    pub total: u64,
+    /// Average since the upload started, in bytes per second
+    pub rate: u64,
+    /// Seconds left at that rate
+    pub eta: Option<u64>,
+    /// Chunks sent again after a network or server error
+    pub chunk_retries: u32,
+}
+
+/// A YouTube channel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Channel {
+    pub id: String,
+    pub title: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -45,260 +374,1140 @@ pub struct UploadResponse {
     status: serde_json::Value,
 }
 
+#[derive(Debug)]
+pub enum UploadError {
+    QuotaExceeded,
+    UploadLimitExceeded,
+    LongUploadsRestricted,
+    InvalidTitle(String),
+    Duplicate(String),
+    /// The upload's cancellation token fired before it finished
+    Cancelled,
+    /// The access token was rejected, e.g. because the grant was revoked
+    Unauthorized(String),
+    Api {
+        status: u16,
+        message: String,
+    },
+}
+
+impl UploadError {
+    /// Maps a failed videos.insert response onto a known failure, falling
+    /// back to the raw API message when the reason isn't one we recognise.
+    pub fn from_response(status: u16, body: &str) -> Self {
+        let error_json: serde_json::Value = serde_json::from_str(body).unwrap_or_default();
+        let message = error_json["error"]["message"]
+            .as_str()
+            .unwrap_or(body)
+            .to_string();
+        let reason = error_json["error"]["errors"][0]["reason"]
+            .as_str()
+            .unwrap_or_default();
+
+        match reason {
+            "quotaExceeded" => UploadError::QuotaExceeded,
+            "uploadLimitExceeded" => UploadError::UploadLimitExceeded,
+            "longUploadsRestricted" => UploadError::LongUploadsRestricted,
+            "invalidTitle" => UploadError::InvalidTitle(message),
+            _ if status == 401 => UploadError::Unauthorized(message),
+            _ => UploadError::Api { status, message },
+        }
+    }
+}
+
+impl UploadError {
+    /// True when authenticating again may let the upload through
+    pub fn is_unauthorized(&self) -> bool {
+        matches!(self, UploadError::Unauthorized(_))
+    }
+
+    /// True when uploading the same file and metadata again can't succeed,
+    /// unlike network, server, authorization, quota and rate limit errors.
+    pub fn is_permanent(&self) -> bool {
+        match self {
+            UploadError::InvalidTitle(_) | UploadError::Duplicate(_) => true,
+            UploadError::Api { status, .. } => {
+                (400..500).contains(status) && ![401, 408, 429].contains(status)
+            }
+            _ => false,
+        }
+    }
+
+    /// True when no further uploads can succeed today
+    pub fn is_quota_exhausted(&self) -> bool {
+        matches!(
+            self,
+            UploadError::QuotaExceeded | UploadError::UploadLimitExceeded
+        )
+    }
+}
+
+impl std::fmt::Display for UploadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UploadError::QuotaExceeded => write!(
+                f,
+                "The project's YouTube Data API quota is exhausted.\n  \
+                 Suggestion: quota resets at midnight Pacific Time, or request more in the Cloud console."
+            ),
+            UploadError::UploadLimitExceeded => write!(
+                f,
+                "The channel has reached its daily upload limit.\n  \
+                 Suggestion: wait 24 hours before retrying, or spread the batch over several days."
+            ),
+            UploadError::LongUploadsRestricted => write!(
+                f,
+                "The channel is not allowed to upload videos longer than 15 minutes.\n  \
+                 Suggestion: verify the account at https://www.youtube.com/verify and retry."
+            ),
+            UploadError::InvalidTitle(message) => write!(
+                f,
+                "YouTube rejected the title: {}\n  \
+                 Suggestion: keep titles under 100 characters and avoid '<' and '>'.",
+                message
+            ),
+            UploadError::Cancelled => write!(f, "Upload cancelled"),
+            UploadError::Unauthorized(message) => write!(
+                f,
+                "YouTube rejected the access token: {}\n  \
+                 Suggestion: authorize again, e.g. after revoking access in the Google account.",
+                message
+            ),
+            UploadError::Duplicate(id) => write!(
+                f,
+                "YouTube rejected the video as a duplicate of an existing upload (ID: {}).\n  \
+                 Suggestion: remove the file from the batch or delete the earlier upload.",
+                id
+            ),
+            UploadError::Api { status, message } => {
+                write!(f, "Upload failed ({}): {}", status, message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for UploadError {}
+
+/// Whether `error` broke the connection rather than the request, so the
+/// upload session is still there to resume.
+fn is_connection_error(error: &reqwest::Error) -> bool {
+    error.is_connect() || error.is_request() || error.is_body() || error.is_timeout()
+}
+
+/// Bytes a session received from its `Range` header, e.g. `bytes=0-999`
+/// for 1000.
+pub fn received_bytes(range: &str) -> Option<u64> {
+    let last = range.trim().strip_prefix("bytes=")?.rsplit('-').next()?;
+    Some(last.parse::<u64>().ok()? + 1)
+}
+
+/// Fails the request about to be sent to `endpoint` when --inject-failure
+/// says so, with the error the real failure would produce.
+fn inject_fault(endpoint: &str) -> Result<(), Box<dyn std::error::Error>> {
+    match fault::roll() {
+        None => Ok(()),
+        Some(FaultKind::Network) => {
+            Err(format!("Injected network failure calling {}", endpoint).into())
+        }
+        Some(FaultKind::Quota) => Err(UploadError::QuotaExceeded.into()),
+        Some(FaultKind::Status(status)) => Err(UploadError::Api {
+            status,
+            message: format!("Injected failure calling {}", endpoint),
+        }
+        .into()),
+    }
+}
+
 pub struct YouTubeUploader {
     client: Client,
+    /// Sends upload data over HTTP/3 until it fails once
+    quic: Option<Client>,
+    quic_failed: AtomicBool,
     access_token: String,
-    oauth_client: BasicClient,
-    client_id: String,
-    client_secret: String,
+    auth: Authenticator,
+    upload_base: String,
+    api_base: String,
+    chunks: ChunkOptions,
 }
 
+const UPLOAD_SCOPE: &str = "https://www.googleapis.com/auth/youtube.upload";
+pub const CALENDAR_SCOPE: &str = "https://www.googleapis.com/auth/calendar.events";
+pub const READONLY_SCOPE: &str = "https://www.googleapis.com/auth/youtube.readonly";
+/// Needed by videos.update and playlistItems.insert, which the upload
+/// scope does not cover
+pub const MANAGE_SCOPE: &str = "https://www.googleapis.com/auth/youtube";
+/// Needed by commentThreads.insert and captions.insert
+pub const COMMENT_SCOPE: &str = "https://www.googleapis.com/auth/youtube.force-ssl";
+/// Quota units YouTube charges for videos.insert
+pub const UPLOAD_QUOTA_COST: u64 = 1600;
+/// Quota units YouTube charges for captions.insert
+pub const CAPTION_QUOTA_COST: u64 = 400;
+/// Quota units YouTube charges for videos.update
+pub const UPDATE_QUOTA_COST: u64 = 50;
+/// Description placeholders that only get a value once the video is
+/// uploaded, filled in with a videos.update afterwards
+pub const POST_UPLOAD_PLACEHOLDERS: [&str; 3] = ["video_id", "video_url", "playlist_url"];
+/// How often a failed chunk is sent again before the upload fails
+const CHUNK_RETRIES: u32 = 3;
+/// How long a lost connection may take to come back before the upload
+/// fails, e.g. while switching from Wi-Fi to Ethernet
+const RECONNECT_WINDOW_SECS: u64 = 600;
+/// Longest wait between attempts to reach a session again
+const RECONNECT_MAX_DELAY_SECS: u64 = 30;
+
 impl YouTubeUploader {
     pub fn new(oauth_config: &OAuthConfig) -> Result<Self, Box<dyn std::error::Error>> {
-        let oauth_client = BasicClient::new(
-            ClientId::new(oauth_config.client_id.clone()),
-            Some(ClientSecret::new(oauth_config.client_secret.clone())),
-            AuthUrl::new("https://accounts.google.com/o/oauth2/v2/auth".to_string())?,
-            Some(TokenUrl::new(
-                "https://oauth2.googleapis.com/token".to_string(),
-            )?),
-        )
-        .set_redirect_uri(RedirectUrl::new(oauth_config.redirect_uri.clone())?);
+        Self::with_token_store(oauth_config, FileTokenStore::default())
+    }
+
+    /// Uploader keeping its tokens in `store` instead of the default file.
+    pub fn with_token_store(
+        oauth_config: &OAuthConfig,
+        store: impl TokenStore + 'static,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut auth = Authenticator::new(oauth_config, store)?;
+        auth.add_scope(UPLOAD_SCOPE);
+        let endpoints = endpoints::current();
 
         Ok(Self {
-            client: Client::new(),
+            client: endpoints::client()?,
+            quic: endpoints::upload_client()?,
+            quic_failed: AtomicBool::new(false),
             access_token: String::new(),
-            oauth_client,
-            client_id: oauth_config.client_id.clone(),
-            client_secret: oauth_config.client_secret.clone(),
+            auth,
+            upload_base: endpoints.upload_base_url,
+            api_base: endpoints.api_base_url,
+            chunks: ChunkOptions::default(),
         })
     }
 
+    /// Sets the chunk size and read-ahead of uploads.
+    pub fn set_chunk_options(&mut self, chunks: ChunkOptions) {
+        self.chunks = chunks;
+    }
+
+    /// Requests an additional scope the next time the user authorizes.
+    /// Tokens stored before the scope was added have to be re-authorized.
+    pub fn add_scope(&mut self, scope: &str) {
+        self.auth.add_scope(scope);
+    }
+
+    /// Refreshes the access token when it expires within `margin`.
+    pub fn set_refresh_margin(&mut self, margin: Duration) {
+        self.auth.set_refresh_margin(margin);
+    }
+
     pub async fn authenticate(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        // Try to load existing tokens
-        if let Ok(tokens) = self.load_tokens() {
-            if let Some(expires_at) = tokens.expires_at {
-                if expires_at > Utc::now() + Duration::minutes(5) {
-                    // Token is still valid
-                    self.access_token = tokens.access_token;
-                    println!("Using existing valid token");
-                    return Ok(());
+        if simulator::base_url().is_some() {
+            self.access_token = "simulated".to_string();
+            journal::record(Event::Authenticated {
+                method: "simulated".to_string(),
+            });
+            return Ok(());
+        }
+
+        let (access_token, source) = self.auth.access_token().await?;
+        self.access_token = access_token;
+        journal::record(Event::Authenticated {
+            method: source.to_string(),
+        });
+        Ok(())
+    }
+
+    /// The stored tokens without refreshing them.
+    pub async fn stored_tokens(&self) -> Result<Option<Tokens>, Box<dyn std::error::Error>> {
+        if simulator::base_url().is_some() {
+            return Ok(None);
+        }
+        self.auth.stored_tokens().await
+    }
+
+    /// Scopes granted to the current access token.
+    pub async fn granted_scopes(&self) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        if simulator::base_url().is_some() {
+            return Ok(self.auth.scopes().to_vec());
+        }
+        self.auth.granted_scopes(&self.access_token).await
+    }
+
+    /// The channel the access token belongs to.
+    pub async fn my_channel(&self) -> Result<Channel, Box<dyn std::error::Error>> {
+        let channels = self
+            .api_get(
+                &format!("{}/youtube/v3/channels", self.api_base),
+                &[("part", "snippet"), ("mine", "true")],
+            )
+            .await?;
+        let channel = &channels["items"][0];
+        Ok(Channel {
+            id: channel["id"]
+                .as_str()
+                .ok_or("The account has no YouTube channel")?
+                .to_string(),
+            title: channel["snippet"]["title"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string(),
+        })
+    }
+
+    /// Authenticates with a new access token after the API rejected the
+    /// current one, authorizing again when the grant itself is gone.
+    pub async fn reauthenticate(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if simulator::base_url().is_none() {
+            self.auth.expire_access_token().await?;
+        }
+        self.authenticate().await
+    }
+
+    /// Uploads a video, see `upload_video_with_progress`.
+    pub async fn upload_video(
+        &self,
+        video_path: &str,
+        metadata: &VideoMetadata,
+        cancel: &CancellationToken,
+    ) -> Result<UploadResponse, Box<dyn std::error::Error>> {
+        self.upload_video_with_progress(video_path, metadata, cancel, &|_| {})
+            .await
+    }
+
+    /// Uploads a video through a resumable session in chunks, 8 MiB unless
+    /// set otherwise, calling `on_progress` after each one. The file is read
+    /// ahead in the background as far as the chunk options allow. Chunks failing with a network or
+    /// server error are sent again, and after a lost connection the upload
+    /// continues from the byte the session reports once the network is
+    /// back. Cancelling `cancel` aborts the transfer and cancels the session.
+    pub async fn upload_video_with_progress(
+        &self,
+        video_path: &str,
+        metadata: &VideoMetadata,
+        cancel: &CancellationToken,
+        on_progress: &(dyn Fn(&UploadProgress) + Send + Sync),
+    ) -> Result<UploadResponse, Box<dyn std::error::Error>> {
+        if cancel.is_cancelled() {
+            return Err(UploadError::Cancelled.into());
+        }
+
+        let total = tokio::fs::metadata(video_path).await?.len();
+        debug!("Uploading {} bytes from {}", total, video_path);
+        let session = self
+            .create_upload_session(&metadata.to_api_json(), Some(total))
+            .await?;
+
+        let started = Instant::now();
+        let mut progress = UploadProgress {
+            bytes_sent: 0,
+            total,
+            rate: 0,
+            eta: None,
+            chunk_retries: 0,
+        };
+        let mut reader = ChunkReader::start(video_path, 0, total, self.chunks);
+        loop {
+            let start = progress.bytes_sent;
+            let chunk = match reader.next().await {
+                Some(chunk) => chunk?,
+                None => return Err(format!("{} ended before byte {}", video_path, total).into()),
+            };
+            let range = if chunk.is_empty() {
+                format!("bytes */{}", total)
+            } else {
+                format!(
+                    "bytes {}-{}/{}",
+                    start,
+                    start + chunk.len() as u64 - 1,
+                    total
+                )
+            };
+
+            let mut retries = 0;
+            // Set when the session was asked for its offset after the
+            // connection broke, rather than answering the chunk
+            let mut resumed = false;
+            let response = loop {
+                let quic = self
+                    .quic
+                    .as_ref()
+                    .filter(|_| !self.quic_failed.load(Ordering::Relaxed));
+                let request = match quic {
+                    Some(client) => client.put(&session).version(reqwest::Version::HTTP_3),
+                    None => self.client.put(&session),
+                };
+                let request =
+                    http_trace::send(request.header("Content-Range", &range).body(chunk.clone()));
+                let result = tokio::select! {
+                    result = request => result,
+                    _ = cancel.cancelled() => {
+                        self.cancel_upload_session(&session).await;
+                        return Err(UploadError::Cancelled.into());
+                    }
+                };
+                match result {
+                    // The session takes the chunk again over TCP
+                    Err(e) if quic.is_some() => {
+                        crate::warning!(
+                            "HTTP/3 upload failed at byte {} ({}), continuing without it",
+                            start,
+                            e
+                        );
+                        self.quic_failed.store(true, Ordering::Relaxed);
+                    }
+                    Ok(response) if !response.status().is_server_error() => break response,
+                    Ok(response) if retries == CHUNK_RETRIES => break response,
+                    // The session outlives the connection, e.g. a network
+                    // change resets it, so continue where YouTube stopped
+                    Err(e) if is_connection_error(&e) => {
+                        crate::warning!(
+                            "Connection lost at byte {} of {} ({}), resuming once the network is back",
+                            start,
+                            total,
+                            e
+                        );
+                        resumed = true;
+                        break self.resume_upload_session(&session, total, cancel).await?;
+                    }
+                    Err(e) if retries == CHUNK_RETRIES => return Err(e.into()),
+                    result => {
+                        retries += 1;
+                        progress.chunk_retries += 1;
+                        debug!(
+                            "Chunk at byte {} failed ({}), retry {}/{}",
+                            start,
+                            result.map_or_else(|e| e.to_string(), |r| r.status().to_string()),
+                            retries,
+                            CHUNK_RETRIES
+                        );
+                        tokio::time::sleep(std::time::Duration::from_secs(retries as u64)).await;
+                    }
+                }
+            };
+
+            let status = response.status().as_u16();
+            if status == 308 {
+                // YouTube reports what it has, which may be less than was
+                // sent. A session asked after nothing arrived has no range
+                let received = response
+                    .headers()
+                    .get("Range")
+                    .and_then(|range| received_bytes(range.to_str().ok()?));
+                progress.bytes_sent = match (received, resumed) {
+                    (Some(received), _) => received,
+                    (None, true) => 0,
+                    (None, false) => start + chunk.len() as u64,
+                };
+                if resumed {
+                    crate::info!(
+                        "Connection back, resuming {} at byte {}",
+                        video_path,
+                        progress.bytes_sent
+                    );
                 }
+                if progress.bytes_sent != start + chunk.len() as u64 {
+                    // The chunks read ahead start at the wrong byte now
+                    reader =
+                        ChunkReader::start(video_path, progress.bytes_sent, total, self.chunks);
+                }
+                let elapsed = started.elapsed().as_secs_f64().max(0.001);
+                progress.rate = (progress.bytes_sent as f64 / elapsed) as u64;
+                progress.eta =
+                    (progress.rate > 0).then(|| (total - progress.bytes_sent) / progress.rate);
+                on_progress(&progress);
+                continue;
             }
 
-            // Try to refresh the token
-            if let Some(refresh_token) = tokens.refresh_token {
-                if let Ok(new_tokens) = self.refresh_token(&refresh_token).await {
-                    self.access_token = new_tokens.access_token.clone();
-                    self.store_tokens(&new_tokens)?;
-                    println!("Refreshed access token");
-                    return Ok(());
+            if response.status().is_success() {
+                progress.bytes_sent = total;
+                progress.rate = (total as f64 / started.elapsed().as_secs_f64().max(0.001)) as u64;
+                progress.eta = Some(0);
+                on_progress(&progress);
+                let upload_response: UploadResponse = response.json().await?;
+                if upload_response.status["uploadStatus"] == "rejected"
+                    && upload_response.status["rejectionReason"] == "duplicate"
+                {
+                    return Err(UploadError::Duplicate(upload_response.id).into());
                 }
+                return Ok(upload_response);
             }
+
+            let error_text = response.text().await?;
+            journal::record(Event::ApiError {
+                endpoint: "videos.insert".to_string(),
+                status,
+                message: error_text.clone(),
+            });
+            return Err(UploadError::from_response(status, &error_text).into());
         }
+    }
 
-        // Perform full OAuth flow
-        self.perform_oauth_flow().await?;
-        Ok(())
+    /// Starts a resumable upload and returns the session URL the chunks are
+    /// sent to. Without a `size` YouTube waits for data until cancelled.
+    async fn create_upload_session(
+        &self,
+        metadata_json: &serde_json::Value,
+        size: Option<u64>,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        inject_fault("videos.insert")?;
+        let mut request = self
+            .client
+            .post(format!("{}/upload/youtube/v3/videos", self.upload_base))
+            .query(&[
+                ("uploadType", "resumable"),
+                ("part", "snippet,status,recordingDetails"),
+            ])
+            .bearer_auth(&self.access_token)
+            .header("X-Upload-Content-Type", "video/mp4")
+            .json(metadata_json);
+        if let Some(size) = size {
+            request = request.header("X-Upload-Content-Length", size);
+        }
+        let response = http_trace::send(request).await?;
+        if !response.status().is_success() {
+            let status = response.status().as_u16();
+            let error_text = response.text().await?;
+            journal::record(Event::ApiError {
+                endpoint: "videos.insert".to_string(),
+                status,
+                message: error_text.clone(),
+            });
+            return Err(UploadError::from_response(status, &error_text).into());
+        }
+        Ok(response
+            .headers()
+            .get("Location")
+            .and_then(|location| location.to_str().ok())
+            .ok_or("No upload session returned")?
+            .to_string())
+    }
+
+    /// Asks `session` how much of the `total` bytes arrived, retrying with
+    /// growing delays while the network is down. Answers 308 with the
+    /// received range, or the finished video when the last chunk made it
+    /// before the connection broke.
+    async fn resume_upload_session(
+        &self,
+        session: &str,
+        total: u64,
+        cancel: &CancellationToken,
+    ) -> Result<reqwest::Response, Box<dyn std::error::Error>> {
+        let lost = Instant::now();
+        let mut delay = 1;
+        loop {
+            tokio::select! {
+                _ = tokio::time::sleep(std::time::Duration::from_secs(delay)) => {}
+                _ = cancel.cancelled() => return Err(UploadError::Cancelled.into()),
+            }
+            let query = http_trace::send(
+                self.client
+                    .put(session)
+                    .header("Content-Range", format!("bytes */{}", total))
+                    .body(Vec::new()),
+            );
+            let result = tokio::select! {
+                result = query => result,
+                _ = cancel.cancelled() => {
+                    self.cancel_upload_session(session).await;
+                    return Err(UploadError::Cancelled.into());
+                }
+            };
+            match result {
+                Ok(response) if !response.status().is_server_error() => return Ok(response),
+                result if lost.elapsed().as_secs() >= RECONNECT_WINDOW_SECS => {
+                    let reason = result.map_or_else(|e| e.to_string(), |r| r.status().to_string());
+                    return Err(format!(
+                        "Connection did not come back within {} minutes: {}",
+                        RECONNECT_WINDOW_SECS / 60,
+                        reason
+                    )
+                    .into());
+                }
+                result => {
+                    debug!(
+                        "Upload session unreachable ({}), trying again in {}s",
+                        result.map_or_else(|e| e.to_string(), |r| r.status().to_string()),
+                        delay
+                    );
+                    delay = (delay * 2).min(RECONNECT_MAX_DELAY_SECS);
+                }
+            }
+        }
     }
 
-    async fn perform_oauth_flow(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        // Generate PKCE challenge
-        let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
+    /// Cancels a resumable session so YouTube drops the partial upload.
+    async fn cancel_upload_session(&self, session: &str) {
+        // Cancelling answers 499, which is the expected outcome here
+        if let Err(e) = http_trace::send(self.client.delete(session)).await {
+            debug!("Cancelling the upload session failed: {}", e);
+        }
+    }
+}
 
-        // Generate authorization URL
-        let (auth_url, _csrf_token) = self
-            .oauth_client
-            .authorize_url(CsrfToken::new_random)
-            .add_scope(Scope::new(
-                "https://www.googleapis.com/auth/youtube.upload".to_string(),
-            ))
-            .set_pkce_challenge(pkce_challenge)
-            .url();
-
-        println!("Open this URL in your browser to authenticate:");
-        println!("{}", auth_url);
-        println!("\nAfter authorization, you'll be redirected to your redirect URI.");
-        println!("Copy the 'code' parameter from the redirect URL and paste it here:");
-
-        // Get authorization code from user
-        let mut auth_code = String::new();
-        std::io::stdin().read_line(&mut auth_code)?;
-        let auth_code = auth_code.trim();
-
-        // Exchange authorization code for access token
-        let token_result = self
-            .oauth_client
-            .exchange_code(AuthorizationCode::new(auth_code.to_string()))
-            .set_pkce_verifier(pkce_verifier)
-            .request_async(async_http_client)
+impl YouTubeUploader {
+    /// Titles of the channel's most recent uploads, newest first.
+    pub async fn recent_upload_titles(
+        &self,
+        limit: usize,
+    ) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        Ok(self
+            .recent_uploads(limit)
+            .await?
+            .iter()
+            .filter_map(|snippet| snippet["title"].as_str())
+            .map(str::to_string)
+            .collect())
+    }
+
+    /// Future publish times of scheduled videos among the channel's most
+    /// recent uploads.
+    pub async fn scheduled_publish_times(
+        &self,
+        limit: usize,
+    ) -> Result<Vec<DateTime<Utc>>, Box<dyn std::error::Error>> {
+        let ids: Vec<String> = self
+            .recent_uploads(limit)
+            .await?
+            .iter()
+            .filter_map(|snippet| snippet["resourceId"]["videoId"].as_str())
+            .map(str::to_string)
+            .collect();
+
+        let mut publish_times = Vec::new();
+        for chunk in ids.chunks(50) {
+            let videos = self
+                .api_get(
+                    &format!("{}/youtube/v3/videos", self.api_base),
+                    &[("part", "status"), ("id", &chunk.join(","))],
+                )
+                .await?;
+            publish_times.extend(
+                videos["items"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|video| video["status"]["publishAt"].as_str())
+                    .filter_map(|at| DateTime::parse_from_rfc3339(at).ok())
+                    .map(|at| at.with_timezone(&Utc))
+                    .filter(|at| *at > Utc::now()),
+            );
+        }
+        publish_times.sort();
+        Ok(publish_times)
+    }
+
+    /// How far processing got for each of the videos `ids`. Videos no
+    /// longer found count as failed.
+    pub async fn processing_states(
+        &self,
+        ids: &[String],
+    ) -> Result<HashMap<String, ProcessingState>, Box<dyn std::error::Error>> {
+        let mut states = HashMap::new();
+        for chunk in ids.chunks(50) {
+            let videos = self
+                .api_get(
+                    &format!("{}/youtube/v3/videos", self.api_base),
+                    &[
+                        ("part", "status,processingDetails"),
+                        ("id", &chunk.join(",")),
+                    ],
+                )
+                .await?;
+            for video in videos["items"].as_array().into_iter().flatten() {
+                if let Some(id) = video["id"].as_str() {
+                    states.insert(id.to_string(), processing_state(video));
+                }
+            }
+        }
+        for id in ids {
+            states
+                .entry(id.clone())
+                .or_insert_with(|| ProcessingState::Failed {
+                    reason: "not found on the channel".to_string(),
+                    transient: false,
+                });
+        }
+        Ok(states)
+    }
+
+    /// Snippets of the channel's most recent uploads, newest first.
+    async fn recent_uploads(
+        &self,
+        limit: usize,
+    ) -> Result<Vec<serde_json::Value>, Box<dyn std::error::Error>> {
+        let channels: serde_json::Value = self
+            .api_get(
+                &format!("{}/youtube/v3/channels", self.api_base),
+                &[("part", "contentDetails"), ("mine", "true")],
+            )
             .await?;
+        let uploads = channels["items"][0]["contentDetails"]["relatedPlaylists"]["uploads"]
+            .as_str()
+            .ok_or("No uploads playlist found for this channel")?
+            .to_string();
+
+        let mut snippets = Vec::new();
+        let mut page_token = String::new();
+        while snippets.len() < limit {
+            let page: serde_json::Value = self
+                .api_get(
+                    &format!("{}/youtube/v3/playlistItems", self.api_base),
+                    &[
+                        ("part", "snippet"),
+                        ("playlistId", &uploads),
+                        ("maxResults", "50"),
+                        ("pageToken", &page_token),
+                    ],
+                )
+                .await?;
+            snippets.extend(
+                page["items"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .map(|item| item["snippet"].clone()),
+            );
+            match page["nextPageToken"].as_str() {
+                Some(token) => page_token = token.to_string(),
+                None => break,
+            }
+        }
+        snippets.truncate(limit);
+        Ok(snippets)
+    }
 
-        // Store tokens
-        let expires_at = token_result
-            .expires_in()
-            .map(|duration| Utc::now() + Duration::seconds(duration.as_secs() as i64));
+    /// Changes the privacy status of an uploaded video with videos.update.
+    pub async fn set_privacy(
+        &self,
+        video_id: &str,
+        privacy: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let request = self
+            .client
+            .put(format!("{}/youtube/v3/videos", self.api_base))
+            .query(&[("part", "status")])
+            .json(&json!({ "id": video_id, "status": { "privacyStatus": privacy } }));
+        self.api_write("videos.update", request).await?;
+        Ok(())
+    }
 
-        let tokens = StoredTokens {
-            access_token: token_result.access_token().secret().clone(),
-            refresh_token: token_result.refresh_token().map(|t| t.secret().clone()),
-            expires_at,
-        };
+    /// Deletes an uploaded video with videos.delete.
+    pub async fn delete_video(&self, video_id: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let request = self
+            .client
+            .delete(format!("{}/youtube/v3/videos", self.api_base))
+            .query(&[("id", video_id)]);
+        self.api_write("videos.delete", request).await?;
+        Ok(())
+    }
 
-        self.access_token = tokens.access_token.clone();
-        self.store_tokens(&tokens)?;
+    /// Replaces title, description, tags and language of an uploaded video
+    /// with videos.update.
+    pub async fn update_snippet(
+        &self,
+        video_id: &str,
+        metadata: &VideoMetadata,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let request = self
+            .client
+            .put(format!("{}/youtube/v3/videos", self.api_base))
+            .query(&[("part", "snippet")])
+            .json(&json!({ "id": video_id, "snippet": metadata.to_api_json()["snippet"] }));
+        self.api_write("videos.update", request).await?;
+        Ok(())
+    }
 
-        println!("Authentication successful!");
+    /// Posts a top-level comment on a video with commentThreads.insert.
+    pub async fn post_comment(
+        &self,
+        video_id: &str,
+        text: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let request = self
+            .client
+            .post(format!("{}/youtube/v3/commentThreads", self.api_base))
+            .query(&[("part", "snippet")])
+            .json(&json!({
+                "snippet": {
+                    "videoId": video_id,
+                    "topLevelComment": { "snippet": { "textOriginal": text } }
+                }
+            }));
+        self.api_write("commentThreads.insert", request).await?;
         Ok(())
     }
 
-    async fn refresh_token(
+    /// Appends a video to a playlist with playlistItems.insert.
+    pub async fn add_to_playlist(
         &self,
-        refresh_token: &str,
-    ) -> Result<StoredTokens, Box<dyn std::error::Error>> {
-        let params = [
-            ("grant_type", "refresh_token"),
-            ("refresh_token", refresh_token),
-            ("client_id", &self.client_id),
-            ("client_secret", &self.client_secret),
-        ];
-
-        let response = self
+        playlist_id: &str,
+        video_id: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let request = self
             .client
-            .post("https://oauth2.googleapis.com/token")
-            .form(&params)
-            .send()
-            .await?;
+            .post(format!("{}/youtube/v3/playlistItems", self.api_base))
+            .query(&[("part", "snippet")])
+            .json(&json!({
+                "snippet": {
+                    "playlistId": playlist_id,
+                    "resourceId": { "kind": "youtube#video", "videoId": video_id }
+                }
+            }));
+        self.api_write("playlistItems.insert", request).await?;
+        Ok(())
+    }
 
-        let token_data: serde_json::Value = response.json().await?;
+    /// ID of the channel's playlist titled `title`, if there is one.
+    pub async fn find_playlist(
+        &self,
+        title: &str,
+    ) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        let url = format!("{}/youtube/v3/playlists", self.api_base);
+        let mut page_token = String::new();
+        loop {
+            let mut query = vec![("part", "snippet"), ("mine", "true"), ("maxResults", "50")];
+            if !page_token.is_empty() {
+                query.push(("pageToken", &page_token));
+            }
+            let page = self.api_get(&url, &query).await?;
+            let found = page["items"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .find(|item| item["snippet"]["title"].as_str() == Some(title))
+                .and_then(|item| item["id"].as_str());
+            if let Some(id) = found {
+                return Ok(Some(id.to_string()));
+            }
+            match page["nextPageToken"].as_str() {
+                Some(token) => page_token = token.to_string(),
+                None => return Ok(None),
+            }
+        }
+    }
 
-        let access_token = token_data["access_token"]
+    /// Creates a public playlist with playlists.insert and returns its ID.
+    pub async fn create_playlist(
+        &self,
+        title: &str,
+        description: &str,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let request = self
+            .client
+            .post(format!("{}/youtube/v3/playlists", self.api_base))
+            .query(&[("part", "snippet,status")])
+            .json(&json!({
+                "snippet": { "title": title, "description": description },
+                "status": { "privacyStatus": "public" }
+            }));
+        let playlist = self.api_write("playlists.insert", request).await?;
+        playlist["id"]
             .as_str()
-            .ok_or("No access token in response")?
-            .to_string();
+            .map(str::to_string)
+            .ok_or_else(|| "playlists.insert returned no playlist ID".into())
+    }
 
-        let expires_in = token_data["expires_in"].as_u64().unwrap_or(3600);
-        let expires_at = Some(Utc::now() + Duration::seconds(expires_in as i64));
+    /// Replaces title and description of a playlist with playlists.update.
+    pub async fn update_playlist(
+        &self,
+        playlist_id: &str,
+        title: &str,
+        description: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let request = self
+            .client
+            .put(format!("{}/youtube/v3/playlists", self.api_base))
+            .query(&[("part", "snippet")])
+            .json(&json!({
+                "id": playlist_id,
+                "snippet": { "title": title, "description": description }
+            }));
+        self.api_write("playlists.update", request).await?;
+        Ok(())
+    }
 
-        Ok(StoredTokens {
-            access_token,
-            refresh_token: Some(refresh_token.to_string()),
-            expires_at,
-        })
+    /// Sets a JPEG as the custom thumbnail of a video with thumbnails.set.
+    pub async fn set_thumbnail(
+        &self,
+        video_id: &str,
+        image: &Path,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let request = self
+            .client
+            .post(format!(
+                "{}/upload/youtube/v3/thumbnails/set",
+                self.upload_base
+            ))
+            .query(&[("videoId", video_id)])
+            .header("Content-Type", "image/jpeg")
+            .body(tokio::fs::read(image).await?);
+        self.api_write("thumbnails.set", request).await?;
+        Ok(())
     }
 
-    fn store_tokens(&self, tokens: &StoredTokens) -> Result<(), Box<dyn std::error::Error>> {
-        let tokens_path = expand_tilde("~/.youtube_tokens.json");
-        let tokens_json = serde_json::to_string_pretty(tokens)?;
-        fs::write(&tokens_path, tokens_json)?;
+    /// Adds the caption track in `file` to `video_id` with captions.insert.
+    pub async fn upload_caption(
+        &self,
+        video_id: &str,
+        language: &str,
+        file: &Path,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        const BOUNDARY: &str = "youtube_scheduler_caption";
+        let snippet = json!({
+            "snippet": {
+                "videoId": video_id,
+                "language": language,
+                "name": ""
+            }
+        });
+        let mut body = format!(
+            "--{b}\r\nContent-Type: application/json; charset=UTF-8\r\n\r\n{}\r\n\
+             --{b}\r\nContent-Type: application/octet-stream\r\n\r\n",
+            snippet,
+            b = BOUNDARY
+        )
+        .into_bytes();
+        body.extend(tokio::fs::read(file).await?);
+        body.extend(format!("\r\n--{}--\r\n", BOUNDARY).into_bytes());
+        let request = self
+            .client
+            .post(format!("{}/upload/youtube/v3/captions", self.upload_base))
+            .query(&[("part", "snippet"), ("uploadType", "multipart")])
+            .header(
+                "Content-Type",
+                format!("multipart/related; boundary={}", BOUNDARY),
+            )
+            .body(body);
+        self.api_write("captions.insert", request).await?;
         Ok(())
     }
 
-    fn load_tokens(&self) -> Result<StoredTokens, Box<dyn std::error::Error>> {
-        let tokens_path = expand_tilde("~/.youtube_tokens.json");
-        let tokens_json = fs::read_to_string(&tokens_path)?;
-        let tokens: StoredTokens = serde_json::from_str(&tokens_json)?;
-        Ok(tokens)
+    /// Sends a request changing the channel, recording failures under
+    /// `endpoint`. Returns the resource YouTube answered with.
+    async fn api_write(
+        &self,
+        endpoint: &str,
+        request: reqwest::RequestBuilder,
+    ) -> Result<serde_json::Value, Box<dyn std::error::Error>> {
+        inject_fault(endpoint)?;
+        let response = http_trace::send(request.bearer_auth(&self.access_token)).await?;
+        if response.status().is_success() {
+            return Ok(serde_json::from_str(&response.text().await?).unwrap_or_default());
+        }
+        let status = response.status().as_u16();
+        let error_text = response.text().await?;
+        journal::record(Event::ApiError {
+            endpoint: endpoint.to_string(),
+            status,
+            message: error_text.clone(),
+        });
+        Err(UploadError::from_response(status, &error_text).into())
     }
 
-    pub async fn upload_video(
+    async fn api_get(
         &self,
-        video_path: &str,
-        metadata: &VideoMetadata,
-    ) -> Result<UploadResponse, Box<dyn std::error::Error>> {
-        // Read video file
-        let video_data = fs::read(video_path)?;
+        url: &str,
+        query: &[(&str, &str)],
+    ) -> Result<serde_json::Value, Box<dyn std::error::Error>> {
+        inject_fault(url)?;
+        let response = http_trace::send(
+            self.client
+                .get(url)
+                .query(query)
+                .bearer_auth(&self.access_token),
+        )
+        .await?;
+        let status = response.status().as_u16();
+        if !response.status().is_success() {
+            let error_text = response.text().await?;
+            if status == 403 && error_text.contains("insufficient") {
+                return Err(
+                    "Access denied. Delete ~/.youtube_tokens.json and authorize \
+                            again to grant the youtube.readonly scope."
+                        .into(),
+                );
+            }
+            journal::record(Event::ApiError {
+                endpoint: url.to_string(),
+                status,
+                message: error_text.clone(),
+            });
+            return Err(UploadError::from_response(status, &error_text).into());
+        }
+        Ok(response.json().await?)
+    }
+}
 
-        // Combine snippet and status into a single JSON object
+impl YouTubeUploader {
+    /// Measures upload throughput by sending `chunks` chunks of
+    /// `chunk_size` bytes into a resumable upload session that is cancelled
+    /// afterwards, so no video is created. Returns the time each chunk took.
+    pub async fn bench_upload(
+        &self,
+        chunks: u64,
+        chunk_size: u64,
+    ) -> Result<Vec<std::time::Duration>, Box<dyn std::error::Error>> {
         let metadata_json = json!({
-            "snippet": {
-                "title": metadata.title,
-                "description": metadata.description,
-                "tags": metadata.tags,
-                "categoryId": metadata.category_id
-            },
-            "status": {
-                "privacyStatus": metadata.privacy_status,
-                "publishAt": metadata.scheduled_start_time
-            }
+            "snippet": { "title": "Upload benchmark" },
+            "status": { "privacyStatus": "private" }
         });
+        let session = self.create_upload_session(&metadata_json, None).await?;
 
-        // Create multipart form with only 2 parts: metadata and media
-        let form = reqwest::multipart::Form::new()
-            .part(
-                "snippet",
-                reqwest::multipart::Part::text(metadata_json.to_string())
-                    .mime_str("application/json")?,
+        let chunk = vec![0u8; chunk_size as usize];
+        let mut timings = Vec::new();
+        for i in 0..chunks {
+            let start = i * chunk_size;
+            let started = std::time::Instant::now();
+            // The total stays unknown, so YouTube waits for more data
+            let response = http_trace::send(
+                self.client
+                    .put(&session)
+                    .header(
+                        "Content-Range",
+                        format!("bytes {}-{}/*", start, start + chunk_size - 1),
+                    )
+                    .body(chunk.clone()),
             )
-            .part(
-                "media",
-                reqwest::multipart::Part::bytes(video_data)
-                    .file_name("video.mp4")
-                    .mime_str("video/mp4")?,
-            );
-
-        let response = self
-            .client
-            .post("https://www.googleapis.com/upload/youtube/v3/videos")
-            .query(&[("part", "snippet,status")])
-            .header("Authorization", format!("Bearer {}", self.access_token))
-            .multipart(form)
-            .send()
             .await?;
+            if response.status().as_u16() != 308 {
+                let status = response.status().as_u16();
+                let error_text = response.text().await?;
+                return Err(UploadError::from_response(status, &error_text).into());
+            }
+            timings.push(started.elapsed());
+            debug!("Chunk {} took {:?}", i + 1, started.elapsed());
+        }
+
+        self.cancel_upload_session(&session).await;
+        Ok(timings)
+    }
+
+    /// Creates or updates the calendar event for an uploaded video. The event
+    /// ID is derived from the video ID, so rescheduling a video moves its
+    /// existing event instead of adding a second one.
+    pub async fn upsert_calendar_event(
+        &self,
+        calendar_id: &str,
+        video_id: &str,
+        metadata: &VideoMetadata,
+        publish_at: DateTime<Utc>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        // Event IDs only allow base32hex characters, hex is a safe subset
+        let event_id: String = video_id.bytes().map(|b| format!("{:02x}", b)).collect();
+        let video_url = format!("https://youtu.be/{}", video_id);
+        let event = json!({
+            "id": event_id,
+            "summary": format!("▶ {}", metadata.title),
+            "description": video_url,
+            "start": { "dateTime": publish_at.to_rfc3339() },
+            "end": { "dateTime": (publish_at + Duration::minutes(15)).to_rfc3339() },
+            "source": { "title": "YouTube", "url": video_url }
+        });
+
+        let events_url = format!(
+            "{}/calendar/v3/calendars/{}/events",
+            self.api_base,
+            encode_segment(calendar_id)
+        );
+        inject_fault("calendar events")?;
+        let response = http_trace::send(
+            self.client
+                .put(format!("{}/{}", events_url, event_id))
+                .bearer_auth(&self.access_token)
+                .json(&event),
+        )
+        .await?;
+
+        let response = if response.status() == reqwest::StatusCode::NOT_FOUND {
+            http_trace::send(
+                self.client
+                    .post(&events_url)
+                    .bearer_auth(&self.access_token)
+                    .json(&event),
+            )
+            .await?
+        } else {
+            response
+        };
 
         if response.status().is_success() {
-            let upload_response: UploadResponse = response.json().await?;
-            Ok(upload_response)
+            Ok(())
+        } else if response.status() == reqwest::StatusCode::FORBIDDEN {
+            Err(
+                "Calendar access denied. Delete ~/.youtube_tokens.json and authorize \
+                 again to grant the calendar scope."
+                    .into(),
+            )
         } else {
+            let status = response.status().as_u16();
             let error_text = response.text().await?;
-            Err(format!("Upload failed: {}", error_text).into())
+            journal::record(Event::ApiError {
+                endpoint: "calendar.events".to_string(),
+                status,
+                message: error_text.clone(),
+            });
+            Err(format!("Calendar update failed ({}): {}", status, error_text).into())
         }
     }
 }
 
+fn encode_segment(segment: &str) -> String {
+    url::form_urlencoded::byte_serialize(segment.as_bytes()).collect()
+}
+
 pub fn create_default_metadata(
     video_files: &[String],
-    description_file: &str,
+    mut description_pool: Option<&mut DescriptionPool>,
+    title_case: CaseStyle,
+    profile: &Profile,
 ) -> Vec<VideoMetadata> {
-    let expanded_path = expand_tilde(description_file);
     video_files
         .iter()
-        .map(|file_path| {
+        .enumerate()
+        .map(|(position, file_path)| {
             let filename = Path::new(file_path)
                 .file_stem()
                 .unwrap_or_default()
                 .to_string_lossy()
                 .to_string();
+            let title = apply_case_style(&filename, title_case);
+            let pool_line = description_pool
+                .as_deref_mut()
+                .map(DescriptionPool::next_description)
+                .unwrap_or_default();
+
+            let description = match &profile.description_template {
+                Some(template) => {
+                    let values = HashMap::from([
+                        ("title", title.clone()),
+                        ("file", filename.clone()),
+                        ("stem", filename.clone()),
+                        ("index", (position + 1).to_string()),
+                        ("pool", pool_line),
+                    ]);
+                    render_template(template, &values)
+                }
+                None => pool_line,
+            };
 
             VideoMetadata {
-                title: filename.to_string(),
-                description: get_random_line(&expanded_path).unwrap_or_default(),
-                tags: vec!["gaming".to_string()],
-                category_id: "20".to_string(), // GAMING
-                privacy_status: "private".to_string(),
+                title,
+                description,
+                tags: profile.tags.clone(),
+                category_id: profile.category_id.clone(),
+                privacy_status: profile.privacy_status.clone(),
                 scheduled_start_time: None,
+                language: profile.language.clone(),
+                recording_date: None,
+                thumbnail_at: None,
+                thumbnail: None,
+                series: profile.series.clone(),
+                episode: None,
+                title_variant: None,
+                made_for_kids: profile.made_for_kids,
+                license: profile.license,
             }
         })
         .collect()
 }
-fn get_random_line(path: &str) -> io::Result<String> {
-    let expanded_path = expand_tilde(path);
-    let file = File::open(expanded_path)?;
-    let reader = BufReader::new(file);
-    let lines: Vec<String> = reader.lines().collect::<io::Result<Vec<String>>>()?;
-
-    if lines.is_empty() {
-        return Err(io::Error::new(
-            io::ErrorKind::Other,
-            "No lines found in the file.",
-        ));
-    }
 
-    let mut rng = rand::thread_rng();
-    let random_line = lines.choose(&mut rng).unwrap().to_string();
-
-    Ok(random_line)
-}
 pub fn load_video_metadata(
     metadata_path: &str,
 ) -> Result<Vec<VideoMetadata>, Box<dyn std::error::Error>> {
@@ -306,16 +1515,3 @@ pub fn load_video_metadata(
     let metadata: Vec<VideoMetadata> = serde_json::from_str(&content)?;
     Ok(metadata)
 }
-
-pub fn load_oauth_config(config_path: &str) -> Result<OAuthConfig, Box<dyn std::error::Error>> {
-    let expanded_path = expand_tilde(config_path);
-    let content = fs::read_to_string(&expanded_path).map_err(|e| {
-        format!(
-            "Failed to read OAuth config from '{}': {}",
-            expanded_path, e
-        )
-    })?;
-    let config: OAuthConfig = serde_json::from_str(&content)
-        .map_err(|e| format!("Failed to parse OAuth config: {}", e))?;
-    Ok(config)
-}